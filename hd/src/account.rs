@@ -14,6 +14,7 @@
 
 //! Module implements LNPBP-32 tracking account type
 
+use std::collections::BTreeSet;
 use std::fmt::{self, Display, Formatter};
 use std::str::FromStr;
 
@@ -21,14 +22,17 @@ use bitcoin::secp256k1::{self, Secp256k1, Signing, Verification};
 use bitcoin::util::bip32::{
     self, ChildNumber, DerivationPath, ExtendedPrivKey, ExtendedPubKey, Fingerprint, KeySource,
 };
-use bitcoin::{OutPoint, XpubIdentifier};
+use bitcoin::{Address, Network, OutPoint, Script, XOnlyPublicKey, XpubIdentifier};
+#[cfg(feature = "miniscript")]
+use miniscript::descriptor::{DescriptorPublicKey, DescriptorXKey, Wildcard};
 #[cfg(feature = "miniscript")]
 use miniscript::MiniscriptKey;
 use slip132::FromSlip132;
 
 use crate::{
-    AccountStep, DerivationSubpath, DerivePatternError, DerivePublicKey, HardenedIndex,
-    SegmentIndexes, TerminalStep, UnhardenedIndex, XpubRef,
+    AccountStep, Bip43, DerivationStandard, DerivationSubpath, DeriveError, DerivePatternError,
+    DerivePublicKey, HardenedIndex, SegmentIndexes, TerminalStep, UnhardenedIndex, XpubRef,
+    HARDENED_INDEX_BOUNDARY,
 };
 
 /// Errors during tracking acocunt parsing
@@ -56,6 +60,142 @@ pub enum ParseError {
     /// incorrect xpub revocation seal `{0}`; the seal must be a valid bitcoin
     /// transaction outpoint in format of `txid:vout`.
     RevocationSeal(String),
+
+    /// unsupported or malformed single-key descriptor `{0}`; the descriptor
+    /// must have one of `pkh(KEY)`, `sh(wpkh(KEY))`, `wpkh(KEY)` or `tr(KEY)`
+    /// forms.
+    InvalidDescriptor(String),
+
+    /// account xpub has depth {depth}, which is less than the length
+    /// {path_len} of its origin derivation path.
+    OriginDepthMismatch {
+        /// Depth of the account xpub
+        depth: u8,
+        /// Number of steps in the account derivation path
+        path_len: usize,
+    },
+
+    /// origin key fingerprint {declared} does not match fingerprint {expected}
+    /// derived from the account xpub.
+    OriginFingerprintMismatch {
+        /// Fingerprint declared in the origin information
+        declared: Fingerprint,
+        /// Fingerprint deduced from the account xpub
+        expected: Fingerprint,
+    },
+
+    /// last origin derivation step {declared} does not match account xpub
+    /// child number {expected}.
+    OriginChildMismatch {
+        /// Last step of the account derivation path
+        declared: ChildNumber,
+        /// Child number of the account xpub
+        expected: ChildNumber,
+    },
+
+    /// requested maximum derivation depth {requested} exceeds the depth limit
+    /// {max} of BIP-32 extended keys.
+    MaxDepthUnsupported {
+        /// Requested maximum derivation depth
+        requested: usize,
+        /// Maximum derivation depth supported by BIP-32 extended keys
+        max: usize,
+    },
+
+    /// Errors in derivation path
+    #[display(inner)]
+    #[from]
+    DerivePattern(DerivePatternError),
+
+    /// {error} (at characters {start}..{end})
+    Spanned {
+        /// Error which happened while parsing the string fragment
+        error: Box<ParseError>,
+        /// Byte offset of the start of the invalid fragment
+        start: usize,
+        /// Byte offset of the end of the invalid fragment (exclusive)
+        end: usize,
+    },
+}
+
+impl ParseError {
+    /// Returns byte offsets of the start and end (exclusive) of the invalid
+    /// fragment of the parsed string, if known.
+    pub fn span(&self) -> Option<(usize, usize)> {
+        match self {
+            ParseError::Spanned { start, end, .. } => Some((*start, *end)),
+            _ => None,
+        }
+    }
+
+    fn with_span(self, start: usize, end: usize) -> ParseError {
+        match self {
+            ParseError::Spanned { .. } => self,
+            error => ParseError::Spanned {
+                error: Box::new(error),
+                start,
+                end,
+            },
+        }
+    }
+}
+
+/// Errors merging accounts into a single multipath account (see
+/// [`DerivationAccount::try_merge`]).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum MergeError {
+    /// no accounts were provided for merging.
+    NoAccounts,
+
+    /// accounts differ in their origin, account key or in more than a single
+    /// step of the terminal derivation path.
+    Mismatch,
+
+    /// accounts already contain multipath steps in their terminal derivation
+    /// paths.
+    NestedMultipath,
+
+    /// merged accounts do not use distinct consecutive indexes at the
+    /// differing terminal derivation path step.
+    NonConsecutiveBranches,
+}
+
+/// Default maximum depth of derivation path for [`DerivationAccount`] parsing
+/// and key derivation. Since BIP32 extended keys encode their depth with a
+/// single byte, deeper keys can't be represented.
+pub const MAX_DERIVATION_DEPTH: usize = u8::MAX as usize;
+
+/// Maximum number of accounts produced by
+/// [`DerivationAccount::split_multipath`]. Since multipath steps share their
+/// representation with index ranges (like `{0-1000}`), this prevents splitting
+/// large ranges into a huge number of accounts.
+pub const MAX_MULTIPATH_BRANCHES: usize = 256;
+
+/// Description of an address derived from [`DerivationAccount`], mirroring
+/// the information provided by Bitcoin Core `getaddressinfo` RPC command.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct AddressInfo {
+    /// Derived address
+    pub address: Address,
+
+    /// `scriptPubkey` of the derived address
+    pub script_pubkey: Script,
+
+    /// Derived public key
+    pub pubkey: secp256k1::PublicKey,
+
+    /// Fingerprint of the master key (or of the account key, if the master
+    /// key is not known)
+    pub master_fingerprint: Fingerprint,
+
+    /// Full derivation path from the master key (or from the account key, if
+    /// the master key is not known)
+    pub derivation_path: DerivationPath,
+
+    /// Key origin in Bitcoin Core format `fingerprint/path`, like
+    /// `73c5da0a/84'/0'/0'/0/1`
+    pub hd_key_path: String,
 }
 
 /// HD wallet account guaranteeing key derivation without access to the
@@ -121,6 +261,170 @@ impl DerivationAccount {
         }
     }
 
+    /// Constructs tracking account out of a bare extended public key with no
+    /// information about its origin (i.e. unknown master key and empty
+    /// account derivation path).
+    ///
+    /// The terminal path is set to a single wildcard step (`/*`), such that
+    /// the account derives keys directly as children of the provided xpub.
+    pub fn from_xpub(account_xpub: ExtendedPubKey) -> DerivationAccount {
+        DerivationAccount {
+            master: XpubRef::Unknown,
+            account_path: empty!(),
+            account_xpub,
+            revocation_seal: None,
+            terminal_path: DerivationSubpath::from(&[TerminalStep::Wildcard][..]),
+        }
+    }
+
+    /// Constructs watch-only receive and change accounts (with `/0/*` and
+    /// `/1/*` terminal paths) for the account-level extended public key
+    /// derived according to the `scheme` at the `account_index` from the
+    /// master key with `master_fingerprint`. The account origin is set to the
+    /// scheme account derivation path for the `network` (like
+    /// `84h/0h/0h` for BIP-84 on mainnet).
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`DeriveError::InconsistentKeyNetwork`] if the xpub
+    /// network does not match `network` and with
+    /// [`DeriveError::UnsupportedScheme`] for the schemes not defining coin
+    /// type and account levels (BIP-45).
+    pub fn watch_only(
+        master_fingerprint: Fingerprint,
+        account_xpub: ExtendedPubKey,
+        scheme: Bip43,
+        account_index: HardenedIndex,
+        network: Network,
+    ) -> Result<(DerivationAccount, DerivationAccount), DeriveError> {
+        if (account_xpub.network == Network::Bitcoin) != (network == Network::Bitcoin) {
+            return Err(DeriveError::InconsistentKeyNetwork);
+        }
+        if scheme.account_depth().is_none() {
+            return Err(DeriveError::UnsupportedScheme(scheme));
+        }
+        let account_path = scheme
+            .to_account_derivation(account_index.into(), network.into())
+            .into_iter()
+            .copied()
+            .map(AccountStep::try_from)
+            .collect::<Result<_, _>>()
+            .expect("account derivation path of a standard scheme");
+        let account = |branch: u8| DerivationAccount {
+            master: XpubRef::Fingerprint(master_fingerprint),
+            account_path: DerivationSubpath::clone(&account_path),
+            account_xpub,
+            revocation_seal: None,
+            terminal_path: vec![TerminalStep::from(branch), TerminalStep::Wildcard].into(),
+        };
+        Ok((account(0), account(1)))
+    }
+
+    /// Splits account with a multipath terminal step (like `/<0;1>/*`) into a
+    /// list of accounts, one per each of the indexes from the multipath step,
+    /// where the multipath step is replaced with that specific index (like
+    /// `/0/*` and `/1/*`). Accounts without multipath steps are returned as a
+    /// single-element list.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`DeriveError::NestedMultipath`] if the terminal path
+    /// contains more than a single multipath step, and with
+    /// [`DeriveError::MultipathTooLarge`] if the multipath step covers more
+    /// than [`MAX_MULTIPATH_BRANCHES`] indexes.
+    pub fn split_multipath(&self) -> Result<Vec<DerivationAccount>, DeriveError> {
+        let mut multipath = self
+            .terminal_path
+            .iter()
+            .enumerate()
+            .filter(|(_, step)| matches!(step, TerminalStep::Range(_)));
+        let (pos, step) = match (multipath.next(), multipath.next()) {
+            (None, _) => return Ok(vec![self.clone()]),
+            (Some(multipath), None) => multipath,
+            (Some(_), Some(_)) => return Err(DeriveError::NestedMultipath),
+        };
+        let count = step.count();
+        if count > MAX_MULTIPATH_BRANCHES {
+            return Err(DeriveError::MultipathTooLarge {
+                count,
+                max: MAX_MULTIPATH_BRANCHES,
+            });
+        }
+        Ok(step
+            .indexes()
+            .map(|index| {
+                let mut branch = self.clone();
+                branch.terminal_path[pos] = TerminalStep::Index(index);
+                branch
+            })
+            .collect())
+    }
+
+    /// Merges accounts which differ only in a single index of their terminal
+    /// paths (like `/0/*` and `/1/*`) into a single account with a multipath
+    /// step at that position (like `/<0;1>/*`). This is the reverse of
+    /// [`DerivationAccount::split_multipath`]. A single account is returned
+    /// as-is.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`MergeError::Mismatch`] if the accounts differ in anything
+    /// but a single terminal step index, with
+    /// [`MergeError::NonConsecutiveBranches`] if the indexes at the differing
+    /// step are not distinct consecutive numbers, and with
+    /// [`MergeError::NestedMultipath`] if the accounts already have multipath
+    /// steps.
+    pub fn try_merge(accounts: &[DerivationAccount]) -> Result<DerivationAccount, MergeError> {
+        let (first, rest) = accounts.split_first().ok_or(MergeError::NoAccounts)?;
+        if first
+            .terminal_path
+            .iter()
+            .any(|step| matches!(step, TerminalStep::Range(_)))
+        {
+            return Err(MergeError::NestedMultipath);
+        }
+        if rest.is_empty() {
+            return Ok(first.clone());
+        }
+        if !rest.iter().all(|account| {
+            account.master == first.master
+                && account.account_path == first.account_path
+                && account.account_xpub == first.account_xpub
+                && account.revocation_seal == first.revocation_seal
+                && account.terminal_path.len() == first.terminal_path.len()
+        }) {
+            return Err(MergeError::Mismatch);
+        }
+        let mut differing = (0..first.terminal_path.len()).filter(|pos| {
+            rest.iter()
+                .any(|account| account.terminal_path[*pos] != first.terminal_path[*pos])
+        });
+        let pos = match (differing.next(), differing.next()) {
+            (Some(pos), None) => pos,
+            (None, _) => return Err(MergeError::NonConsecutiveBranches),
+            (Some(_), Some(_)) => return Err(MergeError::Mismatch),
+        };
+        let mut indexes = accounts
+            .iter()
+            .map(|account| match account.terminal_path[pos] {
+                TerminalStep::Index(index) => Ok(index),
+                TerminalStep::Range(_) => Err(MergeError::NestedMultipath),
+                TerminalStep::Wildcard => Err(MergeError::Mismatch),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        indexes.sort();
+        if indexes
+            .windows(2)
+            .any(|pair| pair[0].first_index() + 1 != pair[1].first_index())
+        {
+            return Err(MergeError::NonConsecutiveBranches);
+        }
+        let mut account = first.clone();
+        account.terminal_path[pos] =
+            TerminalStep::set(indexes).expect("distinct indexes form disjoint ranges");
+        Ok(account)
+    }
+
     /// Detects if the tracking account is seed-based
     pub fn seed_based(&self) -> bool { self.master != XpubRef::Unknown }
 
@@ -157,32 +461,61 @@ impl DerivationAccount {
             .map(|fp| (fp, self.to_account_derivation_path()))
     }
 
+    /// Detects whether both derivation accounts belong to the same wallet
+    /// account, i.e. have the same master key fingerprint, account derivation
+    /// path and account extended public key. Terminal paths (like receive
+    /// `/0/*` and change `/1/*` branches) are ignored.
+    pub fn same_account(&self, other: &DerivationAccount) -> bool {
+        self.master_fingerprint() == other.master_fingerprint()
+            && self.to_account_derivation_path() == other.to_account_derivation_path()
+            && self.account_xpub == other.account_xpub
+    }
+
+    /// Returns depth of the keys derived from the account, i.e. the depth of
+    /// the account xpub (or the length of the account derivation path, if it
+    /// is larger) plus the length of the terminal path.
+    #[inline]
+    pub fn derivation_depth(&self) -> usize {
+        (self.account_xpub.depth as usize).max(self.account_path.len()) + self.terminal_path.len()
+    }
+
+    /// Checks that [`DerivationAccount::derivation_depth`] does not exceed
+    /// `max_depth`.
+    pub fn check_depth(&self, max_depth: usize) -> Result<(), DerivePatternError> {
+        let depth = self.derivation_depth();
+        if depth > max_depth {
+            return Err(DerivePatternError::DepthExceeded {
+                depth,
+                max: max_depth,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns number of indexes in derive pattern required by the terminal
+    /// path, i.e. the number of its wildcard, range and multipath steps.
+    #[inline]
+    pub fn derive_pattern_len(&self) -> usize { derive_pattern_len(&self.terminal_path) }
+
     /// Constructs [`DerivationPath`] from the extended account key to the final
     /// keys. The path will include only unhardened indexes.
+    ///
+    /// Each of the variable terminal steps (wildcards, ranges and multipath
+    /// steps) takes the next index from the derive pattern `pat`, in order of
+    /// their appearance. Thus, a terminal path `/*/*` with pattern `[1, 2]`
+    /// gives `1/2` path, and `/<0;1>/*` with `[1, 2]` gives `1/2` as well.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the pattern length does not match
+    /// [`DerivationAccount::derive_pattern_len`] or if a pattern index is not
+    /// contained in the corresponding range step.
     pub fn to_terminal_derivation_path(
         &self,
         pat: impl AsRef<[UnhardenedIndex]>,
     ) -> Result<DerivationPath, DerivePatternError> {
-        let mut iter = pat.as_ref().iter();
-        // TODO: Convert into a method on TerminalPath type
-        self.terminal_path
-            .iter()
-            .map(|step| {
-                if step.count() == 1 {
-                    Ok(ChildNumber::Normal {
-                        index: step.first_index(),
-                    })
-                } else if let Some(index) = iter.next() {
-                    if !step.contains(index.first_index()) {
-                        Err(DerivePatternError)
-                    } else {
-                        Ok(ChildNumber::from(*index))
-                    }
-                } else {
-                    Err(DerivePatternError)
-                }
-            })
-            .collect()
+        self.check_depth(MAX_DERIVATION_DEPTH)?;
+        terminal_derivation_path(&self.terminal_path, pat.as_ref())
     }
 
     /// Constructs [`DerivationPath`] from the extneded master public key to the
@@ -200,6 +533,24 @@ impl DerivationAccount {
         Ok(derivation_path.into())
     }
 
+    /// Constructs [`KeySource`] for a key at some terminal derivation path,
+    /// consisting of the master key fingerprint (or account fingerprint, if the
+    /// master is not known) and the full derivation path.
+    ///
+    /// Unlike [`DerivationAccount::bip32_derivation`] this does not perform
+    /// any key derivation, so it can be used when only the master fingerprint
+    /// and the derivation paths are known.
+    pub fn key_source(
+        &self,
+        pat: impl AsRef<[UnhardenedIndex]>,
+    ) -> Result<KeySource, DerivePatternError> {
+        Ok((
+            self.master_fingerprint()
+                .unwrap_or_else(|| self.account_fingerprint()),
+            self.to_full_derivation_path(pat)?,
+        ))
+    }
+
     /// Extracts BIP32 derivation information for a specific public key derived
     /// at some terminal derivation path.
     ///
@@ -210,14 +561,236 @@ impl DerivationAccount {
         ctx: &Secp256k1<C>,
         pat: impl AsRef<[UnhardenedIndex]>,
     ) -> Result<(secp256k1::PublicKey, KeySource), DerivePatternError> {
-        Ok((
-            self.derive_public_key(ctx, &pat)?,
-            (
-                self.master_fingerprint()
-                    .unwrap_or_else(|| self.account_fingerprint()),
-                self.to_full_derivation_path(pat)?,
-            ),
-        ))
+        Ok((self.derive_public_key(ctx, &pat)?, self.key_source(pat)?))
+    }
+
+    /// Derives public key for the accounts with a single wildcard (or range)
+    /// terminal step, using `index` as the value for that step.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`DeriveError::HardenedIndex`] if `index` is not less than
+    /// [`HARDENED_INDEX_BOUNDARY`](crate::HARDENED_INDEX_BOUNDARY) and with
+    /// [`DeriveError::DerivePatternMismatch`] if the terminal path requires
+    /// more than a single index or does not contain `index`.
+    pub fn derive_at<C: Verification>(
+        &self,
+        ctx: &Secp256k1<C>,
+        index: u32,
+    ) -> Result<secp256k1::PublicKey, DeriveError> {
+        let index =
+            UnhardenedIndex::from_index(index).map_err(|_| DeriveError::HardenedIndex(index))?;
+        Ok(self.derive_public_key(ctx, [index])?)
+    }
+
+    /// Derives public key at some terminal derivation path and constructs
+    /// `scriptPubkey` matching the single-key script type of the provided
+    /// derivation `scheme` (P2PKH for BIP-44, P2WPKH-in-P2SH for BIP-49, P2WPKH
+    /// for BIP-84 and P2TR for BIP-86).
+    ///
+    /// For BIP-86 the script commits to the derived key tweaked without a
+    /// script tree, as required by the standard.
+    ///
+    /// Errors with [`DeriveError::UnsupportedScheme`] for multi-sig and custom
+    /// BIP-43 schemes.
+    pub fn script_pubkey<C: Verification>(
+        &self,
+        ctx: &Secp256k1<C>,
+        scheme: Bip43,
+        pat: impl AsRef<[UnhardenedIndex]>,
+    ) -> Result<Script, DeriveError> {
+        let pubkey = self.derive_public_key(ctx, pat)?;
+        Self::scheme_script_pubkey(ctx, scheme, pubkey)
+    }
+
+    fn scheme_script_pubkey<C: Verification>(
+        ctx: &Secp256k1<C>,
+        scheme: Bip43,
+        pubkey: secp256k1::PublicKey,
+    ) -> Result<Script, DeriveError> {
+        let pubkey = bitcoin::PublicKey::new(pubkey);
+        let wpubkey_hash = || {
+            pubkey
+                .wpubkey_hash()
+                .expect("secp256k1 public keys are always compressed")
+        };
+        Ok(match scheme {
+            Bip43::Bip44 => Script::new_p2pkh(&pubkey.pubkey_hash()),
+            Bip43::Bip49 => Script::new_p2sh(&Script::new_v0_p2wpkh(&wpubkey_hash()).script_hash()),
+            Bip43::Bip84 => Script::new_v0_p2wpkh(&wpubkey_hash()),
+            Bip43::Bip86 => Script::new_v1_p2tr(ctx, XOnlyPublicKey::from(pubkey.inner), None),
+            _ => return Err(DeriveError::UnsupportedScheme(scheme)),
+        })
+    }
+
+    /// Derives `scriptPubkey`s for the first `count` indexes of each of the
+    /// account branches (like receive and change branches of `/<0;1>/*`
+    /// terminal path, see [`DerivationAccount::split_multipath`]), for
+    /// matching against compact block filters. The script types are defined
+    /// by the derivation `scheme`, as in [`DerivationAccount::script_pubkey`].
+    ///
+    /// Extended public key at the fixed part of each branch path is derived
+    /// only once. Scripts repeating in multiple branches are returned once.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`DeriveError::DerivePatternMismatch`] if the terminal
+    /// path has steps other than fixed indexes followed by an optional
+    /// wildcard, besides a single multipath step.
+    pub fn scripts_for_filter<C: Verification>(
+        &self,
+        ctx: &Secp256k1<C>,
+        scheme: Bip43,
+        count: u32,
+    ) -> Result<Vec<Script>, DeriveError> {
+        let mut seen = BTreeSet::new();
+        let mut scripts = vec![];
+        for branch in self.split_multipath()? {
+            let (wildcard, steps) = match branch.terminal_path.split_last() {
+                Some((TerminalStep::Wildcard, steps)) => (true, steps),
+                _ => (false, &branch.terminal_path[..]),
+            };
+            let path = steps
+                .iter()
+                .map(|step| match step {
+                    TerminalStep::Index(index) => Ok(ChildNumber::from(*index)),
+                    _ => Err(DeriveError::DerivePatternMismatch),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let xpub = branch
+                .account_xpub
+                .derive_pub(ctx, &path)
+                .expect("unhardened derivation failure");
+            let pubkeys = if wildcard {
+                (0..count.min(HARDENED_INDEX_BOUNDARY))
+                    .map(|index| {
+                        xpub.ckd_pub(ctx, ChildNumber::Normal { index })
+                            .expect("unhardened derivation failure")
+                            .public_key
+                    })
+                    .collect()
+            } else {
+                vec![xpub.public_key]
+            };
+            for pubkey in pubkeys {
+                let script = Self::scheme_script_pubkey(ctx, scheme, pubkey)?;
+                if seen.insert(script.clone()) {
+                    scripts.push(script);
+                }
+            }
+        }
+        Ok(scripts)
+    }
+
+    /// Derives public key at some terminal derivation path and constructs
+    /// address for the provided `network` matching the single-key script type
+    /// of the derivation `scheme`. See [`DerivationAccount::script_pubkey`]
+    /// for the details on the supported script types.
+    pub fn address<C: Verification>(
+        &self,
+        ctx: &Secp256k1<C>,
+        scheme: Bip43,
+        pat: impl AsRef<[UnhardenedIndex]>,
+        network: Network,
+    ) -> Result<Address, DeriveError> {
+        let script = self.script_pubkey(ctx, scheme, pat)?;
+        Ok(Address::from_script(&script, network)
+            .expect("single-key BIP-43 scripts always have an address form"))
+    }
+
+    /// Derives address at `index` of the branch named by its `role`, which may
+    /// be either `receive` (or `external`) for branch 0 or `change` (or
+    /// `internal`) for branch 1. The branch is used for the terminal step
+    /// preceding the last one, which must be a wildcard or a range, like in
+    /// `/<0;1>/*` or `/*/*` terminal paths. See [`DerivationAccount::address`]
+    /// for the details on the supported script types.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`DeriveError::UnknownRole`] for other role names and with
+    /// [`DeriveError::DerivePatternMismatch`] if the terminal path has no
+    /// branch step, or if the branch step is a fixed index other than the
+    /// requested branch.
+    pub fn derive_address<C: Verification>(
+        &self,
+        ctx: &Secp256k1<C>,
+        scheme: Bip43,
+        network: Network,
+        role: &str,
+        index: u32,
+    ) -> Result<Address, DeriveError> {
+        let branch = match role {
+            "receive" | "external" => UnhardenedIndex::zero(),
+            "change" | "internal" => UnhardenedIndex::one(),
+            _ => return Err(DeriveError::UnknownRole(role.to_owned())),
+        };
+        let index =
+            UnhardenedIndex::from_index(index).map_err(|_| DeriveError::HardenedIndex(index))?;
+        let pat = match self
+            .terminal_path
+            .len()
+            .checked_sub(2)
+            .map(|i| &self.terminal_path[i])
+        {
+            Some(step) if step.count() > 1 => vec![branch, index],
+            Some(TerminalStep::Index(fixed)) if *fixed == branch => vec![index],
+            _ => return Err(DeriveError::DerivePatternMismatch),
+        };
+        self.address(ctx, scheme, pat, network)
+    }
+
+    /// Derives the first address of the account, using the first index of each
+    /// of the variable terminal steps (i.e. index 0 of the receive branch for
+    /// `/<0;1>/*` and `/0/*` terminal paths). The address is stable for a
+    /// given account and derivation scheme and may be used to recognize the
+    /// wallet. See [`DerivationAccount::address`] for the details on the
+    /// supported script types.
+    pub fn identity_address<C: Verification>(
+        &self,
+        ctx: &Secp256k1<C>,
+        scheme: Bip43,
+        network: Network,
+    ) -> Result<Address, DeriveError> {
+        let pat = self
+            .terminal_path
+            .iter()
+            .filter(|step| step.count() > 1)
+            .map(|step| {
+                UnhardenedIndex::from_index(step.first_index())
+                    .expect("terminal steps contain only unhardened indexes")
+            })
+            .collect::<Vec<_>>();
+        self.address(ctx, scheme, pat, network)
+    }
+
+    /// Derives public key at some terminal derivation path and describes the
+    /// address for it in the same way as Bitcoin Core `getaddressinfo` RPC
+    /// command does. See [`DerivationAccount::script_pubkey`] for the details
+    /// on the supported script types.
+    pub fn address_info<C: Verification>(
+        &self,
+        ctx: &Secp256k1<C>,
+        scheme: Bip43,
+        pat: impl AsRef<[UnhardenedIndex]>,
+        network: Network,
+    ) -> Result<AddressInfo, DeriveError> {
+        let (pubkey, (master_fingerprint, derivation_path)) = self.bip32_derivation(ctx, &pat)?;
+        let address = self.address(ctx, scheme, pat, network)?;
+        let hd_key_path =
+            derivation_path
+                .into_iter()
+                .fold(master_fingerprint.to_string(), |mut path, step| {
+                    path.push_str(&format!("/{}", step));
+                    path
+                });
+        Ok(AddressInfo {
+            script_pubkey: address.script_pubkey(),
+            address,
+            pubkey,
+            master_fingerprint,
+            derivation_path,
+            hd_key_path,
+        })
     }
 }
 
@@ -287,10 +860,111 @@ impl DerivationAccount {
         self.fmt_terminal_path(f)
     }
 
+    /// Checks that the key origin information (master key reference and
+    /// account derivation path) is consistent with the data embedded into the
+    /// account xpub. Only definite mismatches are reported:
+    /// - the account path can't be longer than the account xpub depth;
+    /// - the last account path step must match the xpub child number;
+    /// - a fingerprint referenced right at the account xpub (like a master key
+    ///   with an empty account path) must match the xpub fingerprint;
+    /// - a fingerprint referenced at the xpub parent (like a master key with a
+    ///   single-step account path) must match the xpub parent fingerprint.
+    ///
+    /// Other references can't be verified without the private key.
+    pub fn check_origin(&self) -> Result<(), ParseError> {
+        let xpub = &self.account_xpub;
+        let path_len = self.account_path.len();
+        if (xpub.depth as usize) < path_len {
+            return Err(ParseError::OriginDepthMismatch {
+                depth: xpub.depth,
+                path_len,
+            });
+        }
+        if let Some(step) = self.account_path.last() {
+            let declared = ChildNumber::from(step);
+            if declared != xpub.child_number {
+                return Err(ParseError::OriginChildMismatch {
+                    declared,
+                    expected: xpub.child_number,
+                });
+            }
+        }
+        // Fingerprint of the key after `pos` steps of the account path
+        let fingerprint_at = |pos: usize| match pos {
+            0 => self.master.fingerprint(),
+            pos => self.account_path[pos - 1]
+                .xpub_ref()
+                .and_then(|xpub_ref| xpub_ref.fingerprint()),
+        };
+        let mut checks = vec![(path_len, xpub.fingerprint())];
+        if path_len > 0 {
+            checks.push((path_len - 1, xpub.parent_fingerprint));
+        }
+        for (pos, expected) in checks {
+            match fingerprint_at(pos) {
+                Some(declared) if declared != expected => {
+                    return Err(ParseError::OriginFingerprintMismatch { declared, expected })
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses account in the same way as [`DerivationAccount::from_str`], but
+    /// with a stricter limit on the derivation depth (see
+    /// [`DerivationAccount::derivation_depth`]). The number of path segments
+    /// is checked before parsing, so strings with excessively long paths are
+    /// rejected without processing them.
+    ///
+    /// The limit is applied only while parsing and is not stored in the
+    /// account. It can only lower the default [`MAX_DERIVATION_DEPTH`], which
+    /// is always enforced by parsing and key derivation.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`ParseError::MaxDepthUnsupported`] if `max_depth` exceeds
+    /// [`MAX_DERIVATION_DEPTH`].
+    pub fn from_str_with_max_depth(
+        s: &str,
+        max_depth: usize,
+    ) -> Result<DerivationAccount, ParseError> {
+        if max_depth > MAX_DERIVATION_DEPTH {
+            return Err(ParseError::MaxDepthUnsupported {
+                requested: max_depth,
+                max: MAX_DERIVATION_DEPTH,
+            });
+        }
+        check_str_depth(s, max_depth)?;
+        let account = DerivationAccount::from_str(s)?;
+        account.check_depth(max_depth)?;
+        Ok(account)
+    }
+
+    /// Parses account in the same way as [`DerivationAccount::from_str`] and
+    /// verifies its key origin information with
+    /// [`DerivationAccount::check_origin`], detecting descriptors corrupted in
+    /// transit.
+    pub fn from_str_checked(s: &str) -> Result<DerivationAccount, ParseError> {
+        let account = DerivationAccount::from_str(s)?;
+        account.check_origin()?;
+        Ok(account)
+    }
+
     /// Parse from Bitcoin core representation:
     /// `[fp/hardened_path/account]xpub/unhardened_path`
     pub fn from_str_bitcoin_core(s: &str) -> Result<DerivationAccount, ParseError> {
-        let mut split = s.split('/');
+        check_str_depth(s, MAX_DERIVATION_DEPTH)?;
+        let mut offset = 0usize;
+        let mut split = s.split('/').map(|segment| {
+            let start = offset;
+            offset += segment.len() + 1;
+            (start, segment)
+        });
+        let span = |start: usize, fragment: &str| {
+            let end = start + fragment.len();
+            move |err: bip32::Error| ParseError::from(err).with_span(start, end)
+        };
         let mut account = DerivationAccount {
             master: XpubRef::Unknown,
             account_path: empty!(),
@@ -302,19 +976,36 @@ impl DerivationAccount {
             terminal_path: empty!(),
         };
         let mut xpub = None;
-        if let Some(first) = split.next() {
+        if let Some((start, first)) = split.next() {
             if first.starts_with('[') {
-                account.master = XpubRef::from_str(first.trim_start_matches('['))?;
-                for next in split.by_ref() {
-                    if let Some((index, xpub_str)) = next.split_once(']') {
-                        account.account_path.push(AccountStep::from_str(index)?);
-                        xpub = Some(ExtendedPubKey::from_str(xpub_str)?);
-                        break;
+                let master = first.trim_start_matches('[');
+                let start = start + first.len() - master.len();
+                if let Some((master, xpub_str)) = master.split_once(']') {
+                    // Origin without derivation path: `[fp]xpub`
+                    account.master = XpubRef::from_str(master).map_err(span(start, master))?;
+                    let start = start + master.len() + 1;
+                    xpub = Some(ExtendedPubKey::from_str(xpub_str).map_err(span(start, xpub_str))?);
+                } else {
+                    account.master = XpubRef::from_str(master).map_err(span(start, master))?;
+                    for (start, next) in split.by_ref() {
+                        if let Some((index, xpub_str)) = next.split_once(']') {
+                            account
+                                .account_path
+                                .push(AccountStep::from_str(index).map_err(span(start, index))?);
+                            let start = start + index.len() + 1;
+                            xpub = Some(
+                                ExtendedPubKey::from_str(xpub_str)
+                                    .map_err(span(start, xpub_str))?,
+                            );
+                            break;
+                        }
+                        account
+                            .account_path
+                            .push(AccountStep::from_str(next).map_err(span(start, next))?);
                     }
-                    account.account_path.push(AccountStep::from_str(next)?);
                 }
             } else {
-                xpub = Some(ExtendedPubKey::from_str(first)?);
+                xpub = Some(ExtendedPubKey::from_str(first).map_err(span(start, first))?);
             }
         }
 
@@ -324,16 +1015,20 @@ impl DerivationAccount {
             return Err(ParseError::AccountXpubAbsent(s.to_owned()));
         }
 
-        for next in split {
-            account.terminal_path.push(TerminalStep::from_str(next)?);
+        for (start, next) in split {
+            account
+                .terminal_path
+                .push(TerminalStep::from_str(next).map_err(span(start, next))?);
         }
 
+        account.check_depth(MAX_DERIVATION_DEPTH)?;
         Ok(account)
     }
 
     /// Parse from LNPBP standard representation:
     /// `m=[fp]/hardened_path/account=[xpub]/unhardened_path`
     pub fn from_str_lnpbp(s: &str) -> Result<DerivationAccount, ParseError> {
+        check_str_depth(s, MAX_DERIVATION_DEPTH)?;
         let mut split = s.split('/');
         let mut first = split
             .next()
@@ -405,13 +1100,61 @@ impl DerivationAccount {
             source_path.push(AccountStep::from(branch_index));
         }
 
-        Ok(DerivationAccount {
+        let account = DerivationAccount {
             master,
             account_path: source_path,
             account_xpub: branch_xpub,
             revocation_seal,
             terminal_path,
-        })
+        };
+        account.check_depth(MAX_DERIVATION_DEPTH)?;
+        Ok(account)
+    }
+}
+
+impl DerivationAccount {
+    /// Parses single-key output descriptor in form of `pkh(KEY)`,
+    /// `sh(wpkh(KEY))`, `wpkh(KEY)` or `tr(KEY)`, returning derivation scheme
+    /// implied by the descriptor script type together with the account
+    /// parsed from the `KEY` part. Descriptor checksum, if present, is ignored.
+    pub fn from_descriptor_str(s: &str) -> Result<(Bip43, DerivationAccount), ParseError> {
+        let err = || ParseError::InvalidDescriptor(s.to_owned());
+        let descriptor = s.split_once('#').map(|(d, _)| d).unwrap_or(s).trim();
+        let (scheme, prefix, suffix) = if descriptor.starts_with("sh(wpkh(") {
+            (Bip43::Bip49, "sh(wpkh(", "))")
+        } else if descriptor.starts_with("wpkh(") {
+            (Bip43::Bip84, "wpkh(", ")")
+        } else if descriptor.starts_with("pkh(") {
+            (Bip43::Bip44, "pkh(", ")")
+        } else if descriptor.starts_with("tr(") {
+            (Bip43::Bip86, "tr(", ")")
+        } else {
+            return Err(err());
+        };
+        let key = descriptor
+            .strip_prefix(prefix)
+            .and_then(|d| d.strip_suffix(suffix))
+            .ok_or_else(err)?;
+        // Single-key descriptors can't contain nested expressions or multiple
+        // arguments, while commas are allowed inside index sets like `{0,1}`
+        let mut braces = 0usize;
+        let nested = key.chars().any(|c| match c {
+            '{' => {
+                braces += 1;
+                false
+            }
+            '}' => {
+                braces = braces.saturating_sub(1);
+                false
+            }
+            ',' => braces == 0,
+            '(' | ')' => true,
+            _ => false,
+        });
+        if key.is_empty() || nested {
+            return Err(err());
+        }
+        Ok((scheme, DerivationAccount::from_str(key)?))
     }
 }
 
@@ -425,12 +1168,108 @@ impl Display for DerivationAccount {
     }
 }
 
+/// Returns number of indexes in derive pattern required by the
+/// `terminal_path` steps (see [`DerivationAccount::derive_pattern_len`]).
+pub(crate) fn derive_pattern_len(terminal_path: &[TerminalStep]) -> usize {
+    terminal_path.iter().filter(|step| step.count() > 1).count()
+}
+
+/// Constructs derivation path from the `terminal_path` steps, taking indexes
+/// for the variable steps from the derive pattern `pat` (see
+/// [`DerivationAccount::to_terminal_derivation_path`]).
+pub(crate) fn terminal_derivation_path(
+    terminal_path: &[TerminalStep],
+    pat: &[UnhardenedIndex],
+) -> Result<DerivationPath, DerivePatternError> {
+    if pat.len() != derive_pattern_len(terminal_path) {
+        return Err(DerivePatternError::Mismatch);
+    }
+    let mut iter = pat.iter();
+    // TODO: Convert into a method on TerminalPath type
+    terminal_path
+        .iter()
+        .map(|step| {
+            if step.count() == 1 {
+                Ok(ChildNumber::Normal {
+                    index: step.first_index(),
+                })
+            } else if let Some(index) = iter.next() {
+                if !step.contains(index.first_index()) {
+                    Err(DerivePatternError::Mismatch)
+                } else {
+                    Ok(ChildNumber::from(*index))
+                }
+            } else {
+                Err(DerivePatternError::Mismatch)
+            }
+        })
+        .collect()
+}
+
+/// Checks the number of derivation path segments in the string before
+/// parsing it, bounding the parser work for the untrusted input.
+pub(crate) fn check_str_depth(s: &str, max_depth: usize) -> Result<(), DerivePatternError> {
+    let depth = s.matches('/').count();
+    if depth > max_depth {
+        return Err(DerivePatternError::DepthExceeded {
+            depth,
+            max: max_depth,
+        });
+    }
+    Ok(())
+}
+
 impl FromStr for DerivationAccount {
     type Err = ParseError;
 
+    /// Parses account either from LNPBP or Bitcoin Core representation. If
+    /// both of them fail, the error is reported by the parser matching the
+    /// string syntax: strings with `=` separators or a bracketed account xpub
+    /// (like `[xpub]/0/*`) are LNPBP, the rest are Bitcoin Core strings, for
+    /// which the error points to the failing fragment.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        DerivationAccount::from_str_lnpbp(s)
-            .or_else(|err| DerivationAccount::from_str_bitcoin_core(s).map_err(|_| err))
+        DerivationAccount::from_str_lnpbp(s).or_else(|err| {
+            DerivationAccount::from_str_bitcoin_core(s).map_err(|core_err| {
+                let first = s.split('/').next().unwrap_or_default();
+                if s.contains('=') || (first.starts_with('[') && first.ends_with(']')) {
+                    err
+                } else {
+                    core_err
+                }
+            })
+        })
+    }
+}
+
+#[cfg(feature = "miniscript")]
+impl DerivationAccount {
+    /// Converts account into miniscript descriptor key, using the master key
+    /// fingerprint and account derivation path (if the master key is known)
+    /// as the key origin.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`DeriveError::NonMiniscriptPath`] if the terminal path
+    /// contains multipath steps (like `<0;1>`) or a wildcard which is not
+    /// the last step, since these are not supported by miniscript keys.
+    pub fn to_descriptor_public_key(&self) -> Result<DescriptorPublicKey, DeriveError> {
+        let (wildcard, steps) = match self.terminal_path.split_last() {
+            Some((TerminalStep::Wildcard, steps)) => (Wildcard::Unhardened, steps),
+            _ => (Wildcard::None, &self.terminal_path[..]),
+        };
+        let derivation_path = steps
+            .iter()
+            .map(|step| match step {
+                TerminalStep::Index(index) => Ok(ChildNumber::from(*index)),
+                _ => Err(DeriveError::NonMiniscriptPath),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(DescriptorPublicKey::XPub(DescriptorXKey {
+            origin: self.account_key_source(),
+            xkey: self.account_xpub,
+            derivation_path: derivation_path.into(),
+            wildcard,
+        }))
     }
 }
 
@@ -531,4 +1370,780 @@ mod test {
             assert_eq!(format!("{:#}", account), path);
         }
     }
+
+    #[test]
+    fn script_pubkey_bip_vectors() {
+        let secp = Secp256k1::verification_only();
+        for (scheme, account, address) in [
+            (
+                Bip43::Bip44,
+                "[73c5da0a/44h/0h/0h]xpub6BosfCnifzxcFwrSzQiqu2DBVTshkCXacvNsWGYJVVhhawA7d4R5WSWGFNbi8Aw6ZRc1brxMyWMzG3DSSSSoekkudhUd9yLb6qx39T9nMdj/0/*",
+                "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA",
+            ),
+            (
+                Bip43::Bip49,
+                "[73c5da0a/49h/0h/0h]xpub6C6nQwHaWbSrzs5tZ1q7m5R9cPK9eYpNMFesiXsYrgc1P8bvLLAet9JfHjYXKjToD8cBRswJXXbbFpXgwsswVPAZzKMa1jUp2kVkGVUaJa7/0/*",
+                "37VucYSaXLCAsxYyAPfbSi9eh4iEcbShgf",
+            ),
+            (
+                Bip43::Bip84,
+                "[73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*",
+                "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu",
+            ),
+            (
+                Bip43::Bip86,
+                "[73c5da0a/86h/0h/0h]xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/0/*",
+                "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+            ),
+        ] {
+            let account = DerivationAccount::from_str_bitcoin_core(account).unwrap();
+            let script = account
+                .script_pubkey(&secp, scheme, [UnhardenedIndex::zero()])
+                .unwrap();
+            assert_eq!(
+                bitcoin::Address::from_script(&script, bitcoin::Network::Bitcoin)
+                    .unwrap()
+                    .to_string(),
+                address
+            );
+        }
+
+        let account = DerivationAccount::from_str_bitcoin_core(
+            "[73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*",
+        )
+        .unwrap();
+        assert!(matches!(
+            account.script_pubkey(&secp, Bip43::Bip48Native, [UnhardenedIndex::zero()]),
+            Err(DeriveError::UnsupportedScheme(Bip43::Bip48Native))
+        ));
+    }
+
+    #[test]
+    fn from_xpub() {
+        let xpub = xpubs()[2];
+        let account = DerivationAccount::from_xpub(xpub);
+        assert!(!account.seed_based());
+        assert_eq!(
+            account.to_account_derivation_path(),
+            DerivationPath::master()
+        );
+        assert_eq!(account.to_string(), format!("[{}]/*", xpub));
+        assert_eq!(
+            account
+                .derive_public_key(&Secp256k1::verification_only(), [UnhardenedIndex::from(
+                    7u8
+                )])
+                .unwrap(),
+            xpub.ckd_pub(&Secp256k1::verification_only(), ChildNumber::from(7))
+                .unwrap()
+                .public_key
+        );
+    }
+
+    #[test]
+    fn derive_at() {
+        use crate::HARDENED_INDEX_BOUNDARY;
+
+        let secp = Secp256k1::verification_only();
+        let xpub = xpubs()[2];
+        let account = DerivationAccount::from_xpub(xpub);
+        let largest = HARDENED_INDEX_BOUNDARY - 1;
+        assert_eq!(
+            account.derive_at(&secp, 5).unwrap(),
+            account
+                .derive_public_key(&secp, [UnhardenedIndex::from(5u8)])
+                .unwrap()
+        );
+        assert_eq!(
+            account.derive_at(&secp, largest).unwrap(),
+            account
+                .derive_public_key(&secp, [UnhardenedIndex::largest()])
+                .unwrap()
+        );
+        assert!(matches!(
+            account.derive_at(&secp, HARDENED_INDEX_BOUNDARY),
+            Err(DeriveError::HardenedIndex(HARDENED_INDEX_BOUNDARY))
+        ));
+        assert!(matches!(
+            account.derive_at(&secp, u32::MAX),
+            Err(DeriveError::HardenedIndex(u32::MAX))
+        ));
+
+        let account =
+            DerivationAccount::from_str_bitcoin_core(&format!("{}/<0;1>/*", xpub)).unwrap();
+        assert!(matches!(
+            account.derive_at(&secp, 0),
+            Err(DeriveError::DerivePatternMismatch)
+        ));
+    }
+
+    #[test]
+    fn split_multipath() {
+        let origin = "[73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        let account = |terminal: &str| {
+            DerivationAccount::from_str_bitcoin_core(&format!("{}{}", origin, terminal)).unwrap()
+        };
+
+        assert_eq!(account("/<0;1>/*").split_multipath().unwrap(), vec![
+            account("/0/*"),
+            account("/1/*")
+        ]);
+        assert_eq!(account("/7/{0,3}").split_multipath().unwrap(), vec![
+            account("/7/0"),
+            account("/7/3")
+        ]);
+        assert_eq!(account("/0/*").split_multipath().unwrap(), vec![account(
+            "/0/*"
+        )]);
+        assert!(matches!(
+            account("/<0;1>/<2;3>/*").split_multipath(),
+            Err(DeriveError::NestedMultipath)
+        ));
+        assert!(matches!(
+            account("/{0-1000}/*").split_multipath(),
+            Err(DeriveError::MultipathTooLarge {
+                count: 1001,
+                max: MAX_MULTIPATH_BRANCHES
+            })
+        ));
+        assert_eq!(account("/{0-255}/*").split_multipath().unwrap().len(), 256);
+    }
+
+    #[test]
+    fn try_merge() {
+        let origin = "[73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        let account = |terminal: &str| {
+            DerivationAccount::from_str_bitcoin_core(&format!("{}{}", origin, terminal)).unwrap()
+        };
+        let merge = |terminals: &[&str]| {
+            let accounts = terminals.iter().map(|t| account(t)).collect::<Vec<_>>();
+            DerivationAccount::try_merge(&accounts)
+        };
+
+        let multipath = account("/<0;1>/*");
+        assert_eq!(merge(&["/0/*", "/1/*"]), Ok(multipath.clone()));
+        assert_eq!(merge(&["/1/*", "/0/*"]), Ok(multipath.clone()));
+        assert_eq!(
+            DerivationAccount::try_merge(&multipath.split_multipath().unwrap()),
+            Ok(multipath)
+        );
+        assert_eq!(merge(&["/7/2", "/7/1", "/7/0"]), Ok(account("/7/<0;1;2>")));
+        assert_eq!(merge(&["/0/*"]), Ok(account("/0/*")));
+
+        assert_eq!(merge(&[]), Err(MergeError::NoAccounts));
+        assert_eq!(
+            merge(&["/0/*", "/2/*"]),
+            Err(MergeError::NonConsecutiveBranches)
+        );
+        assert_eq!(
+            merge(&["/0/*", "/0/*"]),
+            Err(MergeError::NonConsecutiveBranches)
+        );
+        assert_eq!(merge(&["/0/0", "/1/1"]), Err(MergeError::Mismatch));
+        assert_eq!(merge(&["/0/*", "/0/1"]), Err(MergeError::Mismatch));
+        assert_eq!(merge(&["/0/*", "/1"]), Err(MergeError::Mismatch));
+        assert_eq!(
+            merge(&["/<0;1>/*", "/<0;1>/0"]),
+            Err(MergeError::NestedMultipath)
+        );
+
+        let mut other = account("/1/*");
+        other.account_path = DerivationSubpath::new();
+        assert_eq!(
+            DerivationAccount::try_merge(&[account("/0/*"), other]),
+            Err(MergeError::Mismatch)
+        );
+    }
+
+    #[test]
+    fn origin_check() {
+        let check = |s: &str| DerivationAccount::from_str_checked(s).map(|_| ());
+        let account = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        let master = xpubs()[0];
+
+        check(&format!("[73c5da0a/84h/0h/0h]{}/0/*", account)).unwrap();
+        check(&format!("[deadbeef/84h/0h/0h]{}/0/*", account)).unwrap();
+        let root =
+            DerivationAccount::from_str_checked(&format!("[{}]{}/*", master.fingerprint(), master))
+                .unwrap();
+        assert_eq!(root.master, XpubRef::Fingerprint(master.fingerprint()));
+        assert!(root.account_path.is_empty());
+        assert_eq!(root.terminal_path, vec![TerminalStep::Wildcard].into());
+        assert_eq!(
+            check(&format!("[73c5da0a/84h/0h/1h]{}/0/*", account)),
+            Err(ParseError::OriginChildMismatch {
+                declared: ChildNumber::from_hardened_idx(1).unwrap(),
+                expected: ChildNumber::from_hardened_idx(0).unwrap(),
+            })
+        );
+        assert_eq!(
+            check(&format!("[73c5da0a/49h/84h/0h/0h]{}/0/*", account)),
+            Err(ParseError::OriginDepthMismatch {
+                depth: 3,
+                path_len: 4
+            })
+        );
+        assert_eq!(
+            check(&format!("[deadbeef]{}/*", master)),
+            Err(ParseError::OriginFingerprintMismatch {
+                declared: Fingerprint::from_str("deadbeef").unwrap(),
+                expected: master.fingerprint(),
+            })
+        );
+
+        let secp = Secp256k1::new();
+        let xpriv = ExtendedPrivKey::new_master(Network::Bitcoin, &[1u8; 32]).unwrap();
+        let master = ExtendedPubKey::from_priv(&secp, &xpriv);
+        let path = DerivationPath::from_str("m/7h").unwrap();
+        let child = ExtendedPubKey::from_priv(&secp, &xpriv.derive_priv(&secp, &path).unwrap());
+        check(&format!("[{}/7h]{}/*", master.fingerprint(), child)).unwrap();
+        assert_eq!(
+            check(&format!("[deadbeef/7h]{}/*", child)),
+            Err(ParseError::OriginFingerprintMismatch {
+                declared: Fingerprint::from_str("deadbeef").unwrap(),
+                expected: master.fingerprint(),
+            })
+        );
+    }
+
+    #[test]
+    fn watch_only() {
+        let xpub = ExtendedPubKey::from_str(
+            "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V",
+        )
+        .unwrap();
+        let fingerprint = Fingerprint::from_str("73c5da0a").unwrap();
+        let (receive, change) = DerivationAccount::watch_only(
+            fingerprint,
+            xpub,
+            Bip43::Bip84,
+            HardenedIndex::zero(),
+            Network::Bitcoin,
+        )
+        .unwrap();
+        assert_eq!(
+            format!("{:#}", receive),
+            format!("[73c5da0a/84h/0h/0h]{}/0/*", xpub)
+        );
+        assert_eq!(
+            format!("{:#}", change),
+            format!("[73c5da0a/84h/0h/0h]{}/1/*", xpub)
+        );
+        receive.check_origin().unwrap();
+        let address = receive
+            .address(
+                &Secp256k1::verification_only(),
+                Bip43::Bip84,
+                [UnhardenedIndex::zero()],
+                Network::Bitcoin,
+            )
+            .unwrap();
+        assert_eq!(
+            address.to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+
+        let (receive, _) = DerivationAccount::watch_only(
+            fingerprint,
+            xpub,
+            Bip43::Bip48Native,
+            HardenedIndex::from(5u8),
+            Network::Bitcoin,
+        )
+        .unwrap();
+        assert_eq!(
+            format!("{:#}", receive),
+            format!("[73c5da0a/48h/0h/5h/2h]{}/0/*", xpub)
+        );
+
+        assert!(matches!(
+            DerivationAccount::watch_only(
+                fingerprint,
+                xpub,
+                Bip43::Bip84,
+                HardenedIndex::zero(),
+                Network::Testnet
+            ),
+            Err(DeriveError::InconsistentKeyNetwork)
+        ));
+        assert!(matches!(
+            DerivationAccount::watch_only(
+                fingerprint,
+                xpub,
+                Bip43::Bip45,
+                HardenedIndex::zero(),
+                Network::Bitcoin
+            ),
+            Err(DeriveError::UnsupportedScheme(Bip43::Bip45))
+        ));
+    }
+
+    #[test]
+    fn same_account() {
+        let xpub = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        let account = |s: &str| DerivationAccount::from_str(&s.replace("{xpub}", xpub)).unwrap();
+        let receive = account("[73c5da0a/84h/0h/0h]{xpub}/0/*");
+        let change = account("[73c5da0a/84'/0'/0']{xpub}/1/*");
+        assert!(receive.same_account(&change));
+        assert!(change.same_account(&receive));
+        assert!(receive.same_account(&account("[73c5da0a/84h/0h/0h]{xpub}/<0;1>/*")));
+
+        assert!(!receive.same_account(&account("[d34db33f/84h/0h/0h]{xpub}/0/*")));
+        assert!(!receive.same_account(&account("[73c5da0a/84h/0h/1h]{xpub}/0/*")));
+        assert!(!receive.same_account(&account("{xpub}/0/*")));
+        let other = "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ";
+        assert!(!receive.same_account(&account(&format!("[73c5da0a/84h/0h/0h]{}/1/*", other))));
+    }
+
+    #[test]
+    fn max_depth() {
+        let account = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        let valid = format!("[73c5da0a/84h/0h/0h]{}/0/*", account);
+        assert_eq!(
+            DerivationAccount::from_str(&valid)
+                .unwrap()
+                .derivation_depth(),
+            5
+        );
+        DerivationAccount::from_str_with_max_depth(&valid, 5).unwrap();
+        assert_eq!(
+            DerivationAccount::from_str_with_max_depth(&valid, 4),
+            Err(ParseError::DerivePattern(
+                DerivePatternError::DepthExceeded { depth: 5, max: 4 }
+            ))
+        );
+        assert_eq!(
+            DerivationAccount::from_str_with_max_depth(&valid, 256),
+            Err(ParseError::MaxDepthUnsupported {
+                requested: 256,
+                max: MAX_DERIVATION_DEPTH
+            })
+        );
+
+        let long = format!("[73c5da0a/84h/0h/0h]{}{}", account, "/0".repeat(1000));
+        for res in [
+            DerivationAccount::from_str(&long),
+            DerivationAccount::from_str_bitcoin_core(&long),
+            DerivationAccount::from_str_lnpbp(&long),
+        ] {
+            assert_eq!(
+                res,
+                Err(ParseError::DerivePattern(
+                    DerivePatternError::DepthExceeded {
+                        depth: 1003,
+                        max: MAX_DERIVATION_DEPTH
+                    }
+                ))
+            );
+        }
+
+        let mut account = DerivationAccount::from_str(&valid).unwrap();
+        account.terminal_path = vec![TerminalStep::from(0u8); 253].into();
+        assert_eq!(account.derivation_depth(), 256);
+        assert_eq!(
+            account.to_terminal_derivation_path([]),
+            Err(DerivePatternError::DepthExceeded {
+                depth: 256,
+                max: MAX_DERIVATION_DEPTH
+            })
+        );
+        assert!(matches!(
+            account.derive_at(&Secp256k1::verification_only(), 0),
+            Err(DeriveError::DepthExceeded { depth: 256, .. })
+        ));
+        account.terminal_path.pop();
+        account.to_terminal_derivation_path([]).unwrap();
+    }
+
+    #[test]
+    fn multiple_wildcards() {
+        let secp = Secp256k1::verification_only();
+        let xpub = xpubs()[2];
+        let account = DerivationAccount::from_str_bitcoin_core(&format!(
+            "[73c5da0a/84h/0h/0h]{}/*/0/*",
+            xpub
+        ))
+        .unwrap();
+        let pat = |pat: &[u8]| {
+            pat.iter()
+                .copied()
+                .map(UnhardenedIndex::from)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(account.derive_pattern_len(), 2);
+        assert_eq!(
+            account.to_terminal_derivation_path(pat(&[3, 5])),
+            Ok(DerivationPath::from_str("m/3/0/5").unwrap())
+        );
+        assert_eq!(
+            account.derive_public_key(&secp, pat(&[3, 5])).unwrap(),
+            xpub.derive_pub(&secp, &DerivationPath::from_str("m/3/0/5").unwrap())
+                .unwrap()
+                .public_key
+        );
+        assert_eq!(
+            account.to_terminal_derivation_path(pat(&[3])),
+            Err(DerivePatternError::Mismatch)
+        );
+        assert_eq!(
+            account.to_terminal_derivation_path(pat(&[3, 5, 7])),
+            Err(DerivePatternError::Mismatch)
+        );
+
+        let account =
+            DerivationAccount::from_str_bitcoin_core(&format!("[73c5da0a]{}/1/2", xpub)).unwrap();
+        assert_eq!(account.derive_pattern_len(), 0);
+        assert_eq!(
+            account.to_terminal_derivation_path(pat(&[])),
+            Ok(DerivationPath::from_str("m/1/2").unwrap())
+        );
+        assert_eq!(
+            account.to_terminal_derivation_path(pat(&[0])),
+            Err(DerivePatternError::Mismatch)
+        );
+    }
+
+    #[test]
+    fn scripts_for_filter() {
+        let secp = Secp256k1::verification_only();
+        let origin = "[73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        let account = |terminal: &str| {
+            DerivationAccount::from_str_bitcoin_core(&format!("{}{}", origin, terminal)).unwrap()
+        };
+        let script = |account: &DerivationAccount, pat: &[u8]| {
+            let pat = pat
+                .iter()
+                .copied()
+                .map(UnhardenedIndex::from)
+                .collect::<Vec<_>>();
+            account.script_pubkey(&secp, Bip43::Bip84, pat).unwrap()
+        };
+
+        let multipath = account("/<0;1>/*");
+        let scripts = multipath
+            .scripts_for_filter(&secp, Bip43::Bip84, 3)
+            .unwrap();
+        assert_eq!(scripts, vec![
+            script(&multipath, &[0, 0]),
+            script(&multipath, &[0, 1]),
+            script(&multipath, &[0, 2]),
+            script(&multipath, &[1, 0]),
+            script(&multipath, &[1, 1]),
+            script(&multipath, &[1, 2]),
+        ]);
+        assert_eq!(
+            format!("{:x}", scripts[0]),
+            "0014c0cebcd6c3d3ca8c75dc5ec62ebe55330ef910e2"
+        );
+
+        let receive = account("/0/*");
+        assert_eq!(
+            receive.scripts_for_filter(&secp, Bip43::Bip84, 3).unwrap(),
+            scripts[..3].to_vec()
+        );
+        assert_eq!(
+            account("/{0-1}/*")
+                .scripts_for_filter(&secp, Bip43::Bip84, 3)
+                .unwrap(),
+            scripts
+        );
+        assert_eq!(
+            account("/1/7")
+                .scripts_for_filter(&secp, Bip43::Bip84, 3)
+                .unwrap(),
+            vec![script(&account("/1/7"), &[])]
+        );
+        assert!(receive
+            .scripts_for_filter(&secp, Bip43::Bip84, 0)
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            account("/*/0").scripts_for_filter(&secp, Bip43::Bip84, 1),
+            Err(DeriveError::DerivePatternMismatch)
+        ));
+    }
+
+    #[test]
+    fn taproot_scripts_for_filter() {
+        // BIP-86 test vectors, matching Bitcoin Core `tr()` descriptor
+        // derivation
+        let secp = Secp256k1::verification_only();
+        let account = DerivationAccount::from_str_bitcoin_core(
+            "[73c5da0a/86h/0h/0h]xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/<0;1>/*",
+        )
+        .unwrap();
+        let scripts = account.scripts_for_filter(&secp, Bip43::Bip86, 2).unwrap();
+        assert_eq!(scripts.len(), 4);
+        assert!(scripts.iter().all(Script::is_v1_p2tr));
+        let addresses = scripts
+            .iter()
+            .map(|script| {
+                Address::from_script(script, Network::Bitcoin)
+                    .unwrap()
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            addresses[0],
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+        assert_eq!(
+            addresses[1],
+            "bc1p4qhjn9zdvkux4e44uhx8tc55attvtyu358kutcqkudyccelu0was9fqzwh"
+        );
+        assert_eq!(
+            addresses[2],
+            "bc1p3qkhfews2uk44qtvauqyr2ttdsw7svhkl9nkm9s9c3x4ax5h60wqwruhk7"
+        );
+    }
+
+    #[test]
+    fn bip44_legacy_address() {
+        let secp = Secp256k1::verification_only();
+        let account = DerivationAccount::from_str_bitcoin_core(
+            "[73c5da0a/44h/0h/0h]xpub6BosfCnifzxcFwrSzQiqu2DBVTshkCXacvNsWGYJVVhhawA7d4R5WSWGFNbi8Aw6ZRc1brxMyWMzG3DSSSSoekkudhUd9yLb6qx39T9nMdj/0/*",
+        )
+        .unwrap();
+        let mainnet = account
+            .address(
+                &secp,
+                Bip43::Bip44,
+                [UnhardenedIndex::zero()],
+                Network::Bitcoin,
+            )
+            .unwrap();
+        assert_eq!(mainnet.to_string(), "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA");
+        let testnet = account
+            .address(
+                &secp,
+                Bip43::Bip44,
+                [UnhardenedIndex::zero()],
+                Network::Testnet,
+            )
+            .unwrap();
+        for (address, version) in [(mainnet, 0x00), (testnet, 0x6f)] {
+            let data = bitcoin::util::base58::from_check(&address.to_string()).unwrap();
+            assert_eq!(data[0], version);
+        }
+    }
+
+    #[test]
+    fn derive_address() {
+        let secp = Secp256k1::verification_only();
+        let account = DerivationAccount::from_str_bitcoin_core(
+            "[73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/<0;1>/*",
+        )
+        .unwrap();
+        let address = |role: &str, index: u32| {
+            account
+                .derive_address(&secp, Bip43::Bip84, Network::Bitcoin, role, index)
+                .map(|address| address.to_string())
+        };
+        for role in ["receive", "external"] {
+            assert_eq!(
+                address(role, 0).unwrap(),
+                "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+            );
+            assert_eq!(
+                address(role, 1).unwrap(),
+                "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g"
+            );
+        }
+        for role in ["change", "internal"] {
+            assert_eq!(
+                address(role, 0).unwrap(),
+                "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el"
+            );
+        }
+        assert!(matches!(
+            address("savings", 0),
+            Err(DeriveError::UnknownRole(role)) if role == "savings"
+        ));
+
+        let receive = account.split_multipath().unwrap().remove(0);
+        assert_eq!(
+            receive
+                .derive_address(&secp, Bip43::Bip84, Network::Bitcoin, "receive", 0)
+                .unwrap()
+                .to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        assert!(matches!(
+            receive.derive_address(&secp, Bip43::Bip84, Network::Bitcoin, "change", 0),
+            Err(DeriveError::DerivePatternMismatch)
+        ));
+    }
+
+    #[test]
+    fn identity_address() {
+        let secp = Secp256k1::verification_only();
+        let xpub = "[73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        for terminal in ["/<0;1>/*", "/0/*", "/*/*"] {
+            let account =
+                DerivationAccount::from_str_bitcoin_core(&format!("{}{}", xpub, terminal)).unwrap();
+            let address = account
+                .identity_address(&secp, Bip43::Bip84, Network::Bitcoin)
+                .unwrap();
+            assert_eq!(
+                address,
+                account
+                    .derive_address(&secp, Bip43::Bip84, Network::Bitcoin, "receive", 0)
+                    .unwrap()
+            );
+            assert_eq!(
+                address.to_string(),
+                "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+            );
+        }
+    }
+
+    #[test]
+    fn bip84_address_info() {
+        let secp = Secp256k1::verification_only();
+        let account = DerivationAccount::from_str_bitcoin_core(
+            "[73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*",
+        )
+        .unwrap();
+        let info = account
+            .address_info(
+                &secp,
+                Bip43::Bip84,
+                [UnhardenedIndex::zero()],
+                Network::Bitcoin,
+            )
+            .unwrap();
+        assert_eq!(
+            info.address.to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        assert_eq!(
+            format!("{:x}", info.script_pubkey),
+            "0014c0cebcd6c3d3ca8c75dc5ec62ebe55330ef910e2"
+        );
+        assert_eq!(
+            info.master_fingerprint,
+            Fingerprint::from_str("73c5da0a").unwrap()
+        );
+        assert_eq!(
+            info.derivation_path,
+            DerivationPath::from_str("m/84'/0'/0'/0/0").unwrap()
+        );
+        assert_eq!(info.hd_key_path, "73c5da0a/84'/0'/0'/0/0");
+
+        let account = DerivationAccount::from_xpub(account.account_xpub);
+        let info = account
+            .address_info(
+                &secp,
+                Bip43::Bip84,
+                [UnhardenedIndex::one()],
+                Network::Bitcoin,
+            )
+            .unwrap();
+        assert_eq!(
+            info.hd_key_path,
+            format!("{}/1", account.account_fingerprint())
+        );
+    }
+
+    #[test]
+    fn bitcoin_core_parse_error_span() {
+        let xpub = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        for (s, span) in [
+            (format!("[zzzzzzzz/84h/0h/0h]{}/0/*", xpub), (1, 9)),
+            (format!("[73c5da0a/84h/0x/0h]{}/0/*", xpub), (14, 16)),
+            (format!("[73c5da0a/84h/0h/0z]{}/0/*", xpub), (17, 19)),
+            (format!("[73c5da0a/84h/0h/0h]{}x/0/*", xpub), (20, 132)),
+            (format!("[73c5da0a/84h/0h/0h]{}/0/q", xpub), (134, 135)),
+            (format!("{}x/0/*", xpub), (0, 112)),
+        ] {
+            let err = DerivationAccount::from_str_bitcoin_core(&s).unwrap_err();
+            assert_eq!(err.span(), Some(span), "{}", s);
+            assert!(err
+                .to_string()
+                .ends_with(&format!("(at characters {}..{})", span.0, span.1)));
+        }
+
+        // Spanned errors are reported by the generic parsers, too
+        let s = format!("[73c5da0a/84h/0x/0h]{}/0/*", xpub);
+        let err = DerivationAccount::from_str(&s).unwrap_err();
+        assert_eq!(err.span(), Some((14, 16)));
+        let err = DerivationAccount::from_str_with_max_depth(&s, 8).unwrap_err();
+        assert_eq!(err.span(), Some((14, 16)));
+        let err = DerivationAccount::from_str(&format!("m=[73c5da0a]/84h/0x=[{}]", xpub));
+        assert_eq!(err.unwrap_err().span(), None);
+
+        let s = s!("[73c5da0a/84h/0h/0h");
+        let err = DerivationAccount::from_str_bitcoin_core(&s).unwrap_err();
+        assert_eq!(err, ParseError::AccountXpubAbsent(s));
+        assert_eq!(err.span(), None);
+    }
+
+    #[test]
+    fn fingerprint_only_key_source() {
+        let account = DerivationAccount::from_str_bitcoin_core(
+            "[73c5da0a/48h/0h/0h/2h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/{0,1}/7/*",
+        )
+        .unwrap();
+        let master = Fingerprint::from_str("73c5da0a").unwrap();
+        assert_eq!(account.master, XpubRef::Fingerprint(master));
+
+        let pat = [UnhardenedIndex::one(), UnhardenedIndex::from(42u8)];
+        let (fingerprint, path) = account.key_source(pat).unwrap();
+        assert_eq!(fingerprint, master);
+        assert_eq!(
+            path,
+            DerivationPath::from_str("m/48'/0'/0'/2'/1/7/42").unwrap()
+        );
+        assert_eq!(account.to_full_derivation_path(pat).unwrap(), path);
+
+        assert_eq!(
+            account.key_source([UnhardenedIndex::from(2u8), UnhardenedIndex::zero()]),
+            Err(DerivePatternError::Mismatch)
+        );
+        assert_eq!(
+            account.key_source([UnhardenedIndex::one()]),
+            Err(DerivePatternError::Mismatch)
+        );
+    }
+
+    #[test]
+    fn from_descriptor_str() {
+        let key = "[73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*";
+        let account = DerivationAccount::from_str(key).unwrap();
+
+        for (descriptor, scheme) in [
+            (format!("pkh({})", key), Bip43::Bip44),
+            (format!("sh(wpkh({}))", key), Bip43::Bip49),
+            (format!("wpkh({})", key), Bip43::Bip84),
+            (format!("tr({})", key), Bip43::Bip86),
+            (format!("wpkh({})#8zl0zxma", key), Bip43::Bip84),
+        ] {
+            assert_eq!(
+                DerivationAccount::from_descriptor_str(&descriptor),
+                Ok((scheme, account.clone()))
+            );
+        }
+
+        for descriptor in [
+            format!("sh(wpkh({})", key),
+            format!("wpkh({}", key),
+            format!("wpkh({}))", key),
+            format!("wsh({})", key),
+            format!("sh({})", key),
+            format!("tr({},pk({}))", key, key),
+            s!("wpkh()"),
+        ] {
+            assert_eq!(
+                DerivationAccount::from_descriptor_str(&descriptor),
+                Err(ParseError::InvalidDescriptor(descriptor))
+            );
+        }
+
+        let key = key.replace("/0/*", "/{0,1}/*");
+        let account = DerivationAccount::from_str(&key).unwrap();
+        assert_eq!(
+            DerivationAccount::from_descriptor_str(&format!("wpkh({})", key)),
+            Ok((Bip43::Bip84, account))
+        );
+    }
 }
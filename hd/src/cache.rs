@@ -0,0 +1,228 @@
+// Descriptor wallet library extending bitcoin & miniscript functionality
+// by LNP/BP Association (https://lnp-bp.org)
+// Written in 2020-2022 by
+//     Dr. Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the Apache-2.0 License
+// along with this software.
+// If not, see <https://opensource.org/licenses/Apache-2.0>.
+
+//! Cache of the intermediate extended public keys derived from an account.
+
+use std::collections::BTreeMap;
+
+use bitcoin::secp256k1::{Secp256k1, Verification};
+use bitcoin::util::bip32::{ChildNumber, ExtendedPubKey};
+use strict_encoding::{StrictDecode, StrictEncode};
+
+use crate::{DerivationAccount, UnhardenedIndex};
+
+/// Errors loading serialized [`DerivationCache`]
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum DerivationCacheError {
+    /// unable to decode derivation cache: {0}
+    #[from]
+    Encoding(strict_encoding::Error),
+
+    /// derivation cache belongs to a different account extended public key
+    /// {0}
+    AccountMismatch(ExtendedPubKey),
+
+    /// derivation cache entry at path {0:?} is not derived from its parent
+    /// key
+    InconsistentEntry(Vec<UnhardenedIndex>),
+}
+
+/// Cache of the intermediate extended public keys (like keys of the receive
+/// and change branches) derived from the account extended public key, saving
+/// the repeated derivations of scripts and addresses at the same branch.
+///
+/// The cache can be persisted with [`DerivationCache::to_bytes`] and loaded
+/// back with [`DerivationCache::from_bytes`], which checks that the cache
+/// belongs to the same account.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[derive(StrictEncode, StrictDecode)]
+pub struct DerivationCache {
+    account_xpub: ExtendedPubKey,
+    xpubs: BTreeMap<Vec<UnhardenedIndex>, ExtendedPubKey>,
+}
+
+impl DerivationCache {
+    /// Constructs empty cache for the account extended public key.
+    pub fn new(account_xpub: ExtendedPubKey) -> DerivationCache {
+        DerivationCache {
+            account_xpub,
+            xpubs: empty!(),
+        }
+    }
+
+    /// Constructs empty cache for the account (see [`DerivationCache::new`]).
+    #[inline]
+    pub fn with_account(account: &DerivationAccount) -> DerivationCache {
+        DerivationCache::new(account.account_xpub)
+    }
+
+    /// Returns the account extended public key the cache belongs to.
+    #[inline]
+    pub fn account_xpub(&self) -> ExtendedPubKey { self.account_xpub }
+
+    /// Returns number of the cached intermediate keys.
+    #[inline]
+    pub fn len(&self) -> usize { self.xpubs.len() }
+
+    /// Detects whether there are no cached intermediate keys.
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.xpubs.is_empty() }
+
+    /// Derives extended public key at `path` relative to the account extended
+    /// public key, starting from the longest cached prefix of the path. All
+    /// derived keys at the intermediate paths (excluding the `path` itself)
+    /// are cached.
+    pub fn derive<C: Verification>(
+        &mut self,
+        ctx: &Secp256k1<C>,
+        path: impl AsRef<[UnhardenedIndex]>,
+    ) -> ExtendedPubKey {
+        let path = path.as_ref();
+        let (mut depth, mut xpub) = (0..path.len())
+            .rev()
+            .find_map(|len| self.xpubs.get(&path[..len]).map(|xpub| (len, *xpub)))
+            .unwrap_or((0, self.account_xpub));
+        while depth < path.len() {
+            xpub = xpub
+                .ckd_pub(ctx, ChildNumber::from(path[depth]))
+                .expect("unhardened derivation failure");
+            depth += 1;
+            if depth < path.len() {
+                self.xpubs.insert(path[..depth].to_vec(), xpub);
+            }
+        }
+        xpub
+    }
+
+    /// Serializes the cache for persisting it.
+    #[inline]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.strict_serialize()
+            .expect("in-memory strict encoding of derivation cache")
+    }
+
+    /// Loads the cache serialized with [`DerivationCache::to_bytes`] for the
+    /// account extended public key `account_xpub`.
+    ///
+    /// Each of the cached keys is checked to have depth, child number and
+    /// parent fingerprint matching its path and the cached key of its parent
+    /// path. This does not involve elliptic curve operations and protects
+    /// against loading a cache of another wallet or a corrupted cache, but not
+    /// against maliciously crafted caches.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`DerivationCacheError::AccountMismatch`] if the cache was
+    /// created for a different account and with
+    /// [`DerivationCacheError::InconsistentEntry`] if some of the keys are not
+    /// consistent with their paths.
+    pub fn from_bytes(
+        data: impl AsRef<[u8]>,
+        account_xpub: ExtendedPubKey,
+    ) -> Result<DerivationCache, DerivationCacheError> {
+        let cache = DerivationCache::strict_deserialize(data)?;
+        if cache.account_xpub != account_xpub {
+            return Err(DerivationCacheError::AccountMismatch(cache.account_xpub));
+        }
+        for (path, xpub) in &cache.xpubs {
+            let (last, parent_path) = path
+                .split_last()
+                .ok_or_else(|| DerivationCacheError::InconsistentEntry(path.clone()))?;
+            let parent = if parent_path.is_empty() {
+                Some(&cache.account_xpub)
+            } else {
+                cache.xpubs.get(parent_path)
+            };
+            let is_consistent = matches!(parent, Some(parent)
+                if parent.fingerprint() == xpub.parent_fingerprint
+                    && xpub.child_number == ChildNumber::from(*last)
+                    && xpub.depth as usize == account_xpub.depth as usize + path.len()
+                    && xpub.network == account_xpub.network);
+            if !is_consistent {
+                return Err(DerivationCacheError::InconsistentEntry(path.clone()));
+            }
+        }
+        Ok(cache)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bitcoin::util::bip32::DerivationPath;
+
+    use super::*;
+    use crate::SegmentIndexes;
+
+    fn xpub() -> ExtendedPubKey {
+        ExtendedPubKey::from_str(
+            "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn reload() {
+        let secp = Secp256k1::verification_only();
+        let xpub = xpub();
+        let path = |branch: u8, index: u8| [UnhardenedIndex::from(branch), index.into()];
+
+        let mut cache = DerivationCache::new(xpub);
+        for index in 0..3 {
+            cache.derive(&secp, path(0, index));
+            cache.derive(&secp, path(1, index));
+        }
+        assert_eq!(cache.len(), 2);
+
+        let mut reloaded = DerivationCache::from_bytes(cache.to_bytes(), xpub).unwrap();
+        assert_eq!(reloaded, cache);
+        assert_eq!(
+            reloaded.derive(&secp, path(1, 7)),
+            xpub.derive_pub(&secp, &DerivationPath::from_str("m/1/7").unwrap())
+                .unwrap()
+        );
+        assert_eq!(reloaded.len(), 2);
+    }
+
+    #[test]
+    fn mismatched_cache() {
+        let secp = Secp256k1::verification_only();
+        let xpub = xpub();
+        let other = xpub
+            .derive_pub(&secp, &DerivationPath::from_str("m/5").unwrap())
+            .unwrap();
+        let mut cache = DerivationCache::new(other);
+        cache.derive(&secp, [UnhardenedIndex::zero(), UnhardenedIndex::zero()]);
+        assert!(matches!(
+            DerivationCache::from_bytes(cache.to_bytes(), xpub),
+            Err(DerivationCacheError::AccountMismatch(key)) if key == other
+        ));
+
+        // Cache with an entry taken from a different account
+        let mut forged = DerivationCache::new(xpub);
+        forged.xpubs = cache.xpubs.clone();
+        assert!(matches!(
+            DerivationCache::from_bytes(forged.to_bytes(), xpub),
+            Err(DerivationCacheError::InconsistentEntry(path))
+                if path == vec![UnhardenedIndex::zero()]
+        ));
+
+        assert!(matches!(
+            DerivationCache::from_bytes([0u8; 3], xpub),
+            Err(DerivationCacheError::Encoding(_))
+        ));
+    }
+}
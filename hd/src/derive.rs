@@ -16,25 +16,38 @@
 use std::cell::Cell;
 
 use bitcoin::secp256k1::{self, Secp256k1, Verification};
+use bitcoin::util::bip32;
 #[cfg(feature = "miniscript")]
 use bitcoin::XOnlyPublicKey;
 use bitcoin::{Address, Network, Script};
 #[cfg(feature = "miniscript")]
 use miniscript::{
-    DescriptorTrait, ForEachKey, MiniscriptKey, ToPublicKey, TranslatePk, TranslatePk2,
+    DescriptorPublicKey, DescriptorTrait, ForEachKey, MiniscriptKey, ToPublicKey, TranslatePk,
+    TranslatePk2,
 };
 
-use crate::UnhardenedIndex;
 #[cfg(feature = "miniscript")]
-use crate::{DerivationAccount, SegmentIndexes};
+use crate::DerivationAccount;
+use crate::{Bip43, IndexRange, IndexRangeList, SegmentIndexes, UnhardenedIndex};
 
-/// the provided derive pattern does not match descriptor derivation
-/// wildcard
+/// Errors in derive patterns and derivation paths
 #[derive(
     Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error
 )]
 #[display(doc_comments)]
-pub struct DerivePatternError;
+pub enum DerivePatternError {
+    /// the provided derive pattern does not match descriptor derivation
+    /// wildcard
+    Mismatch,
+
+    /// derivation path depth {depth} exceeds the maximum allowed depth {max}
+    DepthExceeded {
+        /// Depth of the derivation path
+        depth: usize,
+        /// Maximum allowed derivation depth
+        max: usize,
+    },
+}
 
 // TODO: Merge it with the other derivation trait supporting multiple terminal
 //       segments
@@ -71,9 +84,16 @@ pub enum DeriveError {
 
     /// the provided derive pattern does not match descriptor derivation
     /// wildcard
-    #[from(DerivePatternError)]
     DerivePatternMismatch,
 
+    /// derivation path depth {depth} exceeds the maximum allowed depth {max}
+    DepthExceeded {
+        /// Depth of the derivation path
+        depth: usize,
+        /// Maximum allowed derivation depth
+        max: usize,
+    },
+
     /// descriptor contains no keys; corresponding outputs will be
     /// "anyone-can-sped"
     NoKeys,
@@ -85,9 +105,57 @@ pub enum DeriveError {
     /// incorrect miniscript for the descriptor context
     DescriptorFailure,
 
+    /// all unhardened change indexes are already used
+    ChangeIndexExhausted,
+
+    /// terminal derivation path contains more than one multipath step
+    NestedMultipath,
+
+    /// multipath step covers {count} indexes, exceeding the maximum of {max}
+    /// branches
+    MultipathTooLarge {
+        /// Number of indexes covered by the multipath step
+        count: usize,
+        /// Maximum allowed number of multipath branches
+        max: usize,
+    },
+
+    /// terminal derivation path can't be represented by miniscript descriptor
+    /// key, which supports only fixed indexes followed by an optional
+    /// wildcard
+    NonMiniscriptPath,
+
+    /// index {0} is outside of the unhardened index range and can't be used
+    /// for public key derivation
+    HardenedIndex(u32),
+
+    /// derivation scheme {0} does not define a single-key script type and
+    /// can't be used for scriptPubkey construction out of a single account
+    UnsupportedScheme(Bip43),
+
+    /// unknown address role `{0}`; expected `receive`, `external`, `change` or
+    /// `internal`
+    UnknownRole(String),
+
     /// miniscript-specific failure
     #[from]
     Miniscript(miniscript::Error),
+
+    /// BIP-32 related errors.
+    #[display(inner)]
+    #[from]
+    Bip32(bip32::Error),
+}
+
+impl From<DerivePatternError> for DeriveError {
+    fn from(err: DerivePatternError) -> Self {
+        match err {
+            DerivePatternError::Mismatch => DeriveError::DerivePatternMismatch,
+            DerivePatternError::DepthExceeded { depth, max } => {
+                DeriveError::DepthExceeded { depth, max }
+            }
+        }
+    }
 }
 
 impl std::error::Error for DeriveError {
@@ -96,10 +164,19 @@ impl std::error::Error for DeriveError {
             DeriveError::InconsistentKeyNetwork => None,
             DeriveError::InconsistentKeyDerivePattern => None,
             DeriveError::DerivePatternMismatch => None,
+            DeriveError::DepthExceeded { .. } => None,
             DeriveError::NoKeys => None,
             DeriveError::NoAddressForDescriptor => None,
             DeriveError::DescriptorFailure => None,
+            DeriveError::ChangeIndexExhausted => None,
+            DeriveError::NestedMultipath => None,
+            DeriveError::MultipathTooLarge { .. } => None,
+            DeriveError::NonMiniscriptPath => None,
+            DeriveError::HardenedIndex(_) => None,
+            DeriveError::UnsupportedScheme(_) => None,
+            DeriveError::UnknownRole(_) => None,
             DeriveError::Miniscript(err) => Some(err),
+            DeriveError::Bip32(err) => Some(err),
         }
     }
 }
@@ -118,6 +195,31 @@ pub trait DeriveDescriptor<Key> {
     ) -> Result<Self::Output, DeriveError>;
 }
 
+/// Conversion of descriptors into miniscript descriptors using
+/// [`miniscript::DescriptorPublicKey`] keys, which allows to use miniscript
+/// analysis tools (satisfaction weight, policy lifting etc).
+#[cfg(feature = "miniscript")]
+pub trait ToMiniscriptDescriptor {
+    /// Descriptor type with [`miniscript::DescriptorPublicKey`] keys.
+    type Output;
+
+    /// Translates all keys of the descriptor into miniscript descriptor keys
+    /// (see [`DerivationAccount::to_descriptor_public_key`]).
+    fn to_miniscript_descriptor(&self) -> Result<Self::Output, DeriveError>;
+}
+
+#[cfg(feature = "miniscript")]
+impl<D> ToMiniscriptDescriptor for D
+where
+    D: TranslatePk2<DerivationAccount, DescriptorPublicKey>,
+{
+    type Output = <D as TranslatePk<DerivationAccount, DescriptorPublicKey>>::Output;
+
+    fn to_miniscript_descriptor(&self) -> Result<Self::Output, DeriveError> {
+        self.translate_pk2(DerivationAccount::to_descriptor_public_key)
+    }
+}
+
 /// Standard methods which should be supported by descriptors of different
 /// sorts.
 pub trait Descriptor<Key> {
@@ -147,6 +249,61 @@ pub trait Descriptor<Key> {
         pat: impl AsRef<[UnhardenedIndex]>,
     ) -> Result<Script, DeriveError>;
 
+    /// Reserves next change index which is not yet present in the `used`
+    /// index list, records it into the list (normalizing it afterwards) and
+    /// returns the index together with the corresponding change scriptPubkey.
+    ///
+    /// Descriptors with a single-step derive pattern (like `/1/*`) are derived
+    /// at the reserved index; descriptors with two-step derive pattern (like
+    /// `/{0,1}/*`) are derived at `1/index` change branch.
+    ///
+    /// Since the `used` list is updated, subsequent calls never return the same
+    /// index, even before the change output gets confirmed.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`DeriveError::ChangeIndexExhausted`] if all unhardened
+    /// indexes are already present in the `used` list.
+    fn reserve_change<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        used: &mut Option<IndexRangeList<UnhardenedIndex>>,
+    ) -> Result<(UnhardenedIndex, Script), DeriveError> {
+        let mut next = 0u32;
+        if let Some(used) = used {
+            for range in used.iter() {
+                if range.first_index() > next {
+                    break;
+                }
+                next = next.max(
+                    range
+                        .last_index()
+                        .checked_add(1)
+                        .ok_or(DeriveError::ChangeIndexExhausted)?,
+                );
+            }
+        }
+        let index =
+            UnhardenedIndex::from_index(next).map_err(|_| DeriveError::ChangeIndexExhausted)?;
+
+        let script = match self.derive_pattern_len()? {
+            1 => self.script_pubkey(secp, [index])?,
+            2 => self.script_pubkey(secp, [UnhardenedIndex::one(), index])?,
+            _ => return Err(DeriveError::DerivePatternMismatch),
+        };
+
+        match used {
+            Some(used) => {
+                used.insert(IndexRange::single(index))
+                    .expect("reserved index is not present in the used list");
+                used.normalize();
+            }
+            None => *used = Some(IndexRangeList::from(IndexRange::single(index))),
+        }
+
+        Ok((index, script))
+    }
+
     #[doc(hidden)]
     fn _phantom(_: Key) { unreachable!("phantom method holding generic parameter") }
 }
@@ -226,12 +383,7 @@ where
     fn derive_pattern_len(&self) -> Result<usize, DeriveError> {
         let len = Cell::new(None);
         self.for_each_key(|key| {
-            let c = key
-                .as_key()
-                .terminal_path
-                .iter()
-                .filter(|step| step.count() > 1)
-                .count();
+            let c = key.as_key().derive_pattern_len();
             match (len.get(), c) {
                 (None, c) => {
                     len.set(Some(c));
@@ -281,3 +433,98 @@ where
         Ok(DescriptorTrait::script_pubkey(&d))
     }
 }
+
+#[cfg(all(test, feature = "miniscript"))]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn miniscript_descriptor() {
+        let descriptor = "wsh(multi(2,\
+            [73c5da0a/48'/0'/0'/2']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*,\
+            [d34db33f/48'/0'/0'/2']xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ/0/*\
+            ))";
+        let accounts = miniscript::Descriptor::<DerivationAccount>::from_str(descriptor).unwrap();
+        let converted = accounts.to_miniscript_descriptor().unwrap();
+        assert_eq!(
+            converted,
+            miniscript::Descriptor::<DescriptorPublicKey>::from_str(descriptor).unwrap()
+        );
+        assert_eq!(converted.to_string().split('#').next(), Some(descriptor));
+        assert!(converted.max_satisfaction_weight().is_ok());
+        assert!(converted.sanity_check().is_ok());
+
+        let secp = Secp256k1::verification_only();
+        let index = UnhardenedIndex::from(5u8);
+        assert_eq!(
+            Descriptor::<bitcoin::PublicKey>::script_pubkey(&accounts, &secp, [index]).unwrap(),
+            converted
+                .derive(5)
+                .translate_pk2(|key| key.derive_public_key(&secp))
+                .unwrap()
+                .script_pubkey()
+        );
+
+        let multipath = miniscript::Descriptor::<DerivationAccount>::from_str(
+            "wpkh([73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/<0;1>/*)",
+        )
+        .unwrap();
+        assert!(matches!(
+            multipath.to_miniscript_descriptor(),
+            Err(DeriveError::NonMiniscriptPath)
+        ));
+    }
+
+    #[test]
+    fn reserve_change_distinct() {
+        let secp = Secp256k1::verification_only();
+        let descriptor = miniscript::Descriptor::<DerivationAccount>::from_str(
+            "wpkh([00000000/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/<0;1>/*)",
+        )
+        .unwrap();
+
+        let mut used = None;
+        let (index0, script0) =
+            Descriptor::<bitcoin::PublicKey>::reserve_change(&descriptor, &secp, &mut used)
+                .unwrap();
+        let (index1, script1) =
+            Descriptor::<bitcoin::PublicKey>::reserve_change(&descriptor, &secp, &mut used)
+                .unwrap();
+        let (index2, script2) =
+            Descriptor::<bitcoin::PublicKey>::reserve_change(&descriptor, &secp, &mut used)
+                .unwrap();
+
+        assert_eq!(index0, UnhardenedIndex::zero());
+        assert_eq!(index1, UnhardenedIndex::one());
+        assert_eq!(index2, UnhardenedIndex::from_index(2u32).unwrap());
+        assert_ne!(script0, script1);
+        assert_ne!(script1, script2);
+        assert_ne!(script0, script2);
+        assert_eq!(
+            used,
+            Some(IndexRangeList::from(IndexRange::with(
+                UnhardenedIndex::zero(),
+                index2
+            )))
+        );
+        assert_eq!(
+            script0,
+            Descriptor::<bitcoin::PublicKey>::script_pubkey(&descriptor, &secp, [
+                UnhardenedIndex::one(),
+                UnhardenedIndex::zero()
+            ])
+            .unwrap()
+        );
+
+        let mut used = Some(IndexRangeList::from(IndexRange::with(
+            UnhardenedIndex::zero(),
+            UnhardenedIndex::largest(),
+        )));
+        assert!(matches!(
+            Descriptor::<bitcoin::PublicKey>::reserve_change(&descriptor, &secp, &mut used),
+            Err(DeriveError::ChangeIndexExhausted)
+        ));
+    }
+}
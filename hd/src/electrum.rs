@@ -0,0 +1,188 @@
+// Descriptor wallet library extending bitcoin & miniscript functionality
+// by LNP/BP Association (https://lnp-bp.org)
+// Written in 2020-2022 by
+//     Dr. Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the Apache-2.0 License
+// along with this software.
+// If not, see <https://opensource.org/licenses/Apache-2.0>.
+
+//! Importing master public keys exported by Electrum wallet.
+//!
+//! Electrum 2.x and later exports extended public keys using SLIP-132 version
+//! bytes indicating the wallet script type (`xpub`, `ypub`, `zpub`, `Ypub`,
+//! `Zpub` and their testnet counterparts). Keys of wallets created from
+//! Electrum's own seeds are located at Electrum-specific derivation paths:
+//! - `m` for standard (P2PKH and P2SH multisig) wallets;
+//! - `m/0h` for single-sig segwit wallets;
+//! - `m/1h` for multisig segwit wallets.
+//!
+//! Wallets restored from BIP-39 seeds use user-provided derivation paths.
+
+use bitcoin::util::base58;
+use bitcoin::util::bip32::ExtendedPubKey;
+use slip132::{DefaultResolver, FromSlip132, KeyApplication, KeyVersion};
+
+use crate::{
+    AccountStep, Bip43, DerivationAccount, DerivationStandard, SegmentIndexes, TerminalStep,
+    UnhardenedIndex, XpubRef,
+};
+
+/// Errors importing Electrum master public key
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum ElectrumError {
+    /// SLIP-132 related errors.
+    #[display(inner)]
+    #[from]
+    Slip132(slip132::Error),
+
+    /// Electrum 1.x master public key can't be imported since it has no chain
+    /// code and uses non-BIP-32 key derivation
+    OldMasterPublicKey,
+
+    /// provided key is an extended private key; only master public keys can
+    /// be imported
+    PrivateKey,
+}
+
+/// Parses master public key exported by Electrum into watch-only
+/// [`DerivationAccount`] with `/{0,1}/*` terminal path (covering Electrum
+/// receive and change addresses) and returns it together with the derivation
+/// scheme implied by the key version bytes.
+///
+/// The key origin is filled in when it can be recovered from the key itself:
+/// for depth-0 keys of Electrum standard wallets the key is the master key,
+/// and for depth-1 keys of Electrum segwit wallets (`m/0h` and `m/1h`) the
+/// parent key is the master key. For deeper keys (like the ones from BIP-39
+/// wallets) the master key is unknown.
+///
+/// Since Electrum uses `xpub` both for P2PKH and P2SH multisig wallets, `xpub`
+/// keys are reported with [`Bip43::Bip44`] scheme.
+pub fn account_from_electrum_mpk(mpk: &str) -> Result<(Bip43, DerivationAccount), ElectrumError> {
+    let mpk = mpk.trim();
+    if mpk.len() == 128 && mpk.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ElectrumError::OldMasterPublicKey);
+    }
+
+    let data = base58::from_check(mpk).map_err(slip132::Error::from)?;
+    let version = data
+        .get(..4)
+        .and_then(KeyVersion::from_slice)
+        .ok_or(slip132::Error::WrongExtendedKeyLength(data.len()))?;
+    let application = match version.is_pub::<DefaultResolver>() {
+        Some(false) => return Err(ElectrumError::PrivateKey),
+        // SLIP-132 does not define application for `xpub` and `tpub` keys,
+        // which are used by Electrum for standard wallets
+        Some(true) => version
+            .application::<DefaultResolver>()
+            .unwrap_or(KeyApplication::Hashed),
+        None => return Err(slip132::Error::UnknownSlip32Prefix.into()),
+    };
+    let scheme = Bip43::matching(application).ok_or(slip132::Error::UnknownSlip32Prefix)?;
+    let account_xpub = ExtendedPubKey::from_slip132_str(mpk)?;
+
+    let (master, account_path) = match account_xpub.depth {
+        0 => (XpubRef::Fingerprint(account_xpub.fingerprint()), empty!()),
+        1 => (
+            XpubRef::Fingerprint(account_xpub.parent_fingerprint),
+            vec![AccountStep::try_from(account_xpub.child_number).map_err(slip132::Error::from)?]
+                .into(),
+        ),
+        _ => (XpubRef::Unknown, empty!()),
+    };
+
+    Ok((scheme, DerivationAccount {
+        master,
+        account_path,
+        account_xpub,
+        revocation_seal: None,
+        terminal_path: vec![
+            TerminalStep::range(UnhardenedIndex::zero(), UnhardenedIndex::one()),
+            TerminalStep::Wildcard,
+        ]
+        .into_iter()
+        .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bitcoin::secp256k1::Secp256k1;
+    use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey};
+    use bitcoin::Network;
+    use slip132::ToSlip132;
+
+    use super::*;
+
+    fn electrum_key(path: &str, application: KeyApplication) -> (ExtendedPubKey, String) {
+        let secp = Secp256k1::new();
+        let master = ExtendedPrivKey::new_master(Network::Bitcoin, &[7u8; 32]).unwrap();
+        let path = DerivationPath::from_str(path).unwrap();
+        let xpriv = master.derive_priv(&secp, &path).unwrap();
+        let xpub = ExtendedPubKey::from_priv(&secp, &xpriv);
+        (xpub, xpub.to_slip132_string(application, Network::Bitcoin))
+    }
+
+    #[test]
+    fn electrum_standard() {
+        let (xpub, mpk) = electrum_key("m", KeyApplication::Hashed);
+        let (scheme, account) = account_from_electrum_mpk(&mpk).unwrap();
+        assert_eq!(scheme, Bip43::Bip44);
+        assert_eq!(account.account_xpub, xpub);
+        assert_eq!(account.master, XpubRef::Fingerprint(xpub.fingerprint()));
+        assert!(account.account_path.is_empty());
+        assert_eq!(
+            format!("{:#}", account),
+            format!("[{}]{}/{{0,1}}/*", xpub.fingerprint(), xpub)
+        );
+    }
+
+    #[test]
+    fn electrum_segwit() {
+        let (master, _) = electrum_key("m", KeyApplication::Hashed);
+        let (xpub, mpk) = electrum_key("m/0h", KeyApplication::SegWit);
+        assert!(mpk.starts_with("zpub"));
+        let (scheme, account) = account_from_electrum_mpk(&mpk).unwrap();
+        assert_eq!(scheme, Bip43::Bip84);
+        assert_eq!(account.account_xpub, xpub);
+        assert_eq!(account.master_fingerprint(), Some(master.fingerprint()));
+        assert_eq!(account.to_account_derivation_path().to_string(), "m/0'");
+        account.check_origin().unwrap();
+
+        let (_, mpk) = electrum_key("m/1h", KeyApplication::SegWitMultisig);
+        assert_eq!(
+            account_from_electrum_mpk(&mpk).unwrap().0,
+            Bip43::Bip48Native
+        );
+
+        let (_, mpk) = electrum_key("m/49h/0h/0h", KeyApplication::Nested);
+        let (scheme, account) = account_from_electrum_mpk(&mpk).unwrap();
+        assert_eq!(scheme, Bip43::Bip49);
+        assert_eq!(account.master, XpubRef::Unknown);
+    }
+
+    #[test]
+    fn electrum_invalid() {
+        assert_eq!(
+            account_from_electrum_mpk(&"ab".repeat(64)),
+            Err(ElectrumError::OldMasterPublicKey)
+        );
+        let master = ExtendedPrivKey::new_master(Network::Bitcoin, &[7u8; 32]).unwrap();
+        assert_eq!(
+            account_from_electrum_mpk(&master.to_string()),
+            Err(ElectrumError::PrivateKey)
+        );
+        assert!(matches!(
+            account_from_electrum_mpk("zpub"),
+            Err(ElectrumError::Slip132(_))
+        ));
+    }
+}
@@ -14,6 +14,7 @@
 
 use std::cmp::Ordering;
 use std::fmt::{self, Display, Formatter};
+use std::io;
 use std::str::FromStr;
 
 use bitcoin::util::bip32::{self, ChildNumber, Error};
@@ -242,7 +243,7 @@ pub struct UnhardenedIndexExpected(pub HardenedIndex);
 #[derive(
     Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default, Display, From
 )]
-#[derive(StrictEncode, StrictDecode)]
+#[derive(StrictEncode)]
 #[display(inner)]
 pub struct UnhardenedIndex(
     #[from(u8)]
@@ -316,6 +317,18 @@ impl SegmentIndexes for UnhardenedIndex {
     fn is_hardened(&self) -> bool { false }
 }
 
+impl StrictDecode for UnhardenedIndex {
+    fn strict_decode<D: io::Read>(d: D) -> Result<Self, strict_encoding::Error> {
+        let index = u32::strict_decode(d)?;
+        UnhardenedIndex::from_index(index).map_err(|_| {
+            strict_encoding::Error::DataIntegrityError(format!(
+                "unhardened index value {} exceeds hardened index boundary",
+                index
+            ))
+        })
+    }
+}
+
 impl FromStr for UnhardenedIndex {
     type Err = bip32::Error;
 
@@ -709,6 +722,19 @@ impl TryFrom<AccountStep> for HardenedIndex {
     }
 }
 
+impl TryFrom<AccountStep> for TerminalStep {
+    type Error = bip32::Error;
+
+    fn try_from(value: AccountStep) -> Result<Self, Self::Error> {
+        match value {
+            AccountStep::Normal(index) => Ok(TerminalStep::Index(index)),
+            AccountStep::Hardened { index, .. } => {
+                Err(bip32::Error::InvalidChildNumber(index.first_index()))
+            }
+        }
+    }
+}
+
 /// Derivation segment for the terminal part of the derivation path as defined
 /// by LNPBP-32 standard
 // TODO: Move serde to `TerminalPath` using FromStrDisplay once it will be present
@@ -746,6 +772,33 @@ impl TerminalStep {
             end.into(),
         )))
     }
+
+    /// Constructs terminal step covering an explicit set of indexes (like
+    /// `{0,3,7}`). Adjacent indexes are merged into ranges.
+    ///
+    /// Errors if the set is empty or contains repeated indexes.
+    pub fn set(indexes: impl IntoIterator<Item = UnhardenedIndex>) -> Result<Self, bip32::Error> {
+        let mut list = IndexRangeList::with(indexes.into_iter().map(IndexRange::single))?;
+        list.normalize();
+        Ok(TerminalStep::Range(list))
+    }
+
+    /// Converts the step into a list of index ranges covering the same
+    /// indexes.
+    pub fn to_range_list(&self) -> IndexRangeList<UnhardenedIndex> {
+        match self {
+            TerminalStep::Index(index) => IndexRangeList::from(IndexRange::single(*index)),
+            TerminalStep::Range(list) => list.clone(),
+            TerminalStep::Wildcard => IndexRangeList::from(IndexRange::full()),
+        }
+    }
+
+    /// Returns iterator over all indexes covered by the step, in ascending
+    /// order. For explicit index sets it enumerates exactly the indexes from
+    /// the set.
+    pub fn indexes(&self) -> impl Iterator<Item = UnhardenedIndex> {
+        self.to_range_list().into_indexes()
+    }
 }
 
 impl SegmentIndexes for TerminalStep {
@@ -850,7 +903,11 @@ impl FromStr for TerminalStep {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(match s {
             "*" => TerminalStep::Wildcard,
-            s if s.contains(&['-', ',', ';'][..]) => IndexRangeList::from_str(s)?.into(),
+            s if s.contains(&['-', ',', ';', '{', '<'][..]) => {
+                let mut list = IndexRangeList::from_str(s)?;
+                list.normalize();
+                list.into()
+            }
             s => UnhardenedIndex::from_str(s)?.into(),
         })
     }
@@ -867,6 +924,14 @@ impl TryFrom<TerminalStep> for UnhardenedIndex {
     }
 }
 
+impl TryFrom<TerminalStep> for AccountStep {
+    type Error = bip32::Error;
+
+    fn try_from(value: TerminalStep) -> Result<Self, Self::Error> {
+        UnhardenedIndex::try_from(value).map(AccountStep::Normal)
+    }
+}
+
 impl TryFrom<ChildNumber> for TerminalStep {
     type Error = bip32::Error;
 
@@ -892,3 +957,157 @@ impl TryFrom<TerminalStep> for ChildNumber {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn step_conversions() {
+        let normal = UnhardenedIndex::from(5u8);
+        let hardened = HardenedIndex::from(5u8);
+
+        assert_eq!(AccountStep::from(normal), AccountStep::Normal(normal));
+        assert_eq!(AccountStep::from(hardened), AccountStep::hardened(hardened));
+        assert_eq!(TerminalStep::from(normal), TerminalStep::Index(normal));
+
+        assert_eq!(
+            HardenedIndex::try_from(AccountStep::from(hardened)),
+            Ok(hardened)
+        );
+        assert!(HardenedIndex::try_from(AccountStep::from(normal)).is_err());
+        assert_eq!(
+            UnhardenedIndex::try_from(AccountStep::from(normal)),
+            Ok(normal)
+        );
+        assert!(UnhardenedIndex::try_from(AccountStep::from(hardened)).is_err());
+
+        assert_eq!(
+            TerminalStep::try_from(AccountStep::from(normal)),
+            Ok(TerminalStep::Index(normal))
+        );
+        assert_eq!(
+            TerminalStep::try_from(AccountStep::from(hardened)),
+            Err(bip32::Error::InvalidChildNumber(hardened.first_index()))
+        );
+        assert_eq!(
+            AccountStep::try_from(TerminalStep::Index(normal)),
+            Ok(AccountStep::Normal(normal))
+        );
+        assert!(AccountStep::try_from(TerminalStep::Wildcard).is_err());
+        assert!(AccountStep::try_from(TerminalStep::range(0u8, 1u8)).is_err());
+    }
+
+    #[test]
+    fn terminal_range_bounds() {
+        let largest = HARDENED_INDEX_BOUNDARY - 1;
+
+        assert_eq!(
+            TerminalStep::from_str(&largest.to_string()),
+            Ok(TerminalStep::Index(UnhardenedIndex::largest()))
+        );
+        assert_eq!(
+            TerminalStep::from_str(&format!("0-{}", largest)),
+            Ok(TerminalStep::range(
+                UnhardenedIndex::zero(),
+                UnhardenedIndex::largest()
+            ))
+        );
+        assert_eq!(
+            TerminalStep::from_str(&format!("{{0,{}}}", largest))
+                .unwrap()
+                .last_index(),
+            largest
+        );
+
+        for s in [
+            HARDENED_INDEX_BOUNDARY.to_string(),
+            format!("0-{}", HARDENED_INDEX_BOUNDARY),
+            format!("{}-{}", largest, HARDENED_INDEX_BOUNDARY),
+            s!("0-4294967295"),
+            format!("{{0,{}}}", HARDENED_INDEX_BOUNDARY),
+            format!("<0;{}>", HARDENED_INDEX_BOUNDARY),
+        ] {
+            assert!(TerminalStep::from_str(&s).is_err(), "{} must not parse", s);
+        }
+    }
+
+    #[test]
+    fn terminal_index_set() {
+        let indexes = [0u8, 3, 7].map(UnhardenedIndex::from);
+        let step = TerminalStep::from_str("{0,3,7}").unwrap();
+        assert_eq!(step.to_string(), "{0,3,7}");
+        assert_eq!(TerminalStep::set(indexes), Ok(step.clone()));
+        assert_eq!(step.count(), 3);
+        assert_eq!(step.indexes().collect::<Vec<_>>(), indexes.to_vec());
+        assert!(step.contains(3) && !step.contains(4));
+
+        assert_eq!(
+            TerminalStep::from_str("{5}"),
+            Ok(TerminalStep::set([UnhardenedIndex::from(5u8)]).unwrap())
+        );
+        assert_eq!(
+            TerminalStep::set([2u8, 0, 1, 5].map(UnhardenedIndex::from)),
+            Ok(TerminalStep::from_str("{0-2,5}").unwrap())
+        );
+        assert_eq!(
+            TerminalStep::from_str("{2-3,8}")
+                .unwrap()
+                .indexes()
+                .collect::<Vec<_>>(),
+            [2u8, 3, 8].map(UnhardenedIndex::from).to_vec()
+        );
+        assert_eq!(TerminalStep::from(4u8).indexes().count(), 1);
+
+        // Parsed and constructed sets are normalized in the same way
+        for (s, indexes, display) in [
+            ("{0,1}", vec![0u8, 1], "{0,1}"),
+            ("<0;1>", vec![0, 1], "{0,1}"),
+            ("{0,1,2,5}", vec![0, 1, 2, 5], "{0-2,5}"),
+        ] {
+            let parsed = TerminalStep::from_str(s).unwrap();
+            let set = TerminalStep::set(indexes.into_iter().map(UnhardenedIndex::from)).unwrap();
+            assert_eq!(parsed, set);
+            assert_eq!(parsed.to_string(), display);
+            assert_eq!(set.to_string(), display);
+            assert_eq!(TerminalStep::from_str(display), Ok(set));
+        }
+
+        for s in ["{}", "{0,3,3}", "{0,2-4,3}", "{0,,3}"] {
+            assert!(TerminalStep::from_str(s).is_err(), "{} must not parse", s);
+        }
+        assert!(TerminalStep::set([]).is_err());
+        assert!(TerminalStep::set([1u8, 1].map(UnhardenedIndex::from)).is_err());
+    }
+
+    #[test]
+    fn unhardened_index_strict_decode() {
+        let largest = HARDENED_INDEX_BOUNDARY - 1;
+        assert_eq!(
+            UnhardenedIndex::strict_deserialize(largest.strict_serialize().unwrap()),
+            Ok(UnhardenedIndex::largest())
+        );
+        assert!(UnhardenedIndex::strict_deserialize(
+            HARDENED_INDEX_BOUNDARY.strict_serialize().unwrap()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn index_ordering() {
+        assert!(HardenedIndex::zero() < HardenedIndex::one());
+        assert!(UnhardenedIndex::zero() < UnhardenedIndex::one());
+        assert!(HardenedIndex::one() < HardenedIndex::largest());
+        assert!(UnhardenedIndex::one() < UnhardenedIndex::largest());
+
+        let map = bmap! {
+            HardenedIndex::from_index(2u32).unwrap() => 2u8,
+            HardenedIndex::zero() => 0u8,
+            HardenedIndex::one() => 1u8
+        };
+        assert_eq!(map.into_values().collect::<Vec<_>>(), vec![0, 1, 2]);
+
+        let set = std::collections::HashSet::from([UnhardenedIndex::one(), UnhardenedIndex::one()]);
+        assert_eq!(set.len(), 1);
+    }
+}
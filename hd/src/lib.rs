@@ -31,27 +31,46 @@ extern crate strict_encoding;
 extern crate serde_crate as serde;
 
 pub mod account;
+mod cache;
 mod derive;
+#[cfg(feature = "electrum")]
+mod electrum;
 mod indexes;
 mod path;
 mod ranges;
+mod redacted;
+mod scanner;
+#[cfg(feature = "bip39")]
+mod seed;
 pub mod standards;
 mod traits;
 mod unsatisfiable;
+mod wipe;
 mod xkey;
 mod xpubref;
 
-pub use account::DerivationAccount;
+pub use account::{
+    AddressInfo, DerivationAccount, MergeError, MAX_DERIVATION_DEPTH, MAX_MULTIPATH_BRANCHES,
+};
+pub use cache::{DerivationCache, DerivationCacheError};
+#[cfg(feature = "miniscript")]
+pub use derive::ToMiniscriptDescriptor;
 pub use derive::{DeriveDescriptor, DeriveError, DerivePatternError, DerivePublicKey, Descriptor};
+#[cfg(feature = "electrum")]
+pub use electrum::{account_from_electrum_mpk, ElectrumError};
 pub use indexes::{
     AccountStep, HardenedIndex, HardenedIndexExpected, SegmentIndexes, TerminalStep,
     UnhardenedIndex, UnhardenedIndexExpected,
 };
 pub use path::DerivationSubpath;
-pub use ranges::{IndexRange, IndexRangeList};
+pub use ranges::{IndexRange, IndexRangeList, RangeError};
+pub use redacted::RedactedAccount;
+pub use scanner::GapScanner;
+#[cfg(feature = "bip39")]
+pub use seed::{account_xpub_from_mnemonic, SeedError};
 #[cfg(not(feature = "miniscript"))]
 pub use standards::DescriptorType;
-pub use standards::{Bip43, DerivationStandard};
+pub use standards::{account_xpub, Bip43, DerivationStandard};
 pub use traits::{DerivationPathMaster, HardenedNormalSplit};
 pub use unsatisfiable::UnsatisfiableKey;
 pub use xkey::{
@@ -193,3 +193,22 @@ where
     /// Constructs empty derivation path.
     pub fn new() -> Self { Self::default() }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::UnhardenedIndex;
+
+    #[test]
+    fn path_ordering() {
+        let path = |s: &str| DerivationSubpath::<UnhardenedIndex>::from_str(s).unwrap();
+        let mut paths = vec![path("/1/0"), path("/0/1/0"), path("/0/1"), path("/0/0/5")];
+        paths.sort();
+        assert_eq!(paths, vec![
+            path("/0/0/5"),
+            path("/0/1"),
+            path("/0/1/0"),
+            path("/1/0")
+        ]);
+    }
+}
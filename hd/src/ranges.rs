@@ -13,7 +13,7 @@
 // If not, see <https://opensource.org/licenses/Apache-2.0>.
 
 use std::cmp::Ordering;
-use std::collections::BTreeSet;
+use std::collections::{btree_set, BTreeSet};
 use std::fmt::{self, Display, Formatter};
 use std::io;
 use std::ops::RangeInclusive;
@@ -23,7 +23,42 @@ use amplify::Wrapper;
 use bitcoin::util::bip32;
 use strict_encoding::{self, StrictDecode, StrictEncode};
 
-use crate::SegmentIndexes;
+use crate::{SegmentIndexes, HARDENED_INDEX_BOUNDARY};
+
+/// Errors constructing [`IndexRange`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum RangeError {
+    /// index range end {end} must not be less than its start {start}
+    ReversedBounds {
+        /// Start index of the range
+        start: u32,
+        /// End index of the range
+        end: u32,
+    },
+
+    /// index range step must be a positive number
+    ZeroStep,
+
+    /// can't split {total} indexes into {count} non-empty shards
+    EmptyShard {
+        /// Number of indexes to split into shards
+        total: u32,
+        /// Number of shards
+        count: u32,
+    },
+
+    /// shard {shard} is out of range of {count} shards
+    ShardOutOfRange {
+        /// Requested shard number
+        shard: u32,
+        /// Number of shards
+        count: u32,
+    },
+
+    /// {0} indexes exceed the number of unhardened or hardened indexes
+    IndexOverflow(u32),
+}
 
 // TODO: Implement iterator methods
 
@@ -83,6 +118,49 @@ where
     #[inline]
     pub fn remove(&mut self, range: &IndexRange<Index>) -> bool { self.0.remove(range) }
 
+    /// Merges adjacent index ranges present in the list (like `0-4` and `5-7`)
+    /// into a single range, such that the list always contains the minimal
+    /// number of ranges covering the same indexes.
+    pub fn normalize(&mut self) {
+        let mut normalized = BTreeSet::<IndexRange<Index>>::new();
+        for range in &self.0 {
+            match normalized.iter().last().cloned() {
+                Some(last)
+                    if last.step() == 1
+                        && range.step() == 1
+                        && last.last_index().checked_add(1) == Some(range.first_index()) =>
+                {
+                    normalized.remove(&last);
+                    normalized.insert(IndexRange::with(
+                        last.as_inner().start().clone(),
+                        range.as_inner().end().clone(),
+                    ));
+                }
+                _ => {
+                    normalized.insert(range.clone());
+                }
+            }
+        }
+        self.0 = normalized;
+    }
+
+    /// Returns iterator over disjoint index ranges in the list, ordered by
+    /// their first index.
+    #[inline]
+    pub fn iter(&self) -> btree_set::Iter<'_, IndexRange<Index>> { self.0.iter() }
+
+    /// Returns iterator over all individual indexes covered by the ranges in
+    /// the list, in ascending order.
+    pub fn indexes(&self) -> impl Iterator<Item = Index> + '_ {
+        self.0.iter().flat_map(IndexRange::indexes)
+    }
+
+    /// Converts the list into an iterator over all individual indexes covered
+    /// by its ranges, in ascending order.
+    pub fn into_indexes(self) -> impl Iterator<Item = Index> {
+        self.0.into_iter().flat_map(|range| range.indexes())
+    }
+
     /// Counts number of disjoint ranges withing the list
     #[inline]
     pub fn range_count(&self) -> usize { self.0.len() }
@@ -106,6 +184,36 @@ where
             .last()
             .expect("IndexRangeList guarantees are broken")
     }
+
+    /// Splits the provided indexes into those which are covered by the ranges
+    /// from the list and those which are not, preserving the original order
+    /// of indexes in both parts.
+    ///
+    /// The indexes are matched against the ranges in a single merge pass,
+    /// which takes linear time for indexes sorted in ascending order; indexes
+    /// going out of order fall back to a binary search over the ranges.
+    pub fn partition(&self, indexes: &[Index]) -> (Vec<Index>, Vec<Index>) {
+        let ranges = self.0.iter().collect::<Vec<_>>();
+        let mut watched = Vec::with_capacity(indexes.len());
+        let mut unwatched = Vec::new();
+        let mut pos = 0usize;
+        let mut prev = None;
+        for index in indexes {
+            let value = index.first_index();
+            if prev > Some(value) {
+                pos = ranges.partition_point(|range| range.last_index() < value);
+            }
+            while pos < ranges.len() && ranges[pos].last_index() < value {
+                pos += 1;
+            }
+            prev = Some(value);
+            match ranges.get(pos) {
+                Some(range) if range.contains(value) => watched.push(index.clone()),
+                _ => unwatched.push(index.clone()),
+            }
+        }
+        (watched, unwatched)
+    }
 }
 
 impl<Index> SegmentIndexes for IndexRangeList<Index>
@@ -197,6 +305,18 @@ where
     fn from(range: IndexRange<Index>) -> Self { Self(bset![range]) }
 }
 
+/// Returns bounds of the range if it consists of two consecutive indexes.
+/// Such ranges are displayed as pairs of indexes (like `{0,1}`), which are
+/// parsed and normalized back into the same ranges.
+fn consecutive_pair<Index>(range: &IndexRange<Index>) -> Option<(&Index, &Index)>
+where
+    Index: SegmentIndexes,
+{
+    let inner = range.as_inner();
+    let consecutive = inner.start().first_index().checked_add(1) == Some(inner.end().first_index());
+    (range.step() == 1 && consecutive).then(|| (inner.start(), inner.end()))
+}
+
 impl<Index> Display for IndexRangeList<Index>
 where
     Index: SegmentIndexes + Display,
@@ -206,7 +326,10 @@ where
             // Use Sparrow formatting
             f.write_str("<")?;
             for (index, range) in self.0.iter().enumerate() {
-                Display::fmt(range, f)?;
+                match consecutive_pair(range) {
+                    Some((first, second)) => write!(f, "{:#};{:#}", first, second)?,
+                    None => Display::fmt(range, f)?,
+                }
                 if index < self.0.len() - 1 {
                     f.write_str(";")?;
                 }
@@ -216,7 +339,10 @@ where
             // Use BIP-88 formatting
             let mut s = String::new();
             for (index, range) in self.0.iter().enumerate() {
-                s.extend(format!("{}", range).chars());
+                match consecutive_pair(range) {
+                    Some((first, second)) => s.push_str(&format!("{},{}", first, second)),
+                    None => s.push_str(&format!("{}", range)),
+                }
                 if index < self.0.len() - 1 {
                     s.push(',');
                 }
@@ -236,22 +362,28 @@ where
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut list = Self(bset![]);
-        let s = if s.ends_with(&['h', '\''][..]) {
-            let mut s = s
-                .trim_end_matches(&['h', '\''][..])
-                .replace(',', "h,")
-                .replace(';', "h;")
-                .replace('-', "h-");
-            s.push('h');
-            s
-        } else {
-            s.to_owned()
-        };
+        let hardened = s.ends_with(&['h', '\''][..]);
         let s = s
+            .trim_end_matches(&['h', '\''][..])
             .trim_start_matches(&['<', '{'][..])
             .trim_end_matches(&['>', '}'][..]);
         for item in s.split(&[',', ';'][..]) {
-            list.insert(IndexRange::from_str(item)?)?;
+            if !hardened {
+                list.insert(IndexRange::from_str(item)?)?;
+                continue;
+            }
+            // Hardening suffix applies to the range bounds and not to its step
+            let (bounds, step) = match item.split_once(':') {
+                Some((bounds, step)) => (bounds, Some(step)),
+                None => (item, None),
+            };
+            let mut item = bounds.replace('-', "h-");
+            item.push('h');
+            if let Some(step) = step {
+                item.push(':');
+                item.push_str(step);
+            }
+            list.insert(IndexRange::from_str(&item)?)?;
         }
         Ok(list)
     }
@@ -261,25 +393,92 @@ where
 /// derivation path terminal segment according to BIP-88 and LNPBP-32. The range
 /// is always inclusive.
 ///
-/// The type is guaranteed to have at least one index in the range.
+/// The range may have a step (in form `n-m:step`), in which case it covers
+/// only every `step`-th index starting from `n`. Ranges constructed without
+/// a step have step of 1 and cover all indexes between their bounds.
+///
+/// The type is guaranteed to have at least one index in the range, a non-zero
+/// step and its end bound to be one of the indexes covered by the range.
 // TODO: Remove serde impl and use FromStrDisplay on top instead
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
-    serde(crate = "serde_crate", transparent)
+    serde(
+        crate = "serde_crate",
+        into = "IndexRangeSerde<Index>",
+        try_from = "IndexRangeSerde<Index>",
+        bound(
+            serialize = "Index: serde::Serialize + Clone",
+            deserialize = "Index: serde::Deserialize<'de>"
+        )
+    )
 )]
-#[derive(Wrapper, Clone, PartialEq, Eq, Hash, Debug, From)]
-pub struct IndexRange<Index>(RangeInclusive<Index>)
+#[derive(Wrapper, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct IndexRange<Index>(#[wrap] RangeInclusive<Index>, u32)
 where
     Index: SegmentIndexes;
 
+impl<Index> From<RangeInclusive<Index>> for IndexRange<Index>
+where
+    Index: SegmentIndexes,
+{
+    fn from(range: RangeInclusive<Index>) -> Self { Self(range, 1) }
+}
+
+/// Serde representation of [`IndexRange`], compatible with the serialization
+/// of [`RangeInclusive`] for ranges without step.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct IndexRangeSerde<Index> {
+    start: Index,
+    end: Index,
+    #[serde(default = "IndexRangeSerde::<Index>::default_step")]
+    #[serde(skip_serializing_if = "IndexRangeSerde::<Index>::is_default_step")]
+    step: u32,
+}
+
+#[cfg(feature = "serde")]
+impl<Index> IndexRangeSerde<Index> {
+    fn default_step() -> u32 { 1 }
+
+    fn is_default_step(step: &u32) -> bool { *step == 1 }
+}
+
+#[cfg(feature = "serde")]
+impl<Index> From<IndexRange<Index>> for IndexRangeSerde<Index>
+where
+    Index: SegmentIndexes,
+{
+    fn from(range: IndexRange<Index>) -> Self {
+        let step = range.step();
+        let (start, end) = range.into_inner().into_inner();
+        IndexRangeSerde { start, end, step }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<Index> TryFrom<IndexRangeSerde<Index>> for IndexRange<Index>
+where
+    Index: SegmentIndexes,
+{
+    type Error = RangeError;
+
+    fn try_from(range: IndexRangeSerde<Index>) -> Result<Self, Self::Error> {
+        IndexRange::with_step(range.start, range.end, range.step)
+    }
+}
+
 impl<Index> PartialOrd for IndexRange<Index>
 where
     Index: SegmentIndexes,
 {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         match self.first_index().partial_cmp(&other.first_index()) {
-            Some(Ordering::Equal) => self.last_index().partial_cmp(&other.last_index()),
+            Some(Ordering::Equal) => match self.last_index().partial_cmp(&other.last_index()) {
+                Some(Ordering::Equal) => self.step().partial_cmp(&other.step()),
+                other => other,
+            },
             other => other,
         }
     }
@@ -291,7 +490,10 @@ where
 {
     fn cmp(&self, other: &Self) -> Ordering {
         match self.first_index().cmp(&other.first_index()) {
-            Ordering::Equal => self.last_index().cmp(&other.last_index()),
+            Ordering::Equal => match self.last_index().cmp(&other.last_index()) {
+                Ordering::Equal => self.step().cmp(&other.step()),
+                other => other,
+            },
             other => other,
         }
     }
@@ -302,19 +504,110 @@ where
     Index: SegmentIndexes,
 {
     /// Constructs index range from a single index.
-    pub fn new(index: Index) -> Self { Self(RangeInclusive::new(index.clone(), index)) }
+    pub fn single(index: Index) -> Self { Self(RangeInclusive::new(index.clone(), index), 1) }
+
+    /// Constructs index range from its inclusive bounds.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`RangeError::ReversedBounds`] if `end` < `start`.
+    pub fn new(start: Index, end: Index) -> Result<Self, RangeError> {
+        if end < start {
+            return Err(RangeError::ReversedBounds {
+                start: start.first_index(),
+                end: end.last_index(),
+            });
+        }
+        Ok(Self(RangeInclusive::new(start, end), 1))
+    }
+
+    /// Constructs index range from its inclusive bounds, covering only every
+    /// `step`-th index starting from `start`. If `end` is not reachable from
+    /// `start` with the given step, the range end is set to the last reachable
+    /// index before `end`.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`RangeError::ZeroStep`] if `step` is zero and with
+    /// [`RangeError::ReversedBounds`] if `end` < `start`.
+    pub fn with_step(start: Index, end: Index, step: u32) -> Result<Self, RangeError> {
+        if step == 0 {
+            return Err(RangeError::ZeroStep);
+        }
+        let range = Self::new(start, end)?;
+        let (first, last) = (range.first_index(), range.last_index());
+        if first == last {
+            return Ok(range);
+        }
+        let last = first + (last - first) / step * step;
+        let end = Index::from_index(last).expect("index range guarantees are broken");
+        let (start, _) = range.into_inner().into_inner();
+        Ok(Self(RangeInclusive::new(start, end), step))
+    }
+
+    /// Constructs `shard`-th (counting from zero) of `count` ranges of equal
+    /// length partitioning the first `total` indexes. The last shard also
+    /// covers the remaining indexes if `total` is not divisible by `count`.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`RangeError::IndexOverflow`] if `total` exceeds
+    /// [`HARDENED_INDEX_BOUNDARY`], with [`RangeError::EmptyShard`] if there
+    /// are less indexes than shards and with [`RangeError::ShardOutOfRange`]
+    /// if `shard` is not less than `count`.
+    pub fn shard(total: u32, shard: u32, count: u32) -> Result<Self, RangeError> {
+        if total > HARDENED_INDEX_BOUNDARY {
+            return Err(RangeError::IndexOverflow(total));
+        }
+        if count == 0 || total < count {
+            return Err(RangeError::EmptyShard { total, count });
+        }
+        if shard >= count {
+            return Err(RangeError::ShardOutOfRange { shard, count });
+        }
+        let size = total / count;
+        let overflow = || RangeError::IndexOverflow(total);
+        let start = shard.checked_mul(size).ok_or_else(overflow)?;
+        let end = if shard + 1 == count {
+            total - 1
+        } else {
+            start.checked_add(size - 1).ok_or_else(overflow)?
+        };
+        let index = |index: u32| Index::from_index(index).map_err(|_| overflow());
+        IndexRange::new(index(start)?, index(end)?)
+    }
+
+    /// Constructs index range covering all possible indexes, from zero up to
+    /// the largest index value.
+    pub fn full() -> Self { Self(RangeInclusive::new(Index::zero(), Index::largest()), 1) }
 
     /// Constructs index range from two indexes. If `end` < `start` the order
     /// of indexes is reversed
     pub fn with(start: Index, end: Index) -> Self {
         if end < start {
-            Self(RangeInclusive::new(end, start))
+            Self(RangeInclusive::new(end, start), 1)
         } else {
-            Self(RangeInclusive::new(start, end))
+            Self(RangeInclusive::new(start, end), 1)
         }
     }
 
-    /// Detects whether two index ranges share common indexes (i.e. intersect)
+    /// Returns step between the indexes covered by the range; 1 for ranges
+    /// covering all indexes between their bounds.
+    #[inline]
+    pub fn step(&self) -> u32 { self.1 }
+
+    /// Returns iterator over all individual indexes within the range, in
+    /// ascending order.
+    pub fn indexes(&self) -> impl Iterator<Item = Index> {
+        (self.first_index()..=self.last_index())
+            .step_by(self.1 as usize)
+            .map(|index| Index::from_index(index).expect("index range guarantees are broken"))
+    }
+
+    /// Detects whether two index ranges share common indexes (i.e. intersect).
+    ///
+    /// Ranges with a step are conservatively treated as intersecting whenever
+    /// their bounds overlap.
     #[inline]
     pub fn does_intersect(&self, other: &IndexRange<Index>) -> bool {
         self.first_index() <= other.last_index() && other.first_index() <= self.last_index()
@@ -326,22 +619,24 @@ where
     Index: SegmentIndexes,
 {
     #[inline]
-    fn zero() -> Self { IndexRange(Index::zero()..=Index::zero()) }
+    fn zero() -> Self { IndexRange(Index::zero()..=Index::zero(), 1) }
 
     #[inline]
-    fn one() -> Self { IndexRange(Index::one()..=Index::one()) }
+    fn one() -> Self { IndexRange(Index::one()..=Index::one(), 1) }
 
     #[inline]
-    fn largest() -> Self { IndexRange(Index::largest()..=Index::largest()) }
+    fn largest() -> Self { IndexRange(Index::largest()..=Index::largest(), 1) }
 
     #[inline]
     fn count(&self) -> usize {
-        self.0.end().last_index() as usize - self.0.start().first_index() as usize + 1
+        (self.0.end().last_index() - self.0.start().first_index()) as usize / self.1 as usize + 1
     }
 
     #[inline]
     fn contains(&self, index: u32) -> bool {
-        self.0.start().first_index() <= index && self.0.end().last_index() >= index
+        self.0.start().first_index() <= index
+            && self.0.end().last_index() >= index
+            && (index - self.0.start().first_index()) % self.1 == 0
     }
 
     #[inline]
@@ -349,6 +644,7 @@ where
         let index = index.into();
         Ok(IndexRange(
             Index::from_index(index)?..=Index::from_index(index)?,
+            1,
         ))
     }
 
@@ -362,6 +658,7 @@ where
     fn from_derivation_value(value: u32) -> Result<Self, bip32::Error> {
         Ok(IndexRange(
             Index::from_derivation_value(value)?..=Index::from_derivation_value(value)?,
+            1,
         ))
     }
 
@@ -392,7 +689,11 @@ where
         } else {
             Display::fmt(inner.start(), f)?;
             f.write_str("-")?;
-            Display::fmt(inner.end(), f)
+            Display::fmt(inner.end(), f)?;
+            if self.1 != 1 {
+                write!(f, ":{}", self.1)?;
+            }
+            Ok(())
         }
     }
 }
@@ -405,23 +706,46 @@ where
     type Err = bip32::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (s, step) = match s.split_once(':') {
+            Some((s, step)) => {
+                let step = step
+                    .parse()
+                    .map_err(|_| bip32::Error::InvalidChildNumberFormat)?;
+                (s, Some(step))
+            }
+            None => (s, None),
+        };
         let mut split = s.split('-');
-        Ok(match (split.next(), split.next()) {
-            (Some(start), Some(end)) => {
+        Ok(match (split.next(), split.next(), step) {
+            (Some(start), Some(end), None) => {
                 IndexRange::with(Index::from_str(start)?, Index::from_str(end)?)
             }
-            (Some(start), None) => IndexRange::new(Index::from_str(start)?),
+            (Some(start), Some(end), Some(step)) => {
+                IndexRange::with_step(Index::from_str(start)?, Index::from_str(end)?, step)
+                    .map_err(|_| bip32::Error::InvalidDerivationPathFormat)?
+            }
+            (Some(start), None, None) => IndexRange::single(Index::from_str(start)?),
+            (Some(_), None, Some(_)) => return Err(bip32::Error::InvalidDerivationPathFormat),
             _ => unreachable!(),
         })
     }
 }
 
+/// Flag set in the encoded last index of ranges having a step, which is
+/// followed by the step value. Ranges without a step keep encoding used before
+/// steps were introduced.
+const STEP_ENCODING_FLAG: u32 = 1 << 31;
+
 impl<Index> StrictEncode for IndexRange<Index>
 where
     Index: SegmentIndexes + StrictEncode,
 {
     fn strict_encode<E: io::Write>(&self, mut e: E) -> Result<usize, strict_encoding::Error> {
-        Ok(strict_encode_list!(e; self.first_index(), self.last_index()))
+        if self.1 == 1 {
+            return Ok(strict_encode_list!(e; self.first_index(), self.last_index()));
+        }
+        let last = self.last_index() | STEP_ENCODING_FLAG;
+        Ok(strict_encode_list!(e; self.first_index(), last, self.1))
     }
 }
 
@@ -430,9 +754,169 @@ where
     Index: SegmentIndexes + StrictDecode,
 {
     fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
-        Ok(Self::from_inner(RangeInclusive::new(
-            Index::strict_decode(&mut d)?,
-            Index::strict_decode(&mut d)?,
-        )))
+        let start = Index::from_index(u32::strict_decode(&mut d)?)
+            .map_err(|err| strict_encoding::Error::DataIntegrityError(err.to_string()))?;
+        let last = u32::strict_decode(&mut d)?;
+        let step = if last & STEP_ENCODING_FLAG != 0 {
+            u32::strict_decode(&mut d)?
+        } else {
+            1
+        };
+        let end = Index::from_index(last & !STEP_ENCODING_FLAG)
+            .map_err(|err| strict_encoding::Error::DataIntegrityError(err.to_string()))?;
+        let range = Self::with_step(start, end, step)
+            .map_err(|err| strict_encoding::Error::DataIntegrityError(err.to_string()))?;
+        if range.last_index() != last & !STEP_ENCODING_FLAG {
+            return Err(strict_encoding::Error::DataIntegrityError(s!(
+                "IndexRange end must be reachable from its start with the range step"
+            )));
+        }
+        Ok(range)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{HardenedIndex, UnhardenedIndex};
+
+    #[test]
+    fn shard() {
+        let shard = IndexRange::<UnhardenedIndex>::shard;
+        let range = |start: u32, end: u32| {
+            IndexRange::new(
+                UnhardenedIndex::from_index(start).unwrap(),
+                UnhardenedIndex::from_index(end).unwrap(),
+            )
+        };
+        assert_eq!(shard(100, 0, 4), range(0, 24));
+        assert_eq!(shard(100, 2, 4), range(50, 74));
+        assert_eq!(shard(100, 3, 4), range(75, 99));
+        assert_eq!(shard(10, 2, 3), range(6, 9));
+        assert_eq!(shard(7, 6, 7), range(6, 6));
+
+        let total = HARDENED_INDEX_BOUNDARY;
+        assert_eq!(shard(total, 0, 1), Ok(IndexRange::full()));
+        assert_eq!(shard(total, 2, 3), range(2 * (total / 3), total - 1));
+        assert_eq!(
+            IndexRange::<HardenedIndex>::shard(total, 1, 2)
+                .unwrap()
+                .last_index(),
+            total - 1
+        );
+
+        assert_eq!(
+            shard(total + 1, 0, 2),
+            Err(RangeError::IndexOverflow(total + 1))
+        );
+        assert_eq!(
+            shard(u32::MAX, 3, 4),
+            Err(RangeError::IndexOverflow(u32::MAX))
+        );
+        assert_eq!(
+            shard(100, 4, 4),
+            Err(RangeError::ShardOutOfRange { shard: 4, count: 4 })
+        );
+        assert_eq!(
+            shard(3, 0, 4),
+            Err(RangeError::EmptyShard { total: 3, count: 4 })
+        );
+        assert_eq!(
+            shard(100, 0, 0),
+            Err(RangeError::EmptyShard {
+                total: 100,
+                count: 0
+            })
+        );
+    }
+
+    #[test]
+    fn single_range() {
+        let index = UnhardenedIndex::from(5u8);
+        let range = IndexRange::single(index);
+        assert_eq!(range.count(), 1);
+        assert_eq!(range.first_index(), 5);
+        assert_eq!(range.last_index(), 5);
+        assert_eq!(IndexRange::new(index, index), Ok(range));
+    }
+
+    #[test]
+    fn bounded_range() {
+        let start = UnhardenedIndex::from(2u8);
+        let end = UnhardenedIndex::from(10u8);
+        let range = IndexRange::new(start, end).unwrap();
+        assert_eq!(range.count(), 9);
+        assert!(range.contains(2) && range.contains(10) && !range.contains(11));
+        assert_eq!(
+            IndexRange::new(end, start),
+            Err(RangeError::ReversedBounds { start: 10, end: 2 })
+        );
+    }
+
+    #[test]
+    fn full_range() {
+        let range = IndexRange::<UnhardenedIndex>::full();
+        assert_eq!(range.first_index(), 0);
+        assert_eq!(range.last_index(), (1 << 31) - 1);
+        assert_eq!(range.count(), 1 << 31);
+    }
+
+    #[test]
+    fn stepped_range() {
+        let start = UnhardenedIndex::from(0u8);
+        let end = UnhardenedIndex::from(9u8);
+        let range = IndexRange::with_step(start, end, 2).unwrap();
+        assert_eq!(range.step(), 2);
+        assert_eq!(range.last_index(), 8);
+        assert_eq!(range.count(), 5);
+        assert!(range.contains(4) && !range.contains(5) && !range.contains(10));
+        assert_eq!(
+            range
+                .indexes()
+                .map(|index| index.first_index())
+                .collect::<Vec<_>>(),
+            vec![0, 2, 4, 6, 8]
+        );
+        assert_eq!(range.to_string(), "0-8:2");
+        assert_eq!(IndexRange::from_str("0-9:2"), Ok(range.clone()));
+        assert_eq!(
+            IndexRange::with_step(start, end, 1).unwrap().to_string(),
+            "0-9"
+        );
+        assert_eq!(
+            IndexRange::with_step(start, end, 0),
+            Err(RangeError::ZeroStep)
+        );
+        assert!(IndexRange::<UnhardenedIndex>::from_str("0-8:0").is_err());
+        assert!(IndexRange::<UnhardenedIndex>::from_str("5:2").is_err());
+
+        let data = range.strict_serialize().unwrap();
+        assert_eq!(IndexRange::strict_deserialize(data), Ok(range.clone()));
+        let plain = IndexRange::new(start, end).unwrap();
+        assert_eq!(plain.strict_serialize().unwrap().len(), 8);
+
+        let list = IndexRangeList::<HardenedIndex>::from_str("{0-8:2}h").unwrap();
+        assert_eq!(list.to_string(), "{0-8:2}h");
+        assert_eq!(list.count(), 5);
+    }
+
+    #[test]
+    fn partition() {
+        let list = IndexRangeList::<UnhardenedIndex>::from_str("{0-4,10-19:3,30}").unwrap();
+        let indexes = |values: &[u32]| {
+            values
+                .iter()
+                .map(|value| UnhardenedIndex::from_index(*value).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            list.partition(&indexes(&[0, 3, 5, 10, 11, 13, 19, 30, 31])),
+            (indexes(&[0, 3, 10, 13, 19, 30]), indexes(&[5, 11, 31]))
+        );
+        assert_eq!(
+            list.partition(&indexes(&[30, 12, 4, 16, 100, 2, 4])),
+            (indexes(&[30, 4, 16, 2, 4]), indexes(&[12, 100]))
+        );
+        assert_eq!(list.partition(&[]), (vec![], vec![]));
     }
 }
@@ -0,0 +1,210 @@
+// Descriptor wallet library extending bitcoin & miniscript functionality
+// by LNP/BP Association (https://lnp-bp.org)
+// Written in 2020-2022 by
+//     Dr. Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the Apache-2.0 License
+// along with this software.
+// If not, see <https://opensource.org/licenses/Apache-2.0>.
+
+//! Account descriptors with redacted extended public keys.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use bitcoin::util::bip32::{ChildNumber, DerivationPath, Fingerprint, KeySource};
+
+use crate::account::{check_str_depth, derive_pattern_len, terminal_derivation_path, ParseError};
+use crate::{
+    AccountStep, DerivationAccount, DerivationSubpath, DerivePatternError, TerminalStep,
+    UnhardenedIndex, XpubRef, MAX_DERIVATION_DEPTH,
+};
+
+/// Structure of a [`DerivationAccount`] with the extended public keys
+/// replaced by their fingerprints, allowing to share the derivation paths used
+/// by a wallet without revealing its keys.
+///
+/// The account is represented as a string in form of
+/// `[master_fp/hardened_path]=[account_fp]/unhardened_path`, where the origin
+/// in square brackets is omitted if the master key and the account path are
+/// not known. Since the keys are redacted, only the key sources of the derived
+/// keys may be computed and not the keys themselves.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct RedactedAccount {
+    /// Fingerprint of the master extended key, if known
+    pub master_fingerprint: Option<Fingerprint>,
+
+    /// Derivation path for the account, may contain multiple hardened steps
+    pub account_path: DerivationSubpath<AccountStep>,
+
+    /// Fingerprint of the account extended public key
+    pub account_fingerprint: Fingerprint,
+
+    /// Terminal derivation path, consisting exclusively from unhardened
+    /// indexes
+    pub terminal_path: DerivationSubpath<TerminalStep>,
+}
+
+impl From<&DerivationAccount> for RedactedAccount {
+    fn from(account: &DerivationAccount) -> Self {
+        RedactedAccount {
+            master_fingerprint: account.master_fingerprint(),
+            account_path: account.account_path.clone(),
+            account_fingerprint: account.account_fingerprint(),
+            terminal_path: account.terminal_path.clone(),
+        }
+    }
+}
+
+impl DerivationAccount {
+    /// Returns string representation of the account with the master and
+    /// account extended public keys replaced by their fingerprints (see
+    /// [`RedactedAccount`]).
+    pub fn redacted(&self) -> String { RedactedAccount::from(self).to_string() }
+}
+
+impl RedactedAccount {
+    /// Returns number of indexes in derive pattern required by the terminal
+    /// path (see [`DerivationAccount::derive_pattern_len`]).
+    #[inline]
+    pub fn derive_pattern_len(&self) -> usize { derive_pattern_len(&self.terminal_path) }
+
+    /// Constructs [`KeySource`] for a key at some terminal derivation path,
+    /// matching [`DerivationAccount::key_source`] of the original account. The
+    /// key itself can't be derived, since the account key is not known.
+    pub fn key_source(
+        &self,
+        pat: impl AsRef<[UnhardenedIndex]>,
+    ) -> Result<KeySource, DerivePatternError> {
+        let terminal = terminal_derivation_path(&self.terminal_path, pat.as_ref())?;
+        let mut path = Vec::with_capacity(self.account_path.len() + self.terminal_path.len());
+        if self.master_fingerprint.is_some() {
+            path.extend(self.account_path.iter().map(ChildNumber::from));
+        }
+        path.extend(&terminal);
+        Ok((
+            self.master_fingerprint.unwrap_or(self.account_fingerprint),
+            DerivationPath::from(path),
+        ))
+    }
+}
+
+impl Display for RedactedAccount {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.master_fingerprint.is_some() || !self.account_path.is_empty() {
+            f.write_str("[")?;
+            if let Some(fp) = self.master_fingerprint {
+                write!(f, "{}", fp)?;
+            }
+            for step in self.account_path.iter() {
+                write!(f, "/{}", step)?;
+            }
+            f.write_str("]")?;
+        }
+        write!(f, "={}", XpubRef::Fingerprint(self.account_fingerprint))?;
+        for step in self.terminal_path.iter() {
+            write!(f, "/{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for RedactedAccount {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        check_str_depth(s, MAX_DERIVATION_DEPTH)?;
+        let invalid = || ParseError::InvalidDerivationPathFormat(s.to_owned());
+        let (origin, rest) = s
+            .split_once("=[")
+            .ok_or_else(|| ParseError::AccountXpubAbsent(s.to_owned()))?;
+        let (account, terminal) = rest.split_once(']').ok_or_else(invalid)?;
+
+        let mut master_fingerprint = None;
+        let mut account_path = DerivationSubpath::new();
+        if !origin.is_empty() {
+            let origin = origin
+                .strip_prefix('[')
+                .and_then(|origin| origin.strip_suffix(']'))
+                .ok_or_else(invalid)?;
+            let mut steps = origin.split('/');
+            let master = steps
+                .next()
+                .expect("split always returns at least one item");
+            if !master.is_empty() {
+                master_fingerprint = Some(Fingerprint::from_str(master).map_err(|_| invalid())?);
+            }
+            for step in steps {
+                account_path.push(AccountStep::from_str(step)?);
+            }
+        }
+
+        let mut terminal_path = DerivationSubpath::new();
+        if !terminal.is_empty() {
+            for step in terminal.strip_prefix('/').ok_or_else(invalid)?.split('/') {
+                terminal_path.push(TerminalStep::from_str(step)?);
+            }
+        }
+
+        Ok(RedactedAccount {
+            master_fingerprint,
+            account_path,
+            account_fingerprint: Fingerprint::from_str(account).map_err(|_| invalid())?,
+            terminal_path,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn redacted_roundtrip() {
+        let account = DerivationAccount::from_str(
+            "[73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/<0;1>/*",
+        )
+        .unwrap();
+        let account_fp = account.account_fingerprint();
+        let redacted = account.redacted();
+        assert_eq!(
+            redacted,
+            format!("[73c5da0a/84h/0h/0h]=[{}]/{{0,1}}/*", account_fp)
+        );
+        assert!(DerivationAccount::from_str(&redacted).is_err());
+
+        let parsed = RedactedAccount::from_str(&redacted).unwrap();
+        assert_eq!(parsed, RedactedAccount::from(&account));
+        assert_eq!(
+            parsed.master_fingerprint,
+            Some(Fingerprint::from_str("73c5da0a").unwrap())
+        );
+        assert_eq!(parsed.account_fingerprint, account_fp);
+        assert_eq!(parsed.derive_pattern_len(), 2);
+        let pat = [UnhardenedIndex::from(1u8), UnhardenedIndex::from(5u8)];
+        assert_eq!(parsed.key_source(pat), account.key_source(pat));
+        assert_eq!(
+            parsed.key_source([UnhardenedIndex::from(1u8)]),
+            Err(DerivePatternError::Mismatch)
+        );
+
+        let bare = DerivationAccount::from_str(
+            "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*",
+        )
+        .unwrap();
+        let redacted = bare.redacted();
+        assert_eq!(redacted, format!("=[{}]/0/*", account_fp));
+        let parsed = RedactedAccount::from_str(&redacted).unwrap();
+        assert_eq!(parsed.master_fingerprint, None);
+        let pat = [UnhardenedIndex::from(3u8)];
+        assert_eq!(parsed.key_source(pat), bare.key_source(pat));
+
+        assert!(RedactedAccount::from_str("[73c5da0a/84h]/0/*").is_err());
+        assert!(RedactedAccount::from_str("=[73c5da0a]0/*").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+// Descriptor wallet library extending bitcoin & miniscript functionality
+// by LNP/BP Association (https://lnp-bp.org)
+// Written in 2020-2022 by
+//     Dr. Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the Apache-2.0 License
+// along with this software.
+// If not, see <https://opensource.org/licenses/Apache-2.0>.
+
+//! Gap limit scanning for wallet discovery.
+
+use bitcoin::secp256k1::{Secp256k1, Verification};
+use bitcoin::Script;
+
+use crate::{
+    Bip43, DerivationAccount, DeriveError, SegmentIndexes, UnhardenedIndex, HARDENED_INDEX_BOUNDARY,
+};
+
+/// Stateful scanner deriving `scriptPubkey`s of a single account branch for
+/// wallet discovery (like the one described in BIP-44), which stops once
+/// `gap` consecutive indexes after the last used one are scanned.
+///
+/// The account must have a single-index derive pattern (like `/0/*`; use
+/// [`DerivationAccount::split_multipath`] for the accounts with multipath
+/// terminal steps). The scanner does not query the blockchain itself:
+/// indexes found to be used must be reported back with
+/// [`GapScanner::mark_used`], which extends the scan window.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct GapScanner {
+    account: DerivationAccount,
+    scheme: Bip43,
+    gap: u32,
+    next: u32,
+    last_used: Option<u32>,
+}
+
+impl GapScanner {
+    /// Constructs scanner for the `account` branch with script types defined
+    /// by the derivation `scheme` (see [`DerivationAccount::script_pubkey`]).
+    pub fn new(account: DerivationAccount, scheme: Bip43, gap: u32) -> GapScanner {
+        GapScanner {
+            account,
+            scheme,
+            gap,
+            next: 0,
+            last_used: None,
+        }
+    }
+
+    /// Returns the gap limit of the scanner.
+    #[inline]
+    pub fn gap(&self) -> u32 { self.gap }
+
+    /// Returns the highest index marked as used, if any.
+    #[inline]
+    pub fn last_used(&self) -> Option<UnhardenedIndex> {
+        self.last_used.map(|index| {
+            UnhardenedIndex::from_index(index).expect("used indexes are always unhardened")
+        })
+    }
+
+    /// Returns the index which will be derived next.
+    #[inline]
+    pub fn next_index(&self) -> u32 { self.next }
+
+    /// Returns the end of the current scan window (exclusive), which is
+    /// `gap` indexes after the last used index (or after the start of the
+    /// branch if no indexes were used).
+    pub fn scan_limit(&self) -> u32 {
+        self.last_used
+            .map_or(0, |index| index + 1)
+            .saturating_add(self.gap)
+            .min(HARDENED_INDEX_BOUNDARY)
+    }
+
+    /// Detects whether all of the indexes within the scan window are already
+    /// derived.
+    #[inline]
+    pub fn is_done(&self) -> bool { self.next >= self.scan_limit() }
+
+    /// Marks `index` as used, extending the scan window to `gap` indexes
+    /// after it, if it is past the last used index.
+    pub fn mark_used(&mut self, index: UnhardenedIndex) {
+        let index = index.first_index();
+        self.last_used = Some(self.last_used.map_or(index, |last| last.max(index)));
+    }
+
+    /// Derives `scriptPubkey`s for at most `n` next indexes within the scan
+    /// window. Returns an empty list once the scan is done (see
+    /// [`GapScanner::is_done`]); marking more indexes as used may resume it.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the account derive pattern is not a single index or if the
+    /// derivation scheme does not define a single-key script type.
+    pub fn next_batch<C: Verification>(
+        &mut self,
+        ctx: &Secp256k1<C>,
+        n: u32,
+    ) -> Result<Vec<(UnhardenedIndex, Script)>, DeriveError> {
+        let end = self.next.saturating_add(n).min(self.scan_limit());
+        let mut batch = Vec::with_capacity(end.saturating_sub(self.next) as usize);
+        for index in self.next..end {
+            let index =
+                UnhardenedIndex::from_index(index).expect("scan limit is always unhardened");
+            batch.push((
+                index,
+                self.account.script_pubkey(ctx, self.scheme, [index])?,
+            ));
+        }
+        self.next = self.next.max(end);
+        Ok(batch)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn gap_extension() {
+        let secp = Secp256k1::verification_only();
+        let account = DerivationAccount::from_str_bitcoin_core(
+            "[73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*",
+        )
+        .unwrap();
+        let mut scanner = GapScanner::new(account.clone(), Bip43::Bip84, 20);
+        assert_eq!(scanner.scan_limit(), 20);
+
+        let batch = scanner.next_batch(&secp, 15).unwrap();
+        assert_eq!(batch.len(), 15);
+        assert_eq!(
+            batch[3],
+            (
+                UnhardenedIndex::from(3u8),
+                account
+                    .script_pubkey(&secp, Bip43::Bip84, [UnhardenedIndex::from(3u8)])
+                    .unwrap()
+            )
+        );
+        assert_eq!(scanner.next_batch(&secp, 15).unwrap().len(), 5);
+        assert!(scanner.is_done());
+        assert!(scanner.next_batch(&secp, 15).unwrap().is_empty());
+
+        scanner.mark_used(UnhardenedIndex::from(25u8));
+        assert_eq!(scanner.last_used(), Some(UnhardenedIndex::from(25u8)));
+        assert_eq!(scanner.scan_limit(), 25 + 1 + 20);
+        let batch = scanner.next_batch(&secp, 100).unwrap();
+        assert_eq!(batch.first().unwrap().0, UnhardenedIndex::from(20u8));
+        assert_eq!(batch.last().unwrap().0, UnhardenedIndex::from(25u8 + 20));
+        assert!(scanner.is_done());
+
+        // Marking older indexes as used does not shrink the scan window
+        scanner.mark_used(UnhardenedIndex::from(3u8));
+        assert_eq!(scanner.scan_limit(), 46);
+    }
+}
@@ -0,0 +1,143 @@
+// Descriptor wallet library extending bitcoin & miniscript functionality
+// by LNP/BP Association (https://lnp-bp.org)
+// Written in 2020-2022 by
+//     Dr. Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the Apache-2.0 License
+// along with this software.
+// If not, see <https://opensource.org/licenses/Apache-2.0>.
+
+//! Bootstrapping watch-only accounts out of BIP-39 mnemonic seeds.
+
+use bip39::Mnemonic;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::util::bip32::{self, ExtendedPrivKey, ExtendedPubKey};
+use bitcoin::Network;
+
+use crate::wipe::{wipe_bytes, wipe_xpriv};
+use crate::{
+    account_xpub, AccountStep, Bip43, DerivationAccount, DerivationStandard, DeriveError,
+    HardenedIndex, SegmentIndexes, TerminalStep, UnhardenedIndex, XpubRef,
+};
+
+/// Errors deriving account from a mnemonic seed
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum SeedError {
+    /// mnemonic must not be empty
+    EmptyMnemonic,
+
+    /// invalid BIP-39 mnemonic: {0}
+    #[from]
+    Mnemonic(bip39::Error),
+
+    /// BIP-32 related errors.
+    #[display(inner)]
+    #[from]
+    Bip32(bip32::Error),
+
+    /// unable to derive account extended public key: {0}
+    Derive(DeriveError),
+}
+
+/// Derives account extended public key from BIP-39 mnemonic and passphrase
+/// using the account derivation path defined by the `scheme`, and returns
+/// watch-only [`DerivationAccount`] with `/{0,1}/*` terminal path,
+/// covering both receive and change addresses.
+///
+/// The mnemonic checksum is validated. The function returns only public
+/// data: the seed bytes and the master extended private key are wiped from
+/// memory as soon as they are no longer needed, on both success and error
+/// paths. Wiping is best effort: copies made inside the `bip39`, `bitcoin`
+/// and `secp256k1` libraries are out of reach.
+pub fn account_xpub_from_mnemonic(
+    mnemonic: &str,
+    passphrase: &str,
+    scheme: Bip43,
+    account: HardenedIndex,
+    network: Network,
+) -> Result<DerivationAccount, SeedError> {
+    if mnemonic.trim().is_empty() {
+        return Err(SeedError::EmptyMnemonic);
+    }
+    let mnemonic = Mnemonic::parse(mnemonic)?;
+
+    let secp = Secp256k1::new();
+    let mut seed = mnemonic.to_seed(passphrase);
+    let master_xpriv = ExtendedPrivKey::new_master(network, &seed);
+    wipe_bytes(&mut seed);
+    let mut master_xpriv = master_xpriv?;
+
+    let account_xpub = account_xpub(&secp, &master_xpriv, scheme, account, network);
+    let master_xpub = ExtendedPubKey::from_priv(&secp, &master_xpriv);
+    wipe_xpriv(&mut master_xpriv);
+    let account_xpub = account_xpub.map_err(|err| match err {
+        DeriveError::Bip32(err) => SeedError::Bip32(err),
+        err => SeedError::Derive(err),
+    })?;
+    let derivation = scheme.to_account_derivation(account.into(), network.into());
+
+    Ok(DerivationAccount {
+        master: XpubRef::Fingerprint(master_xpub.fingerprint()),
+        account_path: derivation
+            .into_iter()
+            .copied()
+            .map(AccountStep::try_from)
+            .collect::<Result<_, _>>()?,
+        account_xpub,
+        revocation_seal: None,
+        terminal_path: vec![
+            TerminalStep::range(UnhardenedIndex::zero(), UnhardenedIndex::one()),
+            TerminalStep::Wildcard,
+        ]
+        .into_iter()
+        .collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon \
+                            abandon abandon abandon about";
+
+    #[test]
+    fn bip84_account_from_mnemonic() {
+        let account = account_xpub_from_mnemonic(
+            MNEMONIC,
+            "",
+            Bip43::Bip84,
+            HardenedIndex::zero(),
+            Network::Bitcoin,
+        )
+        .unwrap();
+        assert_eq!(
+            format!("{:#}", account),
+            "[73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/{0,1}/*"
+        );
+    }
+
+    #[test]
+    fn invalid_mnemonic() {
+        let derive = |mnemonic: &str| {
+            account_xpub_from_mnemonic(
+                mnemonic,
+                "",
+                Bip43::Bip84,
+                HardenedIndex::zero(),
+                Network::Bitcoin,
+            )
+        };
+        assert!(matches!(derive(" "), Err(SeedError::EmptyMnemonic)));
+        assert!(matches!(
+            derive(&MNEMONIC.replace("about", "abandon")),
+            Err(SeedError::Mnemonic(bip39::Error::InvalidChecksum))
+        ));
+    }
+}
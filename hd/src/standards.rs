@@ -17,13 +17,15 @@
 use core::convert::TryInto;
 use core::str::FromStr;
 
-use bitcoin::util::bip32::{ChildNumber, DerivationPath};
+use bitcoin::secp256k1::{Secp256k1, Signing};
+use bitcoin::util::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey, ExtendedPubKey};
 use bitcoin::Network;
 #[cfg(feature = "miniscript")]
 use miniscript::descriptor::DescriptorType;
 use slip132::KeyApplication;
 
-use crate::{HardenedIndex, HardenedIndexExpected, SegmentIndexes, UnhardenedIndex};
+use crate::wipe::wipe_xpriv;
+use crate::{DeriveError, HardenedIndex, HardenedIndexExpected, SegmentIndexes, UnhardenedIndex};
 
 /// Errors in parsing derivation scheme string representation
 #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Error, Display)]
@@ -49,6 +51,10 @@ pub enum ParseError {
     /// derivation path can't be recognized as one of BIP-43-based standards
     UnrecognizedBipScheme,
 
+    /// unknown derivation scheme `{0}`; it must be either `bipNN` identifier
+    /// or a script type name like `p2wpkh`, `p2tr` etc
+    UnknownScheme(String),
+
     /// BIP-43 scheme must have form of `bip43/<purpose>h`
     InvalidBip43Scheme,
 
@@ -77,7 +83,9 @@ pub enum DerivationBlockchain {
     #[display("testnet")]
     Testnet,
 
-    /// Custom blockchain (non-testnet)
+    /// Custom blockchain (non-testnet) with the provided coin type, like `2h`
+    /// for Litecoin as registered in SLIP-44. Can be used with standard
+    /// derivation schemes to construct paths like `m/84h/2h/0h`.
     #[display(inner)]
     #[from]
     Custom(HardenedIndex),
@@ -109,6 +117,17 @@ impl DerivationBlockchain {
     }
 }
 
+impl From<Network> for DerivationBlockchain {
+    /// Maps bitcoin mainnet to [`DerivationBlockchain::Bitcoin`] and all other
+    /// networks to [`DerivationBlockchain::Testnet`].
+    fn from(network: Network) -> Self {
+        match network {
+            Network::Bitcoin => DerivationBlockchain::Bitcoin,
+            _ => DerivationBlockchain::Testnet,
+        }
+    }
+}
+
 impl FromStr for DerivationBlockchain {
     type Err = ParseError;
 
@@ -206,6 +225,17 @@ impl FromStr for Bip43 {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let s = s.to_lowercase();
+        match s.as_str() {
+            "p2pkh" | "legacy" => return Ok(Bip43::Bip44),
+            "p2wpkh" | "segwit" | "native-segwit" => return Ok(Bip43::Bip84),
+            "p2wpkh-p2sh" | "p2sh-p2wpkh" | "nested-segwit" => return Ok(Bip43::Bip49),
+            "p2tr" | "taproot" => return Ok(Bip43::Bip86),
+            "p2sh" | "multisig-legacy" => return Ok(Bip43::Bip45),
+            "p2wsh-p2sh" | "p2sh-p2wsh" | "multisig-nested" => return Ok(Bip43::Bip48Nested),
+            "p2wsh" | "multisig-segwit" => return Ok(Bip43::Bip48Native),
+            "multisig-descriptor" => return Ok(Bip43::Bip87),
+            _ => {}
+        }
         let bip = s.strip_prefix("bip").or_else(|| s.strip_prefix("m/"));
         Ok(match bip {
             Some("44") => Bip43::Bip44,
@@ -224,7 +254,7 @@ impl FromStr for Bip43 {
             Some("48-nested") => Bip43::Bip48Nested,
             Some("48-native") => Bip43::Bip48Native,
             Some("87") => Bip43::Bip87,
-            Some(bip43) if s.starts_with("bip43/") => match s.strip_prefix("bip43/") {
+            Some(_) if s.starts_with("bip43/") => match s.strip_prefix("bip43/") {
                 Some(purpose) => {
                     let purpose = HardenedIndex::from_str(purpose)
                         .map_err(|_| ParseError::InvalidPurposeIndex(purpose.to_owned()))?;
@@ -232,11 +262,19 @@ impl FromStr for Bip43 {
                 }
                 None => return Err(ParseError::InvalidBip43Scheme),
             },
-            Some(_) | None => return Err(ParseError::UnrecognizedBipScheme),
+            Some(_) => return Err(ParseError::UnrecognizedBipScheme),
+            None => return Err(ParseError::UnknownScheme(s)),
         })
     }
 }
 
+impl TryFrom<&str> for Bip43 {
+    type Error = ParseError;
+
+    #[inline]
+    fn try_from(s: &str) -> Result<Self, Self::Error> { Bip43::from_str(s) }
+}
+
 impl Bip43 {
     /// Constructs derivation standard corresponding to a single-sig P2PKH.
     pub fn singlesig_pkh() -> Bip43 { Bip43::Bip44 }
@@ -259,6 +297,39 @@ impl Bip43 {
     pub fn multisig_descriptor() -> Bip43 { Bip43::Bip87 }
 }
 
+/// Derives account-level extended public key from the master extended private
+/// key using hardened `purpose'/coin_type'/account'` derivation path defined
+/// by the `scheme` (with additional `script_type'` step for BIP-48).
+///
+/// The path is derived step by step, and each of the derived private keys is
+/// wiped from memory once the next one (or the account public key) is
+/// computed. Copies of the secret data made internally by `bitcoin` and
+/// `secp256k1` libraries are out of reach for the wiping.
+///
+/// Errors with [`DeriveError::InconsistentKeyNetwork`] if the master key
+/// network does not match the provided `network`.
+pub fn account_xpub<C: Signing>(
+    secp: &Secp256k1<C>,
+    master: &ExtendedPrivKey,
+    scheme: Bip43,
+    account: HardenedIndex,
+    network: Network,
+) -> Result<ExtendedPubKey, DeriveError> {
+    if (master.network == Network::Bitcoin) != (network == Network::Bitcoin) {
+        return Err(DeriveError::InconsistentKeyNetwork);
+    }
+    let derivation = scheme.to_account_derivation(account.into(), network.into());
+    let mut xpriv = *master;
+    for child_number in &derivation {
+        let derived = xpriv.ckd_priv(secp, *child_number);
+        wipe_xpriv(&mut xpriv);
+        xpriv = derived?;
+    }
+    let account_xpub = ExtendedPubKey::from_priv(secp, &xpriv);
+    wipe_xpriv(&mut xpriv);
+    Ok(account_xpub)
+}
+
 /// Methods for derivation standard enumeration types.
 pub trait DerivationStandard: Eq + Clone {
     /// Deduces derivation standard used by the provided derivation path, if
@@ -318,7 +389,10 @@ pub trait DerivationStandard: Eq + Clone {
         &self,
         path: &DerivationPath,
     ) -> Option<Result<HardenedIndex, HardenedIndexExpected>> {
+        // Key at depth `n` is derived with the `n`-th step of the path, while
+        // depth zero denotes the master key, which has no derivation step
         self.coin_type_depth()
+            .and_then(|depth| depth.checked_sub(1))
             .and_then(|depth| path.as_ref().get(depth as usize))
             .copied()
             .map(HardenedIndex::try_from)
@@ -339,6 +413,7 @@ pub trait DerivationStandard: Eq + Clone {
         path: &DerivationPath,
     ) -> Option<Result<HardenedIndex, HardenedIndexExpected>> {
         self.account_depth()
+            .and_then(|depth| depth.checked_sub(1))
             .and_then(|depth| path.as_ref().get(depth as usize))
             .copied()
             .map(HardenedIndex::try_from)
@@ -622,3 +697,133 @@ pub enum DescriptorType {
     /// Tr Descriptor
     Tr,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bip84_account_xpub() {
+        let secp = Secp256k1::new();
+        // Master key for `abandon abandon ... about` mnemonic from BIP-84
+        let master = ExtendedPrivKey::from_str(
+            "xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu",
+        )
+        .unwrap();
+        let account = HardenedIndex::zero();
+        let xpub = account_xpub(&secp, &master, Bip43::Bip84, account, Network::Bitcoin).unwrap();
+        assert_eq!(
+            xpub.to_string(),
+            "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V"
+        );
+        assert_eq!(xpub.depth, 3);
+        assert_eq!(xpub.child_number, ChildNumber::Hardened { index: 0 });
+        assert!(matches!(
+            account_xpub(&secp, &master, Bip43::Bip84, account, Network::Testnet),
+            Err(DeriveError::InconsistentKeyNetwork)
+        ));
+    }
+
+    #[test]
+    fn bip43_from_str() {
+        for (scheme, aliases) in [
+            (
+                Bip43::Bip44,
+                &["bip44", "BIP44", "m/44", "p2pkh", "legacy"][..],
+            ),
+            (Bip43::Bip84, &[
+                "bip84",
+                "Bip84",
+                "m/84",
+                "p2wpkh",
+                "P2WPKH",
+                "segwit",
+                "native-segwit",
+            ]),
+            (Bip43::Bip49, &[
+                "bip49",
+                "m/49",
+                "p2wpkh-p2sh",
+                "p2sh-p2wpkh",
+                "nested-segwit",
+            ]),
+            (Bip43::Bip86, &["bip86", "m/86", "p2tr", "Taproot"]),
+            (Bip43::Bip45, &["bip45", "m/45", "p2sh", "multisig-legacy"]),
+            (Bip43::Bip48Nested, &[
+                "bip48-nested",
+                "p2wsh-p2sh",
+                "p2sh-p2wsh",
+                "multisig-nested",
+            ]),
+            (Bip43::Bip48Native, &[
+                "bip48-native",
+                "p2wsh",
+                "multisig-segwit",
+            ]),
+            (Bip43::Bip87, &["bip87", "m/87", "multisig-descriptor"]),
+        ] {
+            for alias in aliases {
+                assert_eq!(Bip43::from_str(alias), Ok(scheme));
+                assert_eq!(Bip43::try_from(*alias), Ok(scheme));
+            }
+            assert_eq!(Bip43::from_str(&scheme.to_string()), Ok(scheme));
+        }
+
+        assert_eq!(Bip43::Bip84.to_string(), "bip84");
+        assert_eq!(
+            Bip43::from_str("bip43/7h"),
+            Ok(Bip43::Bip43 {
+                purpose: HardenedIndex::from_index(7u32).unwrap()
+            })
+        );
+        assert_eq!(
+            Bip43::from_str("P2XYZ"),
+            Err(ParseError::UnknownScheme(s!("p2xyz")))
+        );
+        assert_eq!(
+            Bip43::from_str("bip99"),
+            Err(ParseError::UnrecognizedBipScheme)
+        );
+        assert_eq!(
+            Bip43::from_str("bip48-foo"),
+            Err(ParseError::UnrecognizedBipScheme)
+        );
+        assert_eq!(
+            Bip43::from_str("m/99"),
+            Err(ParseError::UnrecognizedBipScheme)
+        );
+    }
+
+    #[test]
+    fn custom_coin_type() {
+        let litecoin = DerivationBlockchain::from_str("2h").unwrap();
+        assert_eq!(
+            litecoin,
+            DerivationBlockchain::Custom(HardenedIndex::from(2u8))
+        );
+        let account = ChildNumber::from_hardened_idx(0).unwrap();
+        assert_eq!(
+            Bip43::Bip84.to_account_derivation(account, litecoin),
+            DerivationPath::from_str("m/84h/2h/0h").unwrap()
+        );
+        assert_eq!(
+            Bip43::Bip48Native.to_account_derivation(account, litecoin),
+            DerivationPath::from_str("m/48h/2h/0h/2h").unwrap()
+        );
+        assert_eq!(
+            Bip43::Bip84.account_template_string(litecoin),
+            "m/84h/2h/*h"
+        );
+        assert_eq!(
+            Bip43::Bip84.to_account_derivation(account, DerivationBlockchain::Testnet),
+            DerivationPath::from_str("m/84h/1h/0h").unwrap()
+        );
+        let path = DerivationPath::from_str("m/84h/2h/0h").unwrap();
+        assert_eq!(Bip43::deduce(&path), Some(Bip43::Bip84));
+        assert_eq!(
+            Bip43::Bip84.extract_coin_type(&path),
+            Some(Ok(HardenedIndex::from(2u8)))
+        );
+        assert_eq!(Bip43::Bip84.network(&path), None);
+    }
+}
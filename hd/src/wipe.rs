@@ -0,0 +1,44 @@
+// Descriptor wallet library extending bitcoin & miniscript functionality
+// by LNP/BP Association (https://lnp-bp.org)
+// Written in 2020-2022 by
+//     Dr. Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the Apache-2.0 License
+// along with this software.
+// If not, see <https://opensource.org/licenses/Apache-2.0>.
+
+//! Wiping of secret key material from memory.
+
+use core::ptr;
+use core::sync::atomic::{self, Ordering};
+
+use bitcoin::secp256k1;
+use bitcoin::util::bip32::{ChainCode, ExtendedPrivKey};
+
+/// Overwrites the data with zeros. Unlike plain assignments, volatile writes
+/// are never removed by the compiler as dead stores.
+#[cfg(feature = "bip39")]
+pub(crate) fn wipe_bytes(data: &mut [u8]) {
+    for byte in data {
+        // SAFETY: the pointer is obtained from a valid mutable reference
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
+    atomic::compiler_fence(Ordering::SeqCst);
+}
+
+/// Overwrites private key and chain code of the extended private key (see
+/// `wipe_bytes`). The private key is replaced with the valid key `1`, since
+/// a zero secret key can't be represented.
+pub(crate) fn wipe_xpriv(xpriv: &mut ExtendedPrivKey) {
+    // SAFETY: the pointers are obtained from valid mutable references
+    unsafe {
+        ptr::write_volatile(&mut xpriv.private_key, secp256k1::ONE_KEY);
+        ptr::write_volatile(&mut xpriv.chain_code, ChainCode::from(&[0u8; 32][..]));
+    }
+    atomic::compiler_fence(Ordering::SeqCst);
+}
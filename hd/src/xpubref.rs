@@ -109,3 +109,46 @@ impl FromStr for XpubRef {
             .or_else(|_| ExtendedPubKey::from_str(s).map(XpubRef::from))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn xpubref_display_fromstr_roundtrip() {
+        let xpub = ExtendedPubKey::from_str(
+            "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V",
+        )
+        .unwrap();
+        let fingerprint = Fingerprint::from_str("d34db33f").unwrap();
+
+        for (xpubref, s) in [
+            (XpubRef::Unknown, s!("")),
+            (XpubRef::Fingerprint(fingerprint), s!("[d34db33f]")),
+            (
+                XpubRef::XpubIdentifier(xpub.identifier()),
+                format!("[{}]", xpub.identifier()),
+            ),
+            (XpubRef::Xpub(xpub), format!("[{}]", xpub)),
+        ] {
+            assert_eq!(xpubref.to_string(), s);
+            assert_eq!(XpubRef::from_str(&s).unwrap(), xpubref);
+            assert_eq!(
+                XpubRef::from_str(&format!("{:#}", xpubref)).unwrap(),
+                xpubref
+            );
+        }
+
+        assert_eq!(XpubRef::from(xpub), XpubRef::Xpub(xpub));
+        assert_eq!(XpubRef::from(xpub).fingerprint(), Some(xpub.fingerprint()));
+
+        assert_eq!(
+            XpubRef::from_str("d34db33f").unwrap(),
+            XpubRef::Fingerprint(fingerprint)
+        );
+        assert_eq!(
+            XpubRef::from_str(&format!("={}", XpubRef::Fingerprint(fingerprint))).unwrap(),
+            XpubRef::Fingerprint(fingerprint)
+        );
+    }
+}
@@ -0,0 +1,29 @@
+// Descriptor wallet library extending bitcoin & miniscript functionality
+// by LNP/BP Association (https://lnp-bp.org)
+// Written in 2020-2022 by
+//     Dr. Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the Apache-2.0 License
+// along with this software.
+// If not, see <https://opensource.org/licenses/Apache-2.0>.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use psbt::serialize::Serialize;
+use psbt::Psbt;
+
+fuzz_target!(|data: &[u8]| {
+    // Decoding must never panic; successfully decoded PSBTs must survive
+    // serialization and decoding once again
+    if let Ok(psbt) = Psbt::decode(data) {
+        let decoded = Psbt::decode(&psbt.serialize())
+            .expect("serialized PSBT must be decodable once again");
+        assert_eq!(decoded, psbt);
+    }
+});
@@ -295,16 +295,6 @@ impl Psbt {
 
         if change > 0 {
             let change_derivation = [UnhardenedIndex::one(), change_index.into()];
-            let mut bip32_derivation = bmap! {};
-            let bip32_derivation_fn = |key: miniscript::ForEach<DerivationAccount>| {
-                let (pubkey, key_source) = key
-                    .as_key()
-                    .bip32_derivation(SECP256K1, &change_derivation)
-                    .expect("already tested descriptor derivation mismatch");
-                bip32_derivation.insert(pubkey, key_source);
-                true
-            };
-
             let mut psbt_change_output = psbt::Output {
                 index: psbt_outputs.len(),
                 amount: change,
@@ -322,7 +312,6 @@ impl Psbt {
                 };
 
                 psbt_change_output.script = DescriptorTrait::script_pubkey(&change_descriptor);
-                descriptor.for_each_key(bip32_derivation_fn);
 
                 let internal_key: XOnlyPublicKey =
                     change_descriptor.internal_key().to_x_only_pubkey();
@@ -352,7 +341,6 @@ impl Psbt {
                 psbt_change_output.script = DescriptorTrait::script_pubkey(&change_descriptor);
 
                 let dtype = descriptors::CompositeDescrType::from(&change_descriptor);
-                descriptor.for_each_key(bip32_derivation_fn);
 
                 let lock_script = change_descriptor.explicit_script()?;
                 if dtype.has_redeem_script() {
@@ -363,7 +351,14 @@ impl Psbt {
                 }
             }
 
-            psbt_change_output.bip32_derivation = bip32_derivation;
+            descriptor.for_each_key(|key| {
+                let (pubkey, key_source) = key
+                    .as_key()
+                    .bip32_derivation(SECP256K1, change_derivation)
+                    .expect("already tested descriptor derivation mismatch");
+                psbt_change_output.set_derivation(pubkey, key_source);
+                true
+            });
             psbt_outputs.push(psbt_change_output);
         }
 
@@ -379,3 +374,82 @@ impl Psbt {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bitcoin::{EcdsaSighashType, OutPoint, Transaction, TxOut};
+    use bitcoin_hd::DerivationSubpath;
+    use descriptors::locks::SeqNo;
+
+    use super::*;
+
+    #[test]
+    fn change_output_derivation() {
+        let descriptor = Descriptor::<DerivationAccount>::from_str(
+            "wpkh([73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/<0;1>/*)",
+        )
+        .unwrap();
+        let terminal: DerivationSubpath<UnhardenedIndex> =
+            [UnhardenedIndex::zero(), UnhardenedIndex::zero()]
+                .into_iter()
+                .collect();
+        let script_pubkey = DescriptorTrait::script_pubkey(
+            &DeriveDescriptor::<bitcoin::PublicKey>::derive_descriptor(
+                &descriptor,
+                SECP256K1,
+                &terminal,
+            )
+            .unwrap(),
+        );
+        let prev_tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![TxOut {
+                value: 100_000,
+                script_pubkey,
+            }],
+        };
+        let txid = prev_tx.txid();
+        let resolver = bmap! { txid => prev_tx };
+        let input = InputDescriptor {
+            outpoint: OutPoint::new(txid, 0),
+            terminal,
+            seq_no: SeqNo::unencumbered(true),
+            tweak: None,
+            sighash_type: EcdsaSighashType::All,
+        };
+        let outputs = [(PubkeyScript::from(Script::new()), 50_000)];
+
+        let change_index = UnhardenedIndex::from_index(7u32).unwrap();
+        let psbt = Psbt::construct(
+            &descriptor,
+            &[input],
+            &outputs,
+            change_index,
+            1_000,
+            None,
+            &resolver,
+        )
+        .unwrap();
+
+        assert_eq!(psbt.outputs.len(), 2);
+        let change = &psbt.outputs[1];
+        assert_eq!(change.amount, 49_000);
+        let account = DerivationAccount::from_str(
+            "[73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/<0;1>/*",
+        )
+        .unwrap();
+        let (pubkey, key_source) = account
+            .bip32_derivation(SECP256K1, [UnhardenedIndex::one(), change_index])
+            .unwrap();
+        assert_eq!(
+            change.bip32_derivation,
+            bmap! { pubkey => key_source.clone() }
+        );
+        assert_eq!(key_source.1.to_string(), "m/84'/0'/0'/1/7");
+        assert!(psbt.outputs[0].bip32_derivation.is_empty());
+    }
+}
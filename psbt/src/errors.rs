@@ -12,7 +12,11 @@
 // along with this software.
 // If not, see <https://opensource.org/licenses/Apache-2.0>.
 
-use bitcoin::Txid;
+use std::io;
+
+use bitcoin::consensus::encode;
+use bitcoin::util::bip32::ExtendedPubKey;
+use bitcoin::{secp256k1, OutPoint, Txid};
 
 /// Errors during [`Input`](super::Input) construction from an unsigned
 /// transaction input (see [`Input::new`](super::Input::new)).
@@ -28,6 +32,22 @@ pub enum TxinError {
     UnsignedTxHasScriptWitnesses(usize),
 }
 
+/// Errors during [`Output`](super::Output) construction from an unsigned
+/// transaction output (see [`Output::new`](super::Output::new)).
+#[derive(
+    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error
+)]
+#[display(doc_comments)]
+pub enum TxoutError {
+    /// the amount {1} of the {0} unsigned transaction output exceeds 21
+    /// million BTC.
+    AmountOutOfRange(usize, u64),
+
+    /// the {0} output `OP_RETURN` data have {1} bytes, exceeding the
+    /// standardness limit of 80 bytes.
+    OpReturnTooLong(usize, usize),
+}
+
 /// Errors during [`Psbt`](super::Psbt) construction from an unsigned
 /// transaction data (see [`Psbt::with`](super::Psbt::with())).
 #[derive(
@@ -39,11 +59,142 @@ pub enum TxError {
     #[display(inner)]
     Txin(TxinError),
 
-    /// the unsigned transaction has negative version value ({0}), which is not
-    /// allowed in PSBT.
+    /// Error in an unsigned transaction output (see [`TxoutError`]).
+    #[from]
+    #[display(inner)]
+    Txout(TxoutError),
+
+    /// the unsigned transaction has non-positive version value ({0}), which is
+    /// not allowed in PSBT.
     InvalidTxVersion(i32),
 }
 
+/// Errors combining two PSBTs for the same transaction (see
+/// [`Psbt::combine`](super::Psbt::combine)).
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Display, Error, From)]
+pub enum CombineError {
+    /// Error combining PSBT data (see [`Error`](super::Error)).
+    #[from]
+    #[display(inner)]
+    Psbt(bitcoin::psbt::Error),
+
+    /// Invalid output in one of the combined PSBTs (see [`TxoutError`]).
+    #[from]
+    #[display(inner)]
+    Txout(TxoutError),
+}
+
+/// Errors joining inputs and outputs of two different PSBTs (see
+/// [`Psbt::join`](super::Psbt::join)).
+#[derive(
+    Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error
+)]
+#[display(doc_comments)]
+pub enum JoinError {
+    /// output {0} is spent by inputs of both PSBTs.
+    DuplicateOutpoint(OutPoint),
+
+    /// PSBTs have different transaction versions {0} and {1}.
+    TxVersionMismatch(u32, u32),
+
+    /// PSBTs have different fallback lock times.
+    FallbackLocktimeMismatch,
+
+    /// extended public key {0} has different key sources in the joined PSBTs.
+    XpubConflict(ExtendedPubKey),
+}
+
+/// Errors detected during validation of deserialized PSBT data.
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display, Error
+)]
+#[display(doc_comments)]
+pub enum ValidationError {
+    /// transaction version {0} exceeds the maximum value which can be used in
+    /// a bitcoin transaction.
+    TxVersionOutOfRange(u32),
+
+    /// extended public key has depth {depth}, which does not match the length
+    /// {path_len} of its derivation path.
+    XpubDepthMismatch {
+        /// Depth of the extended public key
+        depth: u8,
+        /// Number of steps in the key source derivation path
+        path_len: usize,
+    },
+
+    /// proprietary key has an empty identifier prefix.
+    EmptyProprietaryPrefix,
+
+    /// input at position {position} has a mismatching index {index}.
+    InputIndexMismatch {
+        /// Position of the input in the PSBT
+        position: usize,
+        /// Index stored within the input data
+        index: usize,
+    },
+
+    /// output at position {position} has a mismatching index {index}.
+    OutputIndexMismatch {
+        /// Position of the output in the PSBT
+        position: usize,
+        /// Index stored within the output data
+        index: usize,
+    },
+
+    /// output {0} amount {1} exceeds 21 million BTC.
+    AmountOutOfRange(usize, u64),
+
+    /// total amount of the outputs exceeds 21 million BTC.
+    TotalAmountOutOfRange,
+}
+
+/// Errors setting raw global PSBT key-value pair.
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display, Error
+)]
+#[display(doc_comments)]
+pub enum GlobalKeyError {
+    /// global key of type {0:#04x} must not contain key data.
+    NonEmptyKeyData(u8),
+
+    /// global key of type {0:#04x} has invalid key data.
+    InvalidKeyData(u8),
+
+    /// global key of type {0:#04x} has invalid value.
+    InvalidValue(u8),
+
+    /// global key of type {0:#04x} is defined by the structure of the PSBT
+    /// and can't be set directly.
+    Structural(u8),
+}
+
+/// Error accessing PSBT input or output by an index which is out of range.
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display, Error
+)]
+#[display("index {index} is out of range, since there are only {len} items")]
+pub struct IndexError {
+    /// Index which was requested
+    pub index: usize,
+
+    /// Number of the items present
+    pub len: usize,
+}
+
+/// Error indicating that the same public key is present in the PSBT with
+/// different key sources (master key fingerprint and derivation path).
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display, Error
+)]
+#[display("public key {0} has conflicting key sources in the PSBT")]
+pub struct KeySourceConflict(pub secp256k1::PublicKey);
+
+/// Error parsing PSBT version string (see [`PsbtVersion`](super::PsbtVersion)).
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display, Error)]
+#[display("invalid PSBT version `{0}`; only versions 0 and 2 are defined")]
+pub struct PsbtVersionParseError(pub String);
+
 /// Errors happening when PSBT or other resolver information does not match the
 /// structure of bitcoin transaction
 #[derive(
@@ -60,6 +211,73 @@ pub enum InputMatchError {
     /// spent transaction does not contain input #{0} referenced by the PSBT
     /// input
     UnmatchedInputNumber(u32),
+
+    /// provided `witness_utxo` does not match the spent output from
+    /// `non_witness_utxo`
+    WitnessUtxoMismatch,
+}
+
+/// Errors validating consistency of the input redeem and witness scripts with
+/// the scriptPubKey of the spent output (see
+/// [`Input::validate_scripts`](super::Input::validate_scripts)).
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display, Error, From
+)]
+#[display(doc_comments)]
+pub enum ScriptError {
+    /// Spent output information does not match the PSBT input
+    #[from]
+    #[display(inner)]
+    Prevout(InputMatchError),
+
+    /// redeem script is provided for an input spending non-P2SH output.
+    UnexpectedRedeemScript,
+
+    /// redeem script does not match the P2SH scriptPubKey of the spent output.
+    RedeemScriptMismatch,
+
+    /// witness script is provided for an input spending neither P2WSH nor
+    /// P2SH-P2WSH output.
+    UnexpectedWitnessScript,
+
+    /// witness script is provided for an input spending P2SH output without
+    /// the redeem script.
+    NoRedeemScript,
+
+    /// witness script does not match the P2WSH witness program of the spent
+    /// output.
+    WitnessScriptMismatch,
+}
+
+/// Errors encoding relative time lock into the input sequence number (see
+/// [`Input::set_relative_locktime_time`](super::Input::set_relative_locktime_time)).
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display, Error
+)]
+#[display(doc_comments)]
+pub enum RelativeLockError {
+    /// relative time lock of {0} seconds exceeds maximum of 33553920 seconds
+    /// which can be encoded into the sequence number.
+    TimeOverflow(u32),
+}
+
+/// Errors parsing hex-encoded PSBT keys (see [`RawKeyExt`](super::RawKeyExt)
+/// and [`ProprietaryKeyExt`](super::ProprietaryKeyExt)).
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display, Error, From
+)]
+#[display(doc_comments)]
+pub enum KeyHexError {
+    /// PSBT key is not a valid hexadecimal string.
+    #[from(amplify::hex::Error)]
+    Hex,
+
+    /// PSBT key must contain at least a key type byte.
+    Empty,
+
+    /// PSBT key is not a valid proprietary key.
+    #[from(bitcoin::psbt::Error)]
+    InvalidProprietaryKey,
 }
 
 /// Errors happening during fee computation
@@ -76,3 +294,84 @@ pub enum FeeError {
     /// Sum of inputs is less than sum of outputs
     InputsLessThanOutputs,
 }
+
+/// Errors decoding binary PSBT data (see
+/// [`Psbt::decode`](super::Psbt::decode)).
+#[derive(Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum DecodeError {
+    /// data are not a PSBT: they do not start with PSBT magic bytes
+    /// `70736274ff`.
+    BadMagic,
+
+    /// PSBT version {0} is not supported.
+    UnsupportedVersion(u32),
+
+    /// PSBT data are truncated.
+    TruncatedInput,
+
+    /// PSBT data are followed by {0} extra bytes.
+    TrailingData(usize),
+
+    /// PSBT data exceed the size limit of {0} bytes.
+    SizeLimitExceeded(usize),
+
+    /// invalid PSBT data: {0}.
+    Invalid(encode::Error),
+}
+
+impl From<encode::Error> for DecodeError {
+    fn from(err: encode::Error) -> Self {
+        match err {
+            encode::Error::Io(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                DecodeError::TruncatedInput
+            }
+            encode::Error::Psbt(bitcoin::psbt::Error::InvalidMagic)
+            | encode::Error::Psbt(bitcoin::psbt::Error::InvalidSeparator) => DecodeError::BadMagic,
+            err => DecodeError::Invalid(err),
+        }
+    }
+}
+
+impl From<DecodeError> for encode::Error {
+    fn from(err: DecodeError) -> Self {
+        match err {
+            DecodeError::BadMagic => bitcoin::psbt::Error::InvalidMagic.into(),
+            DecodeError::UnsupportedVersion(_) => {
+                encode::Error::ParseFailed("unsupported PSBT version")
+            }
+            DecodeError::TruncatedInput => {
+                io::Error::new(io::ErrorKind::UnexpectedEof, "truncated PSBT data").into()
+            }
+            DecodeError::TrailingData(_) => encode::Error::ParseFailed(
+                "data not consumed entirely when explicitly deserializing",
+            ),
+            DecodeError::SizeLimitExceeded(_) => {
+                encode::Error::ParseFailed("PSBT data exceed the size limit")
+            }
+            DecodeError::Invalid(err) => err,
+        }
+    }
+}
+
+impl From<TxoutError> for encode::Error {
+    fn from(_: TxoutError) -> Self {
+        encode::Error::ParseFailed("PSBT output amount exceeds 21 million BTC")
+    }
+}
+
+/// Errors extracting or measuring finalized PSBT transaction (see
+/// [`Psbt::extract_finalized_tx`](super::Psbt::extract_finalized_tx) and
+/// [`Psbt::finalized_weight`](super::Psbt::finalized_weight)).
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display, Error, From
+)]
+#[display(doc_comments)]
+pub enum FinalizedTxError {
+    /// input #{0} is not finalized.
+    NotFinalized(usize),
+
+    /// unable to compute transaction fee: {0}.
+    #[from]
+    Fee(FeeError),
+}
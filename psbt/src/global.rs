@@ -12,29 +12,52 @@
 // along with this software.
 // If not, see <https://opensource.org/licenses/Apache-2.0>.
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Display, Formatter};
+use std::io::Read;
 use std::str::FromStr;
 
 use amplify::hex::{FromHex, ToHex};
-use bitcoin::util::bip32::{ExtendedPubKey, KeySource};
-use bitcoin::{consensus, Transaction, Txid};
-use descriptors::locks::LockTime;
+use bitcoin::consensus::encode::VarInt;
+use bitcoin::util::bip32::{ChildNumber, DerivationPath, ExtendedPubKey, Fingerprint, KeySource};
+use bitcoin::{
+    consensus, secp256k1, Amount, EcdsaSighashType, OutPoint, Script, Transaction, Txid,
+};
+use bitcoin_onchain::ResolveTx;
+use descriptors::locks::{LockTime, SeqNo};
 #[cfg(feature = "serde")]
 use serde_with::{hex::Hex, As, Same};
 
 use crate::serialize::{Deserialize, Serialize};
 use crate::v0::PsbtV0;
-use crate::{raw, Error, FeeError, Input, Output, PsbtVersion, TxError};
+use crate::{
+    fingerprint_eq_ct, raw, v2, CombineError, DecodeError, FeeError, FinalizedTxError,
+    GlobalKeyError, IndexError, Input, InputMatchError, JoinError, KeySourceConflict, Output,
+    PsbtSighashType, PsbtVersion, TxError, TxoutError, ValidationError,
+};
 
-// TODO: Do manual serde and strict encoding implementation to check the
-//       deserialized values
-#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub(crate) const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+pub(crate) const PSBT_GLOBAL_XPUB: u8 = 0x01;
+pub(crate) const PSBT_GLOBAL_TX_VERSION: u8 = 0x02;
+pub(crate) const PSBT_GLOBAL_FALLBACK_LOCKTIME: u8 = 0x03;
+pub(crate) const PSBT_GLOBAL_INPUT_COUNT: u8 = 0x04;
+pub(crate) const PSBT_GLOBAL_OUTPUT_COUNT: u8 = 0x05;
+pub(crate) const PSBT_GLOBAL_TX_MODIFIABLE: u8 = 0x06;
+pub(crate) const PSBT_GLOBAL_VERSION: u8 = 0xFB;
+pub(crate) const PSBT_GLOBAL_PROPRIETARY: u8 = 0xFC;
+
+// TODO: Do manual strict encoding implementation to check the deserialized
+//       values
+/// Modern PSBT representation, which is version-agnostic.
+///
+/// Serde deserialization of the structure validates the data with
+/// [`Psbt::validate`], rejecting PSBTs which are not valid.
+#[derive(Clone, Eq, PartialEq, Debug)]
 #[derive(StrictEncode, StrictDecode)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
-    serde(crate = "serde_crate")
+    serde(crate = "serde_crate", try_from = "PsbtUnchecked")
 )]
 pub struct Psbt {
     /// The version number of this PSBT. If omitted, the version number is 0.
@@ -65,9 +88,64 @@ pub struct Psbt {
     pub unknown: BTreeMap<raw::Key, Vec<u8>>,
 }
 
+/// Serde representation of [`Psbt`] before its validation with
+/// [`Psbt::validate`].
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(crate = "serde_crate")]
+struct PsbtUnchecked {
+    psbt_version: PsbtVersion,
+    tx_version: u32,
+    fallback_locktime: Option<LockTime>,
+    inputs: Vec<Input>,
+    outputs: Vec<Output>,
+    xpub: BTreeMap<ExtendedPubKey, KeySource>,
+    #[serde(with = "As::<BTreeMap<Same, Hex>>")]
+    proprietary: BTreeMap<raw::ProprietaryKey, Vec<u8>>,
+    #[serde(with = "As::<BTreeMap<Same, Hex>>")]
+    unknown: BTreeMap<raw::Key, Vec<u8>>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<PsbtUnchecked> for Psbt {
+    type Error = ValidationError;
+
+    fn try_from(unchecked: PsbtUnchecked) -> Result<Self, Self::Error> {
+        let psbt = Psbt {
+            psbt_version: unchecked.psbt_version,
+            tx_version: unchecked.tx_version,
+            fallback_locktime: unchecked.fallback_locktime,
+            inputs: unchecked.inputs,
+            outputs: unchecked.outputs,
+            xpub: unchecked.xpub,
+            proprietary: unchecked.proprietary,
+            unknown: unchecked.unknown,
+        };
+        psbt.validate()?;
+        Ok(psbt)
+    }
+}
+
+impl Default for Psbt {
+    /// Constructs empty PSBTv2 for a transaction of version 2, which is the
+    /// default transaction version according to BIP-370.
+    fn default() -> Self {
+        Psbt {
+            psbt_version: PsbtVersion::V2,
+            tx_version: 2,
+            fallback_locktime: None,
+            inputs: vec![],
+            outputs: vec![],
+            xpub: none!(),
+            proprietary: none!(),
+            unknown: none!(),
+        }
+    }
+}
+
 impl Psbt {
     /// Checks that unsigned transaction does not have scriptSig's or witness
-    /// data
+    /// data, and has a positive version number
     pub fn with(tx: Transaction, psbt_version: PsbtVersion) -> Result<Self, TxError> {
         let inputs = tx
             .input
@@ -79,13 +157,14 @@ impl Psbt {
             .output
             .into_iter()
             .enumerate()
-            .map(|(index, txout)| Output::new(index, txout))
-            .collect();
+            .map(|(index, txout)| Output::new(index, txout).map_err(TxError::from))
+            .collect::<Result<_, TxError>>()?;
 
         let i32_version = tx.version;
-        let tx_version = i32_version
-            .try_into()
-            .map_err(|_| TxError::InvalidTxVersion(i32_version))?;
+        let tx_version = match i32_version.try_into() {
+            Ok(0) | Err(_) => return Err(TxError::InvalidTxVersion(i32_version)),
+            Ok(version) => version,
+        };
 
         let fallback_locktime = match tx.lock_time {
             0 => None,
@@ -104,6 +183,87 @@ impl Psbt {
         })
     }
 
+    /// Validates consistency of the PSBT data: transaction version range,
+    /// depth of the global extended public keys, proprietary key prefixes,
+    /// indexes of inputs and outputs and output amounts, which must not exceed
+    /// 21 million BTC both individually and in total.
+    ///
+    /// This is a separate check, stricter than the one performed by decoding
+    /// of the binary PSBT serialization ([`Psbt::decode`],
+    /// [`Deserialize::deserialize`]) and by [`Psbt::set_global`], which keep
+    /// transaction version and extended public keys as-is for the
+    /// serialization roundtrip to be lossless, and reject only output amounts
+    /// exceeding 21 million BTC. Deserialization of the JSON representation
+    /// with serde always runs this validation.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.tx_version > i32::MAX as u32 {
+            return Err(ValidationError::TxVersionOutOfRange(self.tx_version));
+        }
+        for (xpub, (_, path)) in &self.xpub {
+            if xpub.depth as usize != path.len() {
+                return Err(ValidationError::XpubDepthMismatch {
+                    depth: xpub.depth,
+                    path_len: path.len(),
+                });
+            }
+        }
+        let proprietary = self
+            .proprietary
+            .keys()
+            .chain(
+                self.inputs
+                    .iter()
+                    .flat_map(|input| input.proprietary.keys()),
+            )
+            .chain(
+                self.outputs
+                    .iter()
+                    .flat_map(|output| output.proprietary.keys()),
+            );
+        for key in proprietary {
+            if key.prefix.is_empty() {
+                return Err(ValidationError::EmptyProprietaryPrefix);
+            }
+        }
+        for (position, input) in self.inputs.iter().enumerate() {
+            if input.index != position {
+                return Err(ValidationError::InputIndexMismatch {
+                    position,
+                    index: input.index,
+                });
+            }
+        }
+        for (position, output) in self.outputs.iter().enumerate() {
+            if output.index != position {
+                return Err(ValidationError::OutputIndexMismatch {
+                    position,
+                    index: output.index,
+                });
+            }
+        }
+        let mut total = 0u64;
+        for (index, output) in self.outputs.iter().enumerate() {
+            if output.amount > Amount::MAX_MONEY.as_sat() {
+                return Err(ValidationError::AmountOutOfRange(index, output.amount));
+            }
+            total = total.saturating_add(output.amount);
+        }
+        if total > Amount::MAX_MONEY.as_sat() {
+            return Err(ValidationError::TotalAmountOutOfRange);
+        }
+        Ok(())
+    }
+
+    /// Detects whether the transaction signals replaceability under BIP-125,
+    /// i.e. whether some of its inputs have a sequence number below
+    /// 0xfffffffe.
+    #[inline]
+    pub fn is_rbf_signaling(&self) -> bool {
+        self.inputs
+            .iter()
+            .any(|input| SeqNo::from_consensus(input.sequence()).is_rbf())
+    }
+
     pub fn lock_time(&self) -> LockTime {
         let required_time_locktime = self
             .inputs
@@ -132,6 +292,241 @@ impl Psbt {
         }
     }
 
+    /// Returns reference to the input at the provided `index`, or errors if
+    /// the index is out of range.
+    pub fn input(&self, index: usize) -> Result<&Input, IndexError> {
+        let len = self.inputs.len();
+        self.inputs.get(index).ok_or(IndexError { index, len })
+    }
+
+    /// Returns mutable reference to the input at the provided `index`, or
+    /// errors if the index is out of range.
+    pub fn input_mut(&mut self, index: usize) -> Result<&mut Input, IndexError> {
+        let len = self.inputs.len();
+        self.inputs.get_mut(index).ok_or(IndexError { index, len })
+    }
+
+    /// Returns reference to the output at the provided `index`, or errors if
+    /// the index is out of range.
+    pub fn output(&self, index: usize) -> Result<&Output, IndexError> {
+        let len = self.outputs.len();
+        self.outputs.get(index).ok_or(IndexError { index, len })
+    }
+
+    /// Returns mutable reference to the output at the provided `index`, or
+    /// errors if the index is out of range.
+    pub fn output_mut(&mut self, index: usize) -> Result<&mut Output, IndexError> {
+        let len = self.outputs.len();
+        self.outputs.get_mut(index).ok_or(IndexError { index, len })
+    }
+
+    /// Iterates over inputs together with their indexes and outpoints they
+    /// spend.
+    ///
+    /// Both PSBTv0 and PSBTv2 are represented with per-input
+    /// [`Input::previous_outpoint`] data (for PSBTv0 it is taken from the
+    /// unsigned transaction during parsing), so the outpoints are the same as
+    /// in the transaction returned by [`Psbt::to_unsigned_tx`].
+    pub fn enumerate_inputs(&self) -> impl Iterator<Item = (usize, OutPoint, &Input)> {
+        self.inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| (index, input.previous_outpoint, input))
+    }
+
+    /// Iterates over outputs together with their indexes, `scriptPubkey`s and
+    /// amounts.
+    pub fn enumerate_outputs(&self) -> impl Iterator<Item = (usize, &Script, u64, &Output)> {
+        self.outputs
+            .iter()
+            .enumerate()
+            .map(|(index, output)| (index, &output.script, output.amount, output))
+    }
+
+    /// Sets global key-value pair from its raw representation. Keys of known
+    /// types are decoded and put into the corresponding typed fields; keys of
+    /// unknown types are put into [`Psbt::unknown`] map.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`GlobalKeyError`] if the key or value data are malformed
+    /// for a known key type, or if the key type is determined by the
+    /// structure of the PSBT (unsigned transaction, number of inputs and
+    /// outputs, transaction modification flags).
+    pub fn set_global(&mut self, key: raw::Key, value: Vec<u8>) -> Result<(), GlobalKeyError> {
+        let type_value = key.type_value;
+        let u32_value = || -> Result<u32, GlobalKeyError> {
+            if !key.key.is_empty() {
+                return Err(GlobalKeyError::NonEmptyKeyData(type_value));
+            }
+            let bytes = <[u8; 4]>::try_from(value.as_slice())
+                .map_err(|_| GlobalKeyError::InvalidValue(type_value))?;
+            Ok(u32::from_le_bytes(bytes))
+        };
+
+        match type_value {
+            PSBT_GLOBAL_UNSIGNED_TX
+            | PSBT_GLOBAL_INPUT_COUNT
+            | PSBT_GLOBAL_OUTPUT_COUNT
+            | PSBT_GLOBAL_TX_MODIFIABLE => return Err(GlobalKeyError::Structural(type_value)),
+            PSBT_GLOBAL_XPUB => {
+                let xpub = ExtendedPubKey::decode(&key.key)
+                    .map_err(|_| GlobalKeyError::InvalidKeyData(type_value))?;
+                if value.is_empty() || value.len() % 4 != 0 {
+                    return Err(GlobalKeyError::InvalidValue(type_value));
+                }
+                let fingerprint = Fingerprint::from(&value[..4]);
+                let path = value[4..]
+                    .chunks(4)
+                    .map(|chunk| {
+                        let index = u32::from_le_bytes(chunk.try_into().expect("chunks of 4"));
+                        ChildNumber::from(index)
+                    })
+                    .collect::<Vec<_>>();
+                self.xpub
+                    .insert(xpub, (fingerprint, DerivationPath::from(path)));
+            }
+            // Like the binary PSBT decoders, keeps the version as-is even if
+            // it does not fit into `i32` (see `Psbt::validate`)
+            PSBT_GLOBAL_TX_VERSION => {
+                self.tx_version = u32_value()?;
+            }
+            PSBT_GLOBAL_FALLBACK_LOCKTIME => {
+                self.fallback_locktime = Some(LockTime::from_consensus(u32_value()?));
+            }
+            PSBT_GLOBAL_VERSION => {
+                self.psbt_version = match u32_value()? {
+                    0 => PsbtVersion::V0,
+                    2 => PsbtVersion::V2,
+                    _ => return Err(GlobalKeyError::InvalidValue(type_value)),
+                };
+            }
+            PSBT_GLOBAL_PROPRIETARY => {
+                let key = raw::ProprietaryKey::from_key(key)
+                    .map_err(|_| GlobalKeyError::InvalidKeyData(type_value))?;
+                self.proprietary.insert(key, value);
+            }
+            _ => {
+                self.unknown.insert(key, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Collects all public keys with their key sources (master key fingerprint
+    /// and derivation path) from the global extended public keys map and from
+    /// BIP32 and taproot key origin information of all inputs and outputs.
+    /// Taproot x-only keys are reported as public keys with an even Y
+    /// coordinate, as they are defined by BIP-340.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`KeySourceConflict`] if the same public key is present with
+    /// different key sources.
+    pub fn all_key_sources(
+        &self,
+    ) -> Result<BTreeMap<secp256k1::PublicKey, KeySource>, KeySourceConflict> {
+        let mut key_sources = BTreeMap::<secp256k1::PublicKey, KeySource>::new();
+        let tap_key_origins = self
+            .inputs
+            .iter()
+            .flat_map(|input| &input.tap_key_origins)
+            .chain(
+                self.outputs
+                    .iter()
+                    .flat_map(|output| &output.tap_key_origins),
+            )
+            .map(|(xonly, (_, source))| {
+                let mut data = vec![0x02];
+                data.extend(xonly.serialize().iter());
+                let pubkey = secp256k1::PublicKey::from_slice(&data).expect("fixed size slice");
+                (pubkey, source)
+            });
+        let iter = self
+            .xpub
+            .iter()
+            .map(|(xpub, source)| (&xpub.public_key, source))
+            .chain(self.inputs.iter().flat_map(|input| &input.bip32_derivation))
+            .chain(
+                self.outputs
+                    .iter()
+                    .flat_map(|output| &output.bip32_derivation),
+            )
+            .map(|(pubkey, source)| (*pubkey, source))
+            .chain(tap_key_origins);
+        for (pubkey, source) in iter {
+            match key_sources.get(&pubkey) {
+                Some(first) if first != source => return Err(KeySourceConflict(pubkey)),
+                Some(_) => {}
+                None => {
+                    key_sources.insert(pubkey, source.clone());
+                }
+            }
+        }
+        Ok(key_sources)
+    }
+
+    /// Removes global extended public keys which are not referenced by any of
+    /// the BIP32 or taproot key origins of the inputs and outputs, i.e. which
+    /// have no key source with the same master key fingerprint and a
+    /// derivation path starting with the extended key derivation path.
+    ///
+    /// Extended keys duplicating the public key and chain code of some other
+    /// global extended key (and differing only in their metadata) are removed
+    /// as well.
+    pub fn prune_xpubs(&mut self) {
+        let sources = self
+            .inputs
+            .iter()
+            .flat_map(|input| {
+                input
+                    .bip32_derivation
+                    .values()
+                    .chain(input.tap_key_origins.values().map(|(_, source)| source))
+            })
+            .chain(self.outputs.iter().flat_map(|output| {
+                output
+                    .bip32_derivation
+                    .values()
+                    .chain(output.tap_key_origins.values().map(|(_, source)| source))
+            }))
+            .collect::<Vec<_>>();
+        let mut keys = BTreeSet::new();
+        self.xpub.retain(|xpub, (fingerprint, path)| {
+            let is_referenced = sources.iter().any(|(source_fingerprint, source_path)| {
+                source_fingerprint == fingerprint && source_path.as_ref().starts_with(path.as_ref())
+            });
+            is_referenced && keys.insert((xpub.public_key, xpub.chain_code))
+        });
+    }
+
+    /// Replaces master key fingerprint `old` with `new` in all key sources
+    /// present in the PSBT: global extended public keys, BIP32 and taproot key
+    /// origin information of all inputs and outputs. Key sources with other
+    /// fingerprints and all derivation paths are kept unchanged.
+    pub fn rebase_keys(&mut self, old: Fingerprint, new: Fingerprint) {
+        let rebase = |(fingerprint, _): &mut KeySource| {
+            if fingerprint_eq_ct(fingerprint, &old) {
+                *fingerprint = new;
+            }
+        };
+        self.xpub.values_mut().for_each(rebase);
+        for input in &mut self.inputs {
+            input.bip32_derivation.values_mut().for_each(rebase);
+            input
+                .tap_key_origins
+                .values_mut()
+                .for_each(|(_, source)| rebase(source));
+        }
+        for output in &mut self.outputs {
+            output.bip32_derivation.values_mut().for_each(rebase);
+            output
+                .tap_key_origins
+                .values_mut()
+                .for_each(|(_, source)| rebase(source));
+        }
+    }
+
     pub(crate) fn tx_version(&self) -> i32 { i32::from_be_bytes(self.tx_version.to_be_bytes()) }
 
     /// Returns fee for a transaction, or returns error reporting resolver
@@ -151,11 +546,129 @@ impl Psbt {
         }
     }
 
+    /// Returns indexes of outputs with amounts below the dust threshold for
+    /// their script type at the given dust relay fee rate, in satoshis per
+    /// virtual byte (see [`Output::dust_threshold`]). Transactions having such
+    /// outputs are non-standard and are not relayed by Bitcoin Core nodes.
+    pub fn check_dust(&self, dust_relay_feerate: f64) -> Vec<usize> {
+        self.outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| output.is_dust(dust_relay_feerate))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Detects address reuse within the transaction outputs, returning pairs
+    /// of indexes of the outputs paying to the same `scriptPubkey`. In each
+    /// pair the first index is lower than the second one.
+    pub fn reused_output_scripts(&self) -> Vec<(usize, usize)> {
+        let mut pairs = vec![];
+        for (index, output) in self.outputs.iter().enumerate() {
+            for (other_index, other) in self.outputs.iter().enumerate().skip(index + 1) {
+                if output.script == other.script {
+                    pairs.push((index, other_index));
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Detects outputs paying back to the `scriptPubkey` of one of the spent
+    /// outputs, returning pairs of input and output indexes. Inputs lacking
+    /// information about the spent output are skipped.
+    pub fn outputs_reusing_input_scripts(&self) -> Vec<(usize, usize)> {
+        let mut pairs = vec![];
+        for (index, input) in self.inputs.iter().enumerate() {
+            let prevout = match input.input_prevout() {
+                Ok(prevout) => prevout,
+                Err(_) => continue,
+            };
+            for (output_index, output) in self.outputs.iter().enumerate() {
+                if output.script == prevout.script_pubkey {
+                    pairs.push((index, output_index));
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Removes full previous transactions (`non_witness_utxo`) from all of the
+    /// inputs, keeping the spent outputs (`witness_utxo`), for transmitting
+    /// the PSBT to a signer which already has the previous transactions.
+    ///
+    /// Returns removed transactions, which can be put back with
+    /// [`Psbt::attach_utxos`].
+    pub fn strip_utxos(&mut self) -> BTreeMap<Txid, Transaction> {
+        self.inputs
+            .iter_mut()
+            .filter_map(|input| input.non_witness_utxo.take())
+            .map(|tx| (tx.txid(), tx))
+            .collect()
+    }
+
+    /// Adds previous transactions (`non_witness_utxo`) to the inputs lacking
+    /// them, using the transactions known to the `resolver`; inputs for which
+    /// the resolver fails are left unmodified. This reverses
+    /// [`Psbt::strip_utxos`].
+    ///
+    /// Returns number of the updated inputs.
+    ///
+    /// # Errors
+    ///
+    /// Errors if a resolved transaction does not have the output spent by the
+    /// input (see [`Input::set_non_witness_utxo`]).
+    pub fn attach_utxos(&mut self, resolver: &impl ResolveTx) -> Result<usize, InputMatchError> {
+        let mut count = 0usize;
+        for input in &mut self.inputs {
+            if input.non_witness_utxo.is_some() {
+                continue;
+            }
+            if let Ok(tx) = resolver.resolve_tx(input.previous_outpoint.txid) {
+                input.set_non_witness_utxo(tx)?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
     /// Returns transaction ID for an unsigned transaction. For SegWit
     /// transactions this is equal to the signed transaction id.
+    ///
+    /// Since signatures and final `scriptSig` and `witness` data are not
+    /// included, the value does not change with signing and finalization,
+    /// and is the same for both PSBTv0 and PSBTv2 forms of the PSBT, serving as
+    /// a stable PSBT identifier.
     #[inline]
     pub fn to_txid(&self) -> Txid { self.to_unsigned_tx().txid() }
 
+    /// Returns consensus serialization of the unsigned transaction (see
+    /// [`Psbt::to_unsigned_tx`]), i.e. of the transaction in the form it is
+    /// embedded into the PSBTv0 global map. For PSBTv2 the transaction is
+    /// assembled from the per-input and per-output data.
+    #[inline]
+    pub fn unsigned_tx_bytes(&self) -> Vec<u8> { consensus::serialize(&self.to_unsigned_tx()) }
+
+    /// Checks whether the PSBT describes the same spending as the provided
+    /// transaction: the same version and lock time, inputs spending the same
+    /// outpoints with the same sequence numbers and the same outputs. Input
+    /// `scriptSig`s and witnesses are ignored, so `tx` may be either unsigned
+    /// or signed.
+    pub fn matches_tx(&self, tx: &Transaction) -> bool {
+        let unsigned_tx = self.to_unsigned_tx();
+        unsigned_tx.version == tx.version
+            && unsigned_tx.lock_time == tx.lock_time
+            && unsigned_tx.input.len() == tx.input.len()
+            && unsigned_tx
+                .input
+                .iter()
+                .zip(&tx.input)
+                .all(|(txin, other)| {
+                    txin.previous_output == other.previous_output && txin.sequence == other.sequence
+                })
+            && unsigned_tx.output == tx.output
+    }
+
     /// Constructs transaction with empty `scriptSig` and `witness`
     pub fn to_unsigned_tx(&self) -> Transaction {
         let version = self.tx_version();
@@ -204,20 +717,181 @@ impl Psbt {
         tx
     }
 
+    /// Extracts the signed transaction from this PSBT, requiring all of the
+    /// inputs to be finalized.
+    ///
+    /// Errors with [`FinalizedTxError::NotFinalized`] for the first input
+    /// which is not finalized.
+    pub fn extract_finalized_tx(&self) -> Result<Transaction, FinalizedTxError> {
+        match self.inputs.iter().find(|input| !input.is_finalized()) {
+            Some(input) => Err(FinalizedTxError::NotFinalized(input.index())),
+            None => Ok(self.extract_signed_tx()),
+        }
+    }
+
+    /// Extracts the finalized transaction (see [`Psbt::extract_finalized_tx`])
+    /// and returns hex encoding of its network serialization, ready for
+    /// broadcasting with `sendrawtransaction`.
+    #[inline]
+    pub fn to_network_tx_hex(&self) -> Result<String, FinalizedTxError> {
+        self.extract_finalized_tx()
+            .map(|tx| consensus::serialize(&tx).to_hex())
+    }
+
     /// Combines this [`Psbt`] with `other` PSBT as described by BIP 174.
     ///
     /// In accordance with BIP 174 this function is commutative i.e.,
     /// `A.combine(B) == B.combine(A)`
+    ///
+    /// Errors with [`CombineError::Txout`] if some of the output amounts
+    /// exceed 21 million BTC.
     #[inline]
-    pub fn combine(self, other: Self) -> Result<Self, Error> {
+    pub fn combine(self, other: Self) -> Result<Self, CombineError> {
         let mut first = PsbtV0::from(self);
         first.combine(other.into())?;
-        Ok(first.into())
+        Ok(Psbt::try_from(first)?)
+    }
+
+    /// Joins inputs and outputs of a different `other` PSBT into this PSBT,
+    /// appending them after the existing inputs and outputs. Unlike
+    /// [`Psbt::combine`], which merges data for the same transaction, this
+    /// constructs a new transaction, like a coinjoin.
+    ///
+    /// Signatures which do not commit to the changed parts of the transaction
+    /// (i.e. `ANYONECANPAY` signatures with `NONE` sighash type, or with
+    /// `ALL` or `SINGLE` types if the committed outputs are kept) remain
+    /// valid; all others are removed, together with the final `scriptSig`
+    /// and witness data (for which the input sighash type is assumed). Since
+    /// all sighash types commit to the transaction lock time, all signatures
+    /// of the inputs are removed if the joined transaction has a lock time
+    /// different from the one of the PSBT the input came from. Returns
+    /// indexes of the inputs which got their signatures removed.
+    ///
+    /// # Errors
+    ///
+    /// If both PSBTs spend the same output, have different transaction
+    /// versions or fallback lock times, or have conflicting key sources for
+    /// the same extended public key.
+    pub fn join(&mut self, other: Psbt) -> Result<Vec<usize>, JoinError> {
+        if self.tx_version != other.tx_version {
+            return Err(JoinError::TxVersionMismatch(
+                self.tx_version,
+                other.tx_version,
+            ));
+        }
+        let fallback_locktime = match (self.fallback_locktime, other.fallback_locktime) {
+            (Some(a), Some(b)) if a != b => return Err(JoinError::FallbackLocktimeMismatch),
+            (a, b) => a.or(b),
+        };
+        for input in &other.inputs {
+            if self
+                .inputs
+                .iter()
+                .any(|inp| inp.previous_outpoint == input.previous_outpoint)
+            {
+                return Err(JoinError::DuplicateOutpoint(input.previous_outpoint));
+            }
+        }
+        for (xpub, source) in &other.xpub {
+            match self.xpub.get(xpub) {
+                Some(existing) if existing != source => {
+                    return Err(JoinError::XpubConflict(*xpub));
+                }
+                _ => {}
+            }
+        }
+
+        let (lock_time, other_lock_time) = (self.lock_time(), other.lock_time());
+        self.fallback_locktime = fallback_locktime;
+        self.xpub.extend(other.xpub);
+        for (key, value) in other.proprietary {
+            self.proprietary.entry(key).or_insert(value);
+        }
+        for (key, value) in other.unknown {
+            self.unknown.entry(key).or_insert(value);
+        }
+        let (input_offset, output_offset) = (self.inputs.len(), self.outputs.len());
+        let other_input_count = other.inputs.len();
+        self.inputs.extend(other.inputs);
+        self.outputs.extend(other.outputs);
+        for (index, output) in self.outputs.iter_mut().enumerate() {
+            output.index = index;
+        }
+
+        let input_count = self.inputs.len();
+        let joined_lock_time = self.lock_time();
+        let outputs = &self.outputs;
+        let mut invalidated = vec![];
+        for (index, input) in self.inputs.iter_mut().enumerate() {
+            input.index = index;
+            // Number of inputs and outputs of the PSBT which originally had
+            // this input, index of the input and lock time of that PSBT
+            let (source_index, source_input_count, source_outputs, source_lock_time) =
+                if index < input_offset {
+                    (index, input_offset, &outputs[..output_offset], lock_time)
+                } else {
+                    let source_outputs = &outputs[output_offset..];
+                    (
+                        index - input_offset,
+                        other_input_count,
+                        source_outputs,
+                        other_lock_time,
+                    )
+                };
+            let survives = |sighash_type: u32| {
+                let anyone_can_pay = sighash_type & 0x80 != 0;
+                source_lock_time == joined_lock_time
+                    && (anyone_can_pay || source_input_count == input_count)
+                    && match sighash_type & 0x1f {
+                        // SIGHASH_NONE
+                        0x02 => true,
+                        // SIGHASH_SINGLE
+                        0x03 => {
+                            outputs.get(index).map(Output::to_txout)
+                                == source_outputs.get(source_index).map(Output::to_txout)
+                        }
+                        // SIGHASH_ALL and taproot default sighash
+                        _ => source_outputs.len() == outputs.len(),
+                    }
+            };
+
+            let sig_count = input.partial_sigs.len() + input.tap_script_sigs.len();
+            let mut changed = false;
+            input
+                .partial_sigs
+                .retain(|_, sig| survives(sig.hash_ty as u32));
+            input
+                .tap_script_sigs
+                .retain(|_, sig| survives(sig.hash_ty as u32));
+            if let Some(sig) = input.tap_key_sig {
+                if !survives(sig.hash_ty as u32) {
+                    input.tap_key_sig = None;
+                    changed = true;
+                }
+            }
+            let finalized =
+                input.final_script_sig.is_some() || input.final_script_witness.is_some();
+            let sighash_type = input
+                .sighash_type
+                .map(PsbtSighashType::to_u32)
+                .unwrap_or(EcdsaSighashType::All as u32);
+            if finalized && !survives(sighash_type) {
+                input.final_script_sig = None;
+                input.final_script_witness = None;
+                changed = true;
+            }
+            if changed || sig_count != input.partial_sigs.len() + input.tap_script_sigs.len() {
+                invalidated.push(index);
+            }
+        }
+        Ok(invalidated)
     }
 }
 
-impl From<PsbtV0> for Psbt {
-    fn from(v0: PsbtV0) -> Self {
+impl TryFrom<PsbtV0> for Psbt {
+    type Error = TxoutError;
+
+    fn try_from(v0: PsbtV0) -> Result<Self, Self::Error> {
         let tx = v0.unsigned_tx;
 
         let inputs = v0
@@ -234,7 +908,7 @@ impl From<PsbtV0> for Psbt {
             .zip(tx.output)
             .enumerate()
             .map(|(index, (output, txout))| Output::with(index, output, txout))
-            .collect();
+            .collect::<Result<_, _>>()?;
 
         let tx_version = u32::from_be_bytes(tx.version.to_be_bytes());
 
@@ -243,7 +917,7 @@ impl From<PsbtV0> for Psbt {
             other => Some(other.into()),
         };
 
-        Psbt {
+        Ok(Psbt {
             // We need to serialize back in the same version we deserialzied from
             psbt_version: PsbtVersion::V0,
             xpub: v0.xpub,
@@ -253,7 +927,7 @@ impl From<PsbtV0> for Psbt {
             outputs,
             proprietary: v0.proprietary,
             unknown: v0.unknown,
-        }
+        })
     }
 }
 
@@ -274,7 +948,7 @@ impl From<Psbt> for PsbtV0 {
 
         PsbtV0 {
             unsigned_tx,
-            version: PsbtVersion::V0 as u32,
+            version: PsbtVersion::V0.as_u32(),
             xpub: psbt.xpub,
             proprietary: psbt.proprietary,
             unknown: psbt.unknown,
@@ -287,12 +961,119 @@ impl From<Psbt> for PsbtV0 {
 // TODO: Implement own PSBT BIP174 serialization trait and its own custom error
 //       type handling different PSBT versions.
 impl Serialize for Psbt {
-    fn serialize(&self) -> Vec<u8> { consensus::encode::serialize::<PsbtV0>(&self.clone().into()) }
+    fn serialize(&self) -> Vec<u8> {
+        match self.psbt_version {
+            PsbtVersion::V0 => consensus::encode::serialize::<PsbtV0>(&self.clone().into()),
+            PsbtVersion::V2 => v2::serialize(self),
+        }
+    }
+}
+
+impl Psbt {
+    /// Decodes PSBT from its binary serialization of any supported version,
+    /// reporting the specific reason of the failure.
+    ///
+    /// Unlike [`Deserialize::deserialize`], errors distinguish data which are
+    /// not a PSBT at all ([`DecodeError::BadMagic`]) from PSBTs with an
+    /// unsupported version, truncated PSBTs and PSBTs followed by extra data.
+    ///
+    /// Decoding is less strict than [`Psbt::validate`]: for the serialization
+    /// roundtrip to be lossless it accepts transaction versions exceeding
+    /// `i32::MAX` and extended public keys with depth not matching their
+    /// derivation path. Call [`Psbt::validate`] on the decoded PSBT to reject
+    /// them.
+    pub fn decode(bytes: &[u8]) -> Result<Psbt, DecodeError> {
+        const MAGIC: &[u8] = b"psbt\xff";
+        if !bytes.starts_with(MAGIC) {
+            return Err(if MAGIC.starts_with(bytes) {
+                DecodeError::TruncatedInput
+            } else {
+                DecodeError::BadMagic
+            });
+        }
+        let framed = &bytes[..MAGIC.len() + framed_len(&bytes[MAGIC.len()..])];
+        let (psbt, consumed) = match v2::decode_version(framed)? {
+            PsbtVersion::V0 => {
+                let (v0, consumed) = consensus::encode::deserialize_partial::<PsbtV0>(framed)?;
+                (
+                    Psbt::try_from(v0).map_err(consensus::encode::Error::from)?,
+                    consumed,
+                )
+            }
+            PsbtVersion::V2 => v2::deserialize_partial(framed)?,
+        };
+        if consumed < bytes.len() {
+            return Err(DecodeError::TrailingData(bytes.len() - consumed));
+        }
+        Ok(psbt)
+    }
+
+    /// Decodes PSBT from the `reader`, refusing to read more than `max_bytes`
+    /// bytes from it. This allows processing PSBTs from untrusted sources
+    /// without allocating memory for arbitrary large data: the consumed data
+    /// never exceed `max_bytes` (plus a single byte used to detect the limit
+    /// excess), and all key and value lengths are checked against the
+    /// consumed data before decoding (see [`Psbt::decode`]).
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`DecodeError::SizeLimitExceeded`] if the reader provides
+    /// more than `max_bytes` of data, or with the errors of [`Psbt::decode`].
+    pub fn from_reader_limited(reader: impl Read, max_bytes: usize) -> Result<Psbt, DecodeError> {
+        let mut data = vec![];
+        reader
+            .take((max_bytes as u64).saturating_add(1))
+            .read_to_end(&mut data)
+            .map_err(consensus::encode::Error::from)?;
+        if data.len() > max_bytes {
+            return Err(DecodeError::SizeLimitExceeded(max_bytes));
+        }
+        Psbt::decode(&data)
+    }
+}
+
+/// Walks key-value pairs of the serialized PSBT maps without decoding them,
+/// returning the length of the data prefix made of complete key-value pairs
+/// and map separators. None of the key or value lengths declared within the
+/// prefix exceeds the amount of the remaining data, so decoding the prefix
+/// never allocates buffers for data which are not present.
+///
+/// The scan stops on the first truncated length prefix, key or value, leaving
+/// reporting of the error to the decoder itself: if the decoder needs the data
+/// beyond the prefix the input is truncated, otherwise it is trailing data.
+fn framed_len(data: &[u8]) -> usize {
+    let mut pos = 0usize;
+    loop {
+        let rest = &data[pos..];
+        let (key_len, consumed) = match consensus::encode::deserialize_partial::<VarInt>(rest) {
+            Ok((VarInt(len), consumed)) => (len, consumed),
+            Err(_) => return pos,
+        };
+        // Map separator
+        if key_len == 0 {
+            pos += consumed;
+            continue;
+        }
+        let rest = &rest[consumed..];
+        if key_len > rest.len() as u64 {
+            return pos;
+        }
+        let rest = &rest[key_len as usize..];
+        let (value_len, value_consumed) =
+            match consensus::encode::deserialize_partial::<VarInt>(rest) {
+                Ok((VarInt(len), consumed)) => (len, consumed),
+                Err(_) => return pos,
+            };
+        if value_len > (rest.len() - value_consumed) as u64 {
+            return pos;
+        }
+        pos += consumed + key_len as usize + value_consumed + value_len as usize;
+    }
 }
 
 impl Deserialize for Psbt {
     fn deserialize(bytes: &[u8]) -> Result<Self, consensus::encode::Error> {
-        consensus::deserialize::<PsbtV0>(bytes).map(Psbt::from)
+        Psbt::decode(bytes).map_err(consensus::encode::Error::from)
     }
 }
 
@@ -315,6 +1096,7 @@ impl FromStr for Psbt {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{ProprietaryKeyDescriptor, ProprietaryKeyError};
 
     #[test]
     fn psbt_bip174_serialization() {
@@ -343,4 +1125,1153 @@ mod test {
         assert_eq!(psbt, psbt_prime);
         assert_eq!(hex, hex_prime);
     }
+
+    #[test]
+    fn proprietary_key_canonical_order() {
+        fn prop_key(prefix: &[u8], subtype: u8, key: &[u8]) -> raw::ProprietaryKey {
+            raw::ProprietaryKey {
+                prefix: prefix.to_vec(),
+                subtype,
+                key: key.to_vec(),
+            }
+        }
+
+        let expected = vec![
+            prop_key(b"AAA", 0, b""),
+            prop_key(b"AAA", 0, b"\x01"),
+            prop_key(b"AAA", 1, b""),
+            prop_key(b"AAA", 1, b"\x00"),
+            prop_key(b"BB", 0, b"\xFF"),
+            prop_key(b"BBB", 0, b""),
+        ];
+
+        let mut psbt = Psbt::default();
+        for (no, key) in expected.iter().rev().enumerate() {
+            psbt.proprietary.insert(key.clone(), vec![no as u8]);
+        }
+        assert_eq!(
+            psbt.proprietary.keys().cloned().collect::<Vec<_>>(),
+            expected
+        );
+
+        let serialized = psbt.serialize();
+        let positions = expected
+            .iter()
+            .map(|key| {
+                let needle = consensus::serialize(&key.to_key());
+                serialized
+                    .windows(needle.len())
+                    .position(|window| window == needle.as_slice())
+                    .expect("proprietary key must be present in serialized PSBT")
+            })
+            .collect::<Vec<_>>();
+        let mut sorted = positions.clone();
+        sorted.sort_unstable();
+        assert_eq!(positions, sorted);
+
+        let psbt_prime = Psbt::deserialize(&serialized).unwrap();
+        assert_eq!(psbt_prime.serialize(), serialized);
+    }
+
+    #[test]
+    fn join() {
+        use bitcoin::{
+            EcdsaSig, OutPoint, PublicKey, SchnorrSig, SchnorrSighashType, Script, TxIn, TxOut,
+        };
+
+        let signature = secp256k1::ecdsa::Signature::from_compact(&[1u8; 64]).unwrap();
+        let ecdsa_sig = |hash_ty| EcdsaSig {
+            sig: signature,
+            hash_ty,
+        };
+        let pubkey = |key: &str| PublicKey::from_str(key).unwrap();
+        let pk1 = pubkey("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+        let pk2 = pubkey("02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5");
+        let psbt = |vout: u32, value: u64| {
+            let txin = TxIn {
+                previous_output: OutPoint::new(Txid::default(), vout),
+                ..TxIn::default()
+            };
+            let txout = TxOut {
+                value,
+                script_pubkey: Script::from_str("0014d0c59903c5bac2868760e90fd521a4665aa76520")
+                    .unwrap(),
+            };
+            Psbt {
+                inputs: vec![Input::new(0, txin).unwrap()],
+                outputs: vec![Output::new(0, txout).unwrap()],
+                ..Psbt::default()
+            }
+        };
+
+        let mut first = psbt(0, 1000);
+        first.inputs[0].partial_sigs = bmap! {
+            pk1 => ecdsa_sig(EcdsaSighashType::All),
+            pk2 => ecdsa_sig(EcdsaSighashType::SinglePlusAnyoneCanPay)
+        };
+        let mut second = psbt(1, 2000);
+        second.inputs[0].partial_sigs = bmap! {
+            pk1 => ecdsa_sig(EcdsaSighashType::NonePlusAnyoneCanPay),
+            pk2 => ecdsa_sig(EcdsaSighashType::AllPlusAnyoneCanPay)
+        };
+        let tap_key_sig = SchnorrSig {
+            sig: secp256k1::schnorr::Signature::from_slice(&[1u8; 64]).unwrap(),
+            hash_ty: SchnorrSighashType::SinglePlusAnyoneCanPay,
+        };
+        second.inputs[0].tap_key_sig = Some(tap_key_sig);
+
+        let mut joined = first.clone();
+        assert_eq!(joined.join(second.clone()), Ok(vec![0, 1]));
+        assert_eq!(joined.inputs.len(), 2);
+        assert_eq!(joined.outputs.len(), 2);
+        assert_eq!(joined.inputs[1].index(), 1);
+        assert_eq!(joined.outputs[1].index(), 1);
+        assert_eq!(
+            joined.inputs[1].previous_outpoint,
+            second.inputs[0].previous_outpoint
+        );
+        assert_eq!(joined.outputs[1].amount, 2000);
+        assert_eq!(
+            joined.inputs[0].partial_sigs,
+            bmap! { pk2 => ecdsa_sig(EcdsaSighashType::SinglePlusAnyoneCanPay) }
+        );
+        assert_eq!(
+            joined.inputs[1].partial_sigs,
+            bmap! { pk1 => ecdsa_sig(EcdsaSighashType::NonePlusAnyoneCanPay) }
+        );
+        assert_eq!(joined.inputs[1].tap_key_sig, Some(tap_key_sig));
+        let joined = Psbt::deserialize(&joined.serialize()).unwrap();
+        assert_eq!(joined.inputs.len(), 2);
+        assert_eq!(joined.outputs.len(), 2);
+
+        let mut offset = Psbt {
+            outputs: vec![],
+            ..first.clone()
+        };
+        offset.inputs[0].partial_sigs.clear();
+        assert_eq!(offset.join(second.clone()), Ok(vec![1]));
+        assert_eq!(offset.inputs[1].tap_key_sig, None);
+
+        let mut duplicate = first.clone();
+        assert_eq!(
+            duplicate.join(psbt(0, 5000)),
+            Err(JoinError::DuplicateOutpoint(OutPoint::new(
+                Txid::default(),
+                0
+            )))
+        );
+        assert_eq!(duplicate, first);
+        let mut version = second.clone();
+        version.tx_version = 1;
+        assert_eq!(
+            first.clone().join(version),
+            Err(JoinError::TxVersionMismatch(2, 1))
+        );
+
+        // Lock time of the joined transaction differs from the original one
+        let mut locked = psbt(2, 3000);
+        locked.fallback_locktime = Some(LockTime::from_height(100).unwrap());
+        locked.inputs[0].partial_sigs = bmap! {
+            pk1 => ecdsa_sig(EcdsaSighashType::NonePlusAnyoneCanPay)
+        };
+        let mut joined = first.clone();
+        assert_eq!(joined.join(locked.clone()), Ok(vec![0]));
+        assert!(joined.inputs[0].partial_sigs.is_empty());
+        assert_eq!(joined.inputs[1].partial_sigs, locked.inputs[0].partial_sigs);
+        let mut joined = locked;
+        assert_eq!(joined.join(second), Ok(vec![1]));
+        assert!(joined.inputs[1].partial_sigs.is_empty());
+        assert_eq!(joined.inputs[1].tap_key_sig, None);
+    }
+
+    #[test]
+    fn enumerate_inputs_outputs() {
+        for version in [PsbtVersion::V0, PsbtVersion::V2] {
+            let mut psbt = v2_roundtrip_psbt(2);
+            psbt.psbt_version = version;
+            let psbt = Psbt::deserialize(&psbt.serialize()).unwrap();
+            let tx = psbt.to_unsigned_tx();
+
+            let inputs = psbt.enumerate_inputs().collect::<Vec<_>>();
+            assert_eq!(inputs.len(), 2);
+            for ((index, outpoint, input), txin) in inputs.into_iter().zip(&tx.input) {
+                assert_eq!(outpoint, txin.previous_output);
+                assert_eq!(input.index(), index);
+            }
+
+            let outputs = psbt.enumerate_outputs().collect::<Vec<_>>();
+            assert_eq!(outputs.len(), 2);
+            for (index, script, amount, output) in outputs {
+                assert_eq!(script, &tx.output[index].script_pubkey);
+                assert_eq!(amount, tx.output[index].value);
+                assert_eq!(output.index(), index);
+            }
+        }
+    }
+
+    #[test]
+    fn check_dust() {
+        use bitcoin::{Script, TxOut};
+
+        let output = |value: u64, script: &str| {
+            Output::new(0, TxOut {
+                value,
+                script_pubkey: Script::from_str(script).unwrap(),
+            })
+            .unwrap()
+        };
+        let p2wpkh = "0014d0c59903c5bac2868760e90fd521a4665aa76520";
+        let p2tr = "51200f0c8db753acbd17343a39c2f3f4e35e4be6da749f9e35137ab220e7b238a667";
+        let p2pkh = "76a914d0c59903c5bac2868760e90fd521a4665aa7652088ac";
+        let p2sh = "a9143545e6e33b832c47050f24d3eeb93c9c03948bc787";
+        let op_return = "6a0401020304";
+
+        assert_eq!(output(0, p2wpkh).dust_threshold(3.0), 294);
+        assert_eq!(output(0, p2tr).dust_threshold(3.0), 330);
+        assert_eq!(output(0, p2pkh).dust_threshold(3.0), 546);
+        assert_eq!(output(0, p2sh).dust_threshold(3.0), 540);
+        assert_eq!(output(0, op_return).dust_threshold(3.0), 0);
+        assert_eq!(output(0, p2wpkh).dust_threshold(1.0), 98);
+
+        let psbt = Psbt {
+            outputs: vec![
+                output(100, p2wpkh),
+                output(1000, p2wpkh),
+                output(294, p2wpkh),
+                output(329, p2tr),
+                output(545, p2pkh),
+                output(0, op_return),
+            ],
+            ..Psbt::default()
+        };
+        assert_eq!(psbt.check_dust(3.0), vec![0, 3, 4]);
+        assert_eq!(psbt.check_dust(10.0), vec![0, 2, 3, 4]);
+        assert!(psbt.check_dust(0.0).is_empty());
+    }
+
+    #[test]
+    fn reused_scripts() {
+        use bitcoin::{Script, TxOut};
+
+        let mut psbt = v2_roundtrip_psbt(2);
+        assert!(psbt.reused_output_scripts().is_empty());
+        assert!(psbt.outputs_reusing_input_scripts().is_empty());
+
+        let output = |script: &Script| {
+            Output::new(0, TxOut {
+                value: 1000,
+                script_pubkey: script.clone(),
+            })
+            .unwrap()
+        };
+        let output0 = psbt.outputs[0].script.clone();
+        let prevout0 = psbt.inputs[0].witness_utxo.clone().unwrap().script_pubkey;
+        let prevout1 = psbt.inputs[1].witness_utxo.clone().unwrap().script_pubkey;
+        psbt.outputs.push(output(&prevout1));
+        psbt.outputs.push(output(&output0));
+        psbt.outputs.push(output(&output0));
+        psbt.outputs.push(output(&prevout1));
+
+        assert_eq!(psbt.reused_output_scripts(), vec![
+            (0, 3),
+            (0, 4),
+            (2, 5),
+            (3, 4)
+        ]);
+        assert_eq!(psbt.outputs_reusing_input_scripts(), vec![(1, 2), (1, 5)]);
+
+        psbt.outputs.push(output(&prevout0));
+        psbt.inputs[1].witness_utxo = None;
+        psbt.inputs[1].non_witness_utxo = None;
+        assert_eq!(psbt.outputs_reusing_input_scripts(), vec![(0, 6)]);
+    }
+
+    #[test]
+    fn strip_attach_utxos() {
+        for version in [PsbtVersion::V0, PsbtVersion::V2] {
+            let mut original = v2_roundtrip_psbt(2);
+            original.psbt_version = version;
+            let prev_tx = original.inputs[1].non_witness_utxo.clone().unwrap();
+            original.inputs[0].non_witness_utxo = Some(prev_tx.clone());
+
+            let mut psbt = original.clone();
+            let stripped = psbt.strip_utxos();
+            assert_eq!(stripped, bmap! { prev_tx.txid() => prev_tx.clone() });
+            assert!(psbt
+                .inputs
+                .iter()
+                .all(|input| input.non_witness_utxo.is_none()));
+            assert_eq!(psbt.inputs[1].witness_utxo, original.inputs[1].witness_utxo);
+            assert!(psbt.serialize().len() < original.serialize().len());
+            assert!(psbt.strip_utxos().is_empty());
+
+            assert_eq!(psbt.attach_utxos(&BTreeMap::new()), Ok(0));
+            let mut restored = psbt.clone();
+            assert_eq!(restored.attach_utxos(&stripped), Ok(2));
+            assert_eq!(restored, original);
+            assert_eq!(restored.serialize(), original.serialize());
+
+            psbt.inputs[0].previous_outpoint.vout = 7;
+            assert_eq!(
+                psbt.attach_utxos(&stripped),
+                Err(InputMatchError::UnmatchedInputNumber(7))
+            );
+        }
+    }
+
+    #[test]
+    fn network_tx_hex() {
+        use bitcoin::Witness;
+
+        let mut psbt = v2_roundtrip_psbt(2);
+        assert_eq!(
+            psbt.to_network_tx_hex(),
+            Err(FinalizedTxError::NotFinalized(0))
+        );
+        psbt.inputs[0].final_script_witness = Some(Witness::from_vec(vec![vec![0x01; 72]]));
+        assert_eq!(
+            psbt.to_network_tx_hex(),
+            Err(FinalizedTxError::NotFinalized(1))
+        );
+        psbt.inputs[1].final_script_sig = Some(Script::from(vec![0x51]));
+
+        let hex = psbt.to_network_tx_hex().unwrap();
+        let tx: Transaction = consensus::deserialize(&Vec::from_hex(&hex).unwrap()).unwrap();
+        assert_eq!(tx, psbt.extract_signed_tx());
+        assert_eq!(tx.input[0].witness.to_vec(), vec![vec![0x01; 72]]);
+        assert_eq!(tx.input[1].script_sig, Script::from(vec![0x51]));
+    }
+
+    #[test]
+    fn decode_errors() {
+        for version in [PsbtVersion::V0, PsbtVersion::V2] {
+            let psbt = Psbt {
+                psbt_version: version,
+                ..v2_roundtrip_psbt(2)
+            };
+            let data = psbt.serialize();
+            assert_eq!(Psbt::decode(&data).unwrap(), psbt);
+
+            assert!(matches!(
+                Psbt::decode(&data[..data.len() - 3]),
+                Err(DecodeError::TruncatedInput)
+            ));
+            assert!(matches!(
+                Psbt::decode(&data[..3]),
+                Err(DecodeError::TruncatedInput)
+            ));
+            assert!(matches!(
+                Psbt::decode(&[]),
+                Err(DecodeError::TruncatedInput)
+            ));
+
+            let mut trailing = data.clone();
+            trailing.extend([0u8; 2]);
+            assert!(matches!(
+                Psbt::decode(&trailing),
+                Err(DecodeError::TrailingData(2))
+            ));
+            assert!(Psbt::deserialize(&trailing).is_err());
+
+            // Garbage with lengths exceeding the data is still trailing data
+            let mut garbage = data.clone();
+            garbage.extend([0xfd, 0xff, 0xff, 0x01]);
+            assert!(matches!(
+                Psbt::decode(&garbage),
+                Err(DecodeError::TrailingData(4))
+            ));
+        }
+
+        let tx = consensus::serialize(&v2_roundtrip_psbt(2).to_unsigned_tx());
+        assert!(matches!(Psbt::decode(&tx), Err(DecodeError::BadMagic)));
+        assert!(matches!(
+            Psbt::decode(b"psbt\x00"),
+            Err(DecodeError::BadMagic)
+        ));
+
+        // PSBT_GLOBAL_VERSION key with value 3 and empty map separator
+        let data = Vec::from_hex("70736274ff01fb040300000000").unwrap();
+        assert!(matches!(
+            Psbt::decode(&data),
+            Err(DecodeError::UnsupportedVersion(3))
+        ));
+        assert!(matches!(
+            Psbt::decode(&Vec::from_hex("70736274ff01fb040200000000").unwrap()),
+            Err(DecodeError::Invalid(_))
+        ));
+
+        // Transaction versions out of `i32` range are rejected only by
+        // validation
+        let psbt = Psbt::decode(&v2_roundtrip_psbt(0x8000_0000).serialize()).unwrap();
+        assert_eq!(psbt.tx_version, 0x8000_0000);
+        assert_eq!(
+            psbt.validate(),
+            Err(ValidationError::TxVersionOutOfRange(0x8000_0000))
+        );
+    }
+
+    #[test]
+    fn rbf_signaling() {
+        let mut tx = v2_roundtrip_psbt(2).to_unsigned_tx();
+        for txin in &mut tx.input {
+            txin.sequence = 0xFFFF_FFFF;
+        }
+        for version in [PsbtVersion::V0, PsbtVersion::V2] {
+            let psbt = Psbt::with(tx.clone(), version).unwrap();
+            let psbt = Psbt::decode(&psbt.serialize()).unwrap();
+            assert_eq!(psbt.inputs[0].sequence(), 0xFFFF_FFFF);
+            assert!(!psbt.is_rbf_signaling());
+        }
+
+        tx.input[1].sequence = 0xFFFF_FFFD;
+        for version in [PsbtVersion::V0, PsbtVersion::V2] {
+            let psbt = Psbt::with(tx.clone(), version).unwrap();
+            let psbt = Psbt::decode(&psbt.serialize()).unwrap();
+            assert_eq!(psbt.inputs[1].sequence(), 0xFFFF_FFFD);
+            assert!(psbt.is_rbf_signaling());
+        }
+    }
+
+    #[test]
+    fn matches_tx() {
+        let psbt = v2_roundtrip_psbt(2);
+        let mut tx = psbt.to_unsigned_tx();
+        assert!(psbt.matches_tx(&tx));
+
+        tx.input[0].script_sig = Script::from(vec![0u8; 72]);
+        tx.input[1].witness = bitcoin::Witness::from_vec(vec![vec![0u8; 64]]);
+        assert!(psbt.matches_tx(&tx));
+
+        let mut tampered = tx.clone();
+        tampered.output[0].value -= 1;
+        assert!(!psbt.matches_tx(&tampered));
+
+        let mut tampered = tx.clone();
+        tampered.output[0].script_pubkey = Script::new_op_return(&[]);
+        assert!(!psbt.matches_tx(&tampered));
+
+        let mut tampered = tx.clone();
+        tampered.input[1].sequence ^= 1;
+        assert!(!psbt.matches_tx(&tampered));
+
+        let mut tampered = tx;
+        tampered.input.pop();
+        assert!(!psbt.matches_tx(&tampered));
+    }
+
+    #[test]
+    fn unsigned_tx_bytes() {
+        let mut psbt = v2_roundtrip_psbt(2);
+        psbt.inputs[0].final_script_sig = Some(Script::from(vec![0u8; 72]));
+        let data = psbt.unsigned_tx_bytes();
+        let tx = consensus::deserialize::<Transaction>(&data).unwrap();
+        assert_eq!(tx, psbt.to_unsigned_tx());
+        assert!(tx.input[0].script_sig.is_empty());
+        assert_eq!(tx.txid(), psbt.to_txid());
+        assert_eq!(
+            data,
+            consensus::serialize(&PsbtV0::from(psbt.clone()).unsigned_tx)
+        );
+
+        psbt.psbt_version = PsbtVersion::V0;
+        assert_eq!(psbt.unsigned_tx_bytes(), data);
+    }
+
+    #[test]
+    fn amount_out_of_range() {
+        let max_money = Amount::MAX_MONEY.as_sat();
+        let psbt = v2_roundtrip_psbt(2);
+        assert_eq!(psbt.validate(), Ok(()));
+
+        let mut tx = psbt.to_unsigned_tx();
+        tx.output[0].value = max_money + 1;
+        assert_eq!(
+            Psbt::with(tx.clone(), PsbtVersion::V2),
+            Err(TxError::Txout(TxoutError::AmountOutOfRange(
+                0,
+                max_money + 1
+            )))
+        );
+        let mut v0 = PsbtV0::from(psbt.clone());
+        v0.unsigned_tx = tx;
+        let data = consensus::serialize(&v0);
+        assert!(matches!(Psbt::decode(&data), Err(DecodeError::Invalid(_))));
+
+        let mut invalid = psbt.clone();
+        invalid.outputs[0].amount = max_money + 1;
+        assert_eq!(
+            invalid.validate(),
+            Err(ValidationError::AmountOutOfRange(0, max_money + 1))
+        );
+        assert!(matches!(
+            Psbt::decode(&invalid.serialize()),
+            Err(DecodeError::Invalid(_))
+        ));
+
+        let mut invalid = psbt.clone();
+        invalid.outputs[1].amount = max_money;
+        assert_eq!(
+            invalid.validate(),
+            Err(ValidationError::TotalAmountOutOfRange)
+        );
+
+        let mut invalid = psbt;
+        invalid.outputs[0].amount = u64::MAX;
+        assert_eq!(
+            invalid.clone().combine(invalid),
+            Err(CombineError::Txout(TxoutError::AmountOutOfRange(
+                0,
+                u64::MAX
+            )))
+        );
+    }
+
+    #[test]
+    fn decode_oversized_lengths() {
+        // Global map key with a declared length of 4 MB
+        let data = Vec::from_hex("70736274fffe00004000").unwrap();
+        assert!(matches!(
+            Psbt::decode(&data),
+            Err(DecodeError::TruncatedInput)
+        ));
+
+        // PSBT_GLOBAL_VERSION key with a declared value length of 2^64 - 1
+        let data = Vec::from_hex("70736274ff01fbffffffffffffffffff").unwrap();
+        assert!(matches!(
+            Psbt::decode(&data),
+            Err(DecodeError::TruncatedInput)
+        ));
+
+        // Valid value length followed by an oversized key in the next pair
+        let data = Vec::from_hex("70736274ff01fb0402000000fdffff00").unwrap();
+        assert!(matches!(
+            Psbt::decode(&data),
+            Err(DecodeError::TruncatedInput)
+        ));
+
+        // PSBT_GLOBAL_INPUT_COUNT claiming 2^32 inputs with no input maps
+        let psbt = v2_roundtrip_psbt(2);
+        let mut data = psbt.serialize();
+        let count = Vec::from_hex("01040102").unwrap();
+        let pos = data.windows(4).position(|w| w == &count[..]).unwrap();
+        data.splice(
+            pos + 2..pos + 4,
+            Vec::from_hex("09ff0000000001000000").unwrap(),
+        );
+        assert!(matches!(
+            Psbt::decode(&data),
+            Err(DecodeError::TruncatedInput)
+        ));
+
+        // Input and output counts of 2^64 - 1 and 1 overflowing their sum
+        let data = Vec::from_hex(
+            "70736274ff01fb040200000001020402000000010409ffffffffffffffffff0105010100",
+        )
+        .unwrap();
+        assert!(matches!(
+            Psbt::decode(&data),
+            Err(DecodeError::TruncatedInput)
+        ));
+    }
+
+    #[test]
+    fn from_reader_limited() {
+        let psbt = v2_roundtrip_psbt(2);
+        let data = psbt.serialize();
+        assert_eq!(
+            Psbt::from_reader_limited(&data[..], data.len()).unwrap(),
+            psbt
+        );
+        assert_eq!(
+            Psbt::from_reader_limited(&data[..], usize::MAX).unwrap(),
+            psbt
+        );
+        assert!(matches!(
+            Psbt::from_reader_limited(&data[..], data.len() - 1),
+            Err(DecodeError::SizeLimitExceeded(len)) if len == data.len() - 1
+        ));
+
+        // Endless stream is cut at the limit
+        let stream = (&data[..]).chain(std::io::repeat(0xFF));
+        assert!(matches!(
+            Psbt::from_reader_limited(stream, 1_000_000),
+            Err(DecodeError::SizeLimitExceeded(1_000_000))
+        ));
+
+        // Value length of 2^64 - 1 declared within the limit
+        let data = Vec::from_hex("70736274ff01fbffffffffffffffffff").unwrap();
+        assert!(matches!(
+            Psbt::from_reader_limited(&data[..], 1_000),
+            Err(DecodeError::TruncatedInput)
+        ));
+    }
+
+    fn v2_roundtrip_psbt(tx_version: u32) -> Psbt {
+        use bitcoin::{OutPoint, Script, TxIn, TxOut};
+
+        let prev_tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn::default()],
+            output: vec![
+                TxOut {
+                    value: 2_100_000_000_000_000,
+                    script_pubkey: Script::from_str("0014d0c59903c5bac2868760e90fd521a4665aa76520")
+                        .unwrap(),
+                },
+                TxOut {
+                    value: 1,
+                    script_pubkey: Script::from_str(
+                        "a9143545e6e33b832c47050f24d3eeb93c9c03948bc787",
+                    )
+                    .unwrap(),
+                },
+            ],
+        };
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![
+                TxIn {
+                    previous_output: OutPoint::new(prev_tx.txid(), 0),
+                    ..TxIn::default()
+                },
+                TxIn {
+                    previous_output: OutPoint::new(prev_tx.txid(), 1),
+                    sequence: 0xFFFF_FFFD,
+                    ..TxIn::default()
+                },
+            ],
+            output: vec![
+                TxOut {
+                    value: Amount::MAX_MONEY.as_sat(),
+                    script_pubkey: Script::from_str(
+                        "51200f0c8db753acbd17343a39c2f3f4e35e4be6da749f9e35137ab220e7b238a667",
+                    )
+                    .unwrap(),
+                },
+                TxOut {
+                    value: 0,
+                    script_pubkey: Script::from_str("6a").unwrap(),
+                },
+            ],
+        };
+
+        let mut psbt = Psbt::with(tx, PsbtVersion::V2).unwrap();
+        psbt.tx_version = tx_version;
+        psbt.inputs[0].witness_utxo = Some(prev_tx.output[0].clone());
+        psbt.inputs[1].non_witness_utxo = Some(prev_tx.clone());
+        psbt.inputs[1].witness_utxo = Some(prev_tx.output[1].clone());
+        psbt
+    }
+
+    #[test]
+    fn v0_roundtrip_preserves_taproot_fields() {
+        use bitcoin::hashes::Hash;
+        use bitcoin::psbt::TapTree;
+        use bitcoin::schnorr::SchnorrSig;
+        use bitcoin::util::taproot::{
+            ControlBlock, LeafVersion, TapBranchHash, TapLeafHash, TaprootBuilder,
+        };
+        use bitcoin::{SchnorrSighashType, Script, XOnlyPublicKey};
+
+        let internal_key = XOnlyPublicKey::from_str(
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        let leaf_script = Script::from_str("51").unwrap();
+        let leaf_hash = TapLeafHash::from_script(&leaf_script, LeafVersion::TapScript);
+        let sig = SchnorrSig {
+            sig: secp256k1::schnorr::Signature::from_slice(&[1u8; 64]).unwrap(),
+            hash_ty: SchnorrSighashType::AllPlusAnyoneCanPay,
+        };
+        let mut control_block = vec![0xc0];
+        control_block.extend(internal_key.serialize());
+        let control_block = ControlBlock::from_slice(&control_block).unwrap();
+        let source = (
+            Fingerprint::from_str("73c5da0a").unwrap(),
+            DerivationPath::from_str("m/86'/0'/0'/0/0").unwrap(),
+        );
+        let tap_tree = TapTree::from_builder(
+            TaprootBuilder::new()
+                .add_leaf(0, leaf_script.clone())
+                .unwrap(),
+        )
+        .unwrap();
+
+        let mut psbt = v2_roundtrip_psbt(2);
+        psbt.psbt_version = PsbtVersion::V0;
+        let input = &mut psbt.inputs[0];
+        input.tap_key_sig = Some(sig);
+        input.tap_script_sigs = bmap! { (internal_key, leaf_hash) => sig };
+        input.tap_scripts = bmap! {
+            control_block => (leaf_script, LeafVersion::TapScript)
+        };
+        input.tap_key_origins = bmap! { internal_key => (vec![leaf_hash], source.clone()) };
+        input.tap_internal_key = Some(internal_key);
+        input.tap_merkle_root = Some(TapBranchHash::from_inner(leaf_hash.into_inner()));
+        let output = &mut psbt.outputs[0];
+        output.tap_internal_key = Some(internal_key);
+        output.tap_tree = Some(tap_tree);
+        output.tap_key_origins = bmap! { internal_key => (vec![leaf_hash], source) };
+
+        let psbt_prime = Psbt::try_from(PsbtV0::from(psbt.clone())).unwrap();
+        assert_eq!(psbt_prime.inputs, psbt.inputs);
+        assert_eq!(psbt_prime.outputs, psbt.outputs);
+
+        let psbt_prime = Psbt::deserialize(&psbt.serialize()).unwrap();
+        assert_eq!(psbt_prime.psbt_version, PsbtVersion::V0);
+        assert_eq!(psbt_prime.inputs, psbt.inputs);
+        assert_eq!(psbt_prime.outputs, psbt.outputs);
+    }
+
+    #[test]
+    fn v0_v2_roundtrip_preserves_amounts() {
+        for tx_version in [1u32, 2, 0x7FFF_FFFF, 0x8000_0000, u32::MAX] {
+            let psbt = v2_roundtrip_psbt(tx_version);
+            let fee = psbt.fee();
+
+            let v0 = PsbtV0::from(psbt.clone());
+            assert_eq!(v0.unsigned_tx, psbt.to_unsigned_tx());
+            assert_eq!(v0.unsigned_tx.output[0].value, Amount::MAX_MONEY.as_sat());
+
+            let mut psbt_prime = Psbt::try_from(v0).unwrap();
+            assert_eq!(psbt_prime.psbt_version, PsbtVersion::V0);
+            assert_eq!(psbt_prime.tx_version, tx_version);
+            assert_eq!(psbt_prime.fee(), fee);
+            for (input, input_prime) in psbt.inputs.iter().zip(&psbt_prime.inputs) {
+                assert_eq!(input.input_prevout(), input_prime.input_prevout());
+                assert_eq!(input.witness_utxo, input_prime.witness_utxo);
+                assert_eq!(input.non_witness_utxo, input_prime.non_witness_utxo);
+                assert_eq!(input.sequence_number, input_prime.sequence_number);
+            }
+            for (output, output_prime) in psbt.outputs.iter().zip(&psbt_prime.outputs) {
+                assert_eq!(output.amount, output_prime.amount);
+                assert_eq!(output.script, output_prime.script);
+            }
+
+            psbt_prime.psbt_version = PsbtVersion::V2;
+            assert_eq!(psbt_prime, psbt);
+
+            let deserialized = Psbt::deserialize(&psbt.serialize()).unwrap();
+            assert_eq!(deserialized.to_unsigned_tx(), psbt.to_unsigned_tx());
+            assert_eq!(deserialized.inputs, psbt.inputs);
+            assert_eq!(deserialized.outputs, psbt.outputs);
+        }
+    }
+
+    #[test]
+    fn default_tx_version() {
+        let psbt = Psbt::default();
+        assert_eq!(psbt.psbt_version, PsbtVersion::V2);
+        assert_eq!(psbt.tx_version, 2);
+        assert_eq!(psbt.to_unsigned_tx().version, 2);
+
+        let mut tx = v2_roundtrip_psbt(2).into_unsigned_tx();
+        assert_eq!(
+            Psbt::with(tx.clone(), PsbtVersion::V2).unwrap().tx_version,
+            2
+        );
+        tx.version = 1;
+        assert_eq!(
+            Psbt::with(tx.clone(), PsbtVersion::V0).unwrap().tx_version,
+            1
+        );
+        for version in [0, -1, i32::MIN] {
+            tx.version = version;
+            assert_eq!(
+                Psbt::with(tx.clone(), PsbtVersion::V2),
+                Err(TxError::InvalidTxVersion(version))
+            );
+        }
+    }
+
+    #[test]
+    fn txid_stability() {
+        use bitcoin::{Script, Witness};
+        use descriptors::locks::SeqNo;
+
+        let psbt = v2_roundtrip_psbt(2);
+        let txid = psbt.to_txid();
+        assert_eq!(txid, psbt.clone().into_unsigned_tx().txid());
+
+        let mut v0 = Psbt::try_from(PsbtV0::from(psbt.clone())).unwrap();
+        v0.psbt_version = PsbtVersion::V0;
+        assert_eq!(v0.to_txid(), txid);
+        assert_eq!(PsbtV0::from(psbt.clone()).unsigned_tx.txid(), txid);
+        let v2 = Psbt::deserialize(&psbt.serialize()).unwrap();
+        assert_eq!(v2.to_txid(), txid);
+
+        let mut finalized = psbt.clone();
+        finalized.inputs[0].final_script_witness = Some(Witness::from_vec(vec![vec![0x01; 72]]));
+        finalized.inputs[1].final_script_sig =
+            Some(Script::from_str("160014d0c59903c5bac2868760e90fd521a4665aa76520").unwrap());
+        assert_eq!(finalized.to_txid(), txid);
+        assert_ne!(finalized.extract_signed_tx().txid(), txid);
+
+        let mut changed = psbt;
+        changed.inputs[0].sequence_number = Some(SeqNo::from_consensus(0));
+        assert_ne!(changed.to_txid(), txid);
+    }
+
+    #[test]
+    fn out_of_range_index() {
+        let mut psbt = v2_roundtrip_psbt(2);
+        let inputs = psbt.inputs.len();
+        let outputs = psbt.outputs.len();
+        let input_err = IndexError {
+            index: inputs,
+            len: inputs,
+        };
+        let output_err = IndexError {
+            index: outputs,
+            len: outputs,
+        };
+
+        assert!(psbt.input(inputs - 1).is_ok());
+        assert!(psbt.output(outputs - 1).is_ok());
+        assert_eq!(psbt.input(inputs), Err(input_err));
+        assert_eq!(psbt.input_mut(inputs), Err(input_err));
+        assert_eq!(psbt.output(outputs), Err(output_err));
+        assert_eq!(psbt.output_mut(outputs), Err(output_err));
+
+        let key =
+            ProprietaryKeyDescriptor::from_str(&format!("input({}) TEST(1)", inputs)).unwrap();
+        assert_eq!(
+            psbt.push_proprietary(key),
+            Err(ProprietaryKeyError::InputOutOfRange(inputs as u16, inputs))
+        );
+        let key =
+            ProprietaryKeyDescriptor::from_str(&format!("output({}) TEST(1)", outputs)).unwrap();
+        assert_eq!(
+            psbt.push_proprietary(key),
+            Err(ProprietaryKeyError::OutputOutOfRange(
+                outputs as u16,
+                outputs
+            ))
+        );
+        let key = ProprietaryKeyDescriptor::from_str("output(1) TEST(1) 01:02").unwrap();
+        psbt.push_proprietary(key.clone()).unwrap();
+        assert_eq!(
+            psbt.outputs[1].proprietary.get(&key.into()),
+            Some(&vec![2u8])
+        );
+    }
+
+    #[test]
+    fn map_insertion_order_equality() {
+        let entries = (0u8..8)
+            .map(|no| {
+                (
+                    raw::ProprietaryKey {
+                        prefix: b"TEST".to_vec(),
+                        subtype: no % 3,
+                        key: vec![no],
+                    },
+                    raw::Key {
+                        type_value: 0xF0 + no,
+                        key: vec![no; no as usize],
+                    },
+                    vec![no],
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut psbt1 = v2_roundtrip_psbt(2);
+        let mut psbt2 = psbt1.clone();
+        for (prop, unknown, value) in &entries {
+            psbt1.proprietary.insert(prop.clone(), value.clone());
+            psbt1.unknown.insert(unknown.clone(), value.clone());
+            psbt1.inputs[0]
+                .proprietary
+                .insert(prop.clone(), value.clone());
+            psbt1.outputs[1]
+                .unknown
+                .insert(unknown.clone(), value.clone());
+        }
+        for (prop, unknown, value) in entries.iter().rev() {
+            psbt2.outputs[1]
+                .unknown
+                .insert(unknown.clone(), value.clone());
+            psbt2.inputs[0]
+                .proprietary
+                .insert(prop.clone(), value.clone());
+            psbt2.unknown.insert(unknown.clone(), value.clone());
+            psbt2.proprietary.insert(prop.clone(), value.clone());
+        }
+
+        assert_eq!(psbt1, psbt2);
+        assert_eq!(psbt1.serialize(), psbt2.serialize());
+    }
+
+    #[test]
+    fn all_key_sources() {
+        use bitcoin::util::bip32::{DerivationPath, Fingerprint};
+
+        let secp = secp256k1::Secp256k1::new();
+        let xpub = ExtendedPubKey::from_str(
+            "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V",
+        )
+        .unwrap();
+        let fingerprint = Fingerprint::from_str("73c5da0a").unwrap();
+        let key_source = |path: &str| (fingerprint, DerivationPath::from_str(path).unwrap());
+        let derive = |path: &str| {
+            xpub.derive_pub(&secp, &DerivationPath::from_str(path).unwrap())
+                .unwrap()
+                .public_key
+        };
+
+        let mut psbt = v2_roundtrip_psbt(2);
+        psbt.xpub.insert(xpub, key_source("m/84'/0'/0'"));
+        psbt.inputs[0]
+            .bip32_derivation
+            .insert(derive("m/0/0"), key_source("m/84'/0'/0'/0/0"));
+        psbt.inputs[1]
+            .bip32_derivation
+            .insert(derive("m/0/1"), key_source("m/84'/0'/0'/0/1"));
+        psbt.outputs[0]
+            .bip32_derivation
+            .insert(derive("m/0/1"), key_source("m/84'/0'/0'/0/1"));
+        psbt.outputs[1]
+            .bip32_derivation
+            .insert(derive("m/1/0"), key_source("m/84'/0'/0'/1/0"));
+
+        assert_eq!(
+            psbt.all_key_sources(),
+            Ok(bmap! {
+                xpub.public_key => key_source("m/84'/0'/0'"),
+                derive("m/0/0") => key_source("m/84'/0'/0'/0/0"),
+                derive("m/0/1") => key_source("m/84'/0'/0'/0/1"),
+                derive("m/1/0") => key_source("m/84'/0'/0'/1/0")
+            })
+        );
+
+        // Taproot x-only keys are lifted to the keys with even Y coordinate
+        let xonly = secp256k1::XOnlyPublicKey::from(derive("m/0/2"));
+        psbt.outputs[0]
+            .tap_key_origins
+            .insert(xonly, (vec![], key_source("m/86'/0'/0'/0/2")));
+        let key_sources = psbt.all_key_sources().unwrap();
+        let mut even_key = vec![0x02];
+        even_key.extend(xonly.serialize().iter());
+        let even_key = secp256k1::PublicKey::from_slice(&even_key).unwrap();
+        assert_eq!(key_sources.len(), 5);
+        assert_eq!(key_sources[&even_key], key_source("m/86'/0'/0'/0/2"));
+        psbt.inputs[0]
+            .tap_key_origins
+            .insert(xonly, (vec![], key_source("m/86'/0'/0'/0/3")));
+        assert_eq!(psbt.all_key_sources(), Err(KeySourceConflict(even_key)));
+        psbt.inputs[0].tap_key_origins.clear();
+
+        psbt.outputs[1]
+            .bip32_derivation
+            .insert(derive("m/0/0"), key_source("m/84'/0'/0'/1/0"));
+        assert_eq!(
+            psbt.all_key_sources(),
+            Err(KeySourceConflict(derive("m/0/0")))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_validation() {
+        let psbt = v2_roundtrip_psbt(2);
+        let json = serde_json::to_value(&psbt).unwrap();
+        let decoded: Psbt = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(decoded, psbt);
+
+        let mut invalid = json.clone();
+        invalid["tx_version"] = serde_json::json!(u32::MAX);
+        let err = serde_json::from_value::<Psbt>(invalid).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            ValidationError::TxVersionOutOfRange(u32::MAX).to_string()
+        );
+
+        let mut invalid = json.clone();
+        invalid["inputs"][1]["index"] = serde_json::json!(0);
+        assert!(serde_json::from_value::<Psbt>(invalid).is_err());
+
+        let mut invalid = json;
+        invalid["xpub"] = serde_json::json!({ "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL": ["deadbeef", "m"] });
+        let err = serde_json::from_value::<Psbt>(invalid).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            ValidationError::XpubDepthMismatch {
+                depth: 4,
+                path_len: 0
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn rebase_keys() {
+        use bitcoin::util::bip32::DerivationPath;
+
+        let secp = secp256k1::Secp256k1::new();
+        let xpub = ExtendedPubKey::from_str(
+            "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V",
+        )
+        .unwrap();
+        let derive = |path: &str| {
+            xpub.derive_pub(&secp, &DerivationPath::from_str(path).unwrap())
+                .unwrap()
+                .public_key
+        };
+        let old = Fingerprint::from_str("73c5da0a").unwrap();
+        let new = Fingerprint::from_str("d34db33f").unwrap();
+        let other = Fingerprint::from_str("0badf00d").unwrap();
+        let path = |path: &str| DerivationPath::from_str(path).unwrap();
+
+        let mut psbt = v2_roundtrip_psbt(2);
+        psbt.xpub.insert(xpub, (old, path("m/84'/0'/0'")));
+        psbt.inputs[0]
+            .bip32_derivation
+            .insert(derive("m/0/0"), (old, path("m/84'/0'/0'/0/0")));
+        psbt.inputs[1]
+            .bip32_derivation
+            .insert(derive("m/0/1"), (other, path("m/84'/0'/1'/0/1")));
+        psbt.inputs[1].tap_key_origins.insert(
+            derive("m/0/2").into(),
+            (vec![], (old, path("m/86'/0'/0'/0/2"))),
+        );
+        psbt.outputs[0]
+            .bip32_derivation
+            .insert(derive("m/1/0"), (old, path("m/84'/0'/0'/1/0")));
+
+        let mut expected = psbt.clone();
+        expected.xpub.insert(xpub, (new, path("m/84'/0'/0'")));
+        expected.inputs[0]
+            .bip32_derivation
+            .insert(derive("m/0/0"), (new, path("m/84'/0'/0'/0/0")));
+        expected.inputs[1].tap_key_origins.insert(
+            derive("m/0/2").into(),
+            (vec![], (new, path("m/86'/0'/0'/0/2"))),
+        );
+        expected.outputs[0]
+            .bip32_derivation
+            .insert(derive("m/1/0"), (new, path("m/84'/0'/0'/1/0")));
+
+        psbt.rebase_keys(old, new);
+        assert_eq!(psbt, expected);
+        assert_eq!(
+            psbt.inputs[1].bip32_derivation[&derive("m/0/1")],
+            (other, path("m/84'/0'/1'/0/1"))
+        );
+    }
+
+    #[test]
+    fn prune_xpubs() {
+        use bitcoin::util::bip32::DerivationPath;
+
+        let secp = secp256k1::Secp256k1::new();
+        let xpub = ExtendedPubKey::from_str(
+            "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V",
+        )
+        .unwrap();
+        let fingerprint = Fingerprint::from_str("73c5da0a").unwrap();
+        let path = |path: &str| DerivationPath::from_str(path).unwrap();
+        let derive = |path: &str| {
+            xpub.derive_pub(&secp, &DerivationPath::from_str(path).unwrap())
+                .unwrap()
+        };
+        let unreferenced = derive("m/1");
+        let duplicate = ExtendedPubKey {
+            network: bitcoin::Network::Testnet,
+            ..xpub
+        };
+
+        let mut psbt = v2_roundtrip_psbt(2);
+        psbt.xpub.insert(xpub, (fingerprint, path("m/84'/0'/0'")));
+        psbt.xpub
+            .insert(duplicate, (fingerprint, path("m/84'/0'/0'")));
+        psbt.xpub
+            .insert(unreferenced, (fingerprint, path("m/84'/0'/1'")));
+        psbt.inputs[0].bip32_derivation.insert(
+            derive("m/0/0").public_key,
+            (fingerprint, path("m/84'/0'/0'/0/0")),
+        );
+
+        let mut expected = psbt.clone();
+        expected.xpub.remove(&duplicate);
+        expected.xpub.remove(&unreferenced);
+        psbt.prune_xpubs();
+        assert_eq!(psbt, expected);
+        assert_eq!(psbt.xpub.len(), 1);
+
+        psbt.inputs[0].bip32_derivation.clear();
+        psbt.prune_xpubs();
+        assert!(psbt.xpub.is_empty());
+    }
+
+    #[test]
+    fn set_global() {
+        let mut psbt = Psbt::default();
+        let key = |type_value: u8, key: Vec<u8>| raw::Key { type_value, key };
+
+        psbt.set_global(key(0x02, vec![]), 1u32.to_le_bytes().to_vec())
+            .unwrap();
+        assert_eq!(psbt.tx_version, 1);
+        psbt.set_global(key(0x03, vec![]), 500_000u32.to_le_bytes().to_vec())
+            .unwrap();
+        assert_eq!(
+            psbt.fallback_locktime,
+            Some(LockTime::from_consensus(500_000))
+        );
+        psbt.set_global(key(0xFB, vec![]), 0u32.to_le_bytes().to_vec())
+            .unwrap();
+        assert_eq!(psbt.psbt_version, PsbtVersion::V0);
+
+        let xpub = ExtendedPubKey::from_str(
+            "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V",
+        )
+        .unwrap();
+        let mut value = vec![0x73, 0xc5, 0xda, 0x0a];
+        for index in [0x80000054u32, 0x80000000, 0x80000000] {
+            value.extend(index.to_le_bytes());
+        }
+        psbt.set_global(key(0x01, xpub.encode().to_vec()), value)
+            .unwrap();
+        assert_eq!(
+            psbt.xpub[&xpub],
+            (
+                Fingerprint::from(&[0x73, 0xc5, 0xda, 0x0a][..]),
+                DerivationPath::from_str("m/84'/0'/0'").unwrap()
+            )
+        );
+
+        let mut proprietary = vec![3];
+        proprietary.extend(b"TST");
+        proprietary.extend([0x01, 0xAA]);
+        psbt.set_global(key(0xFC, proprietary), vec![0xFF]).unwrap();
+        assert_eq!(psbt.proprietary.len(), 1);
+        psbt.set_global(key(0xF0, vec![0x01]), vec![0x02]).unwrap();
+        assert_eq!(psbt.unknown[&key(0xF0, vec![0x01])], vec![0x02]);
+
+        assert_eq!(
+            psbt.set_global(key(0x00, vec![]), vec![]),
+            Err(GlobalKeyError::Structural(0x00))
+        );
+        assert_eq!(
+            psbt.set_global(key(0x02, vec![0x00]), vec![0; 4]),
+            Err(GlobalKeyError::NonEmptyKeyData(0x02))
+        );
+        assert_eq!(
+            psbt.set_global(key(0x02, vec![]), vec![0; 3]),
+            Err(GlobalKeyError::InvalidValue(0x02))
+        );
+        assert_eq!(
+            psbt.set_global(key(0xFB, vec![]), 1u32.to_le_bytes().to_vec()),
+            Err(GlobalKeyError::InvalidValue(0xFB))
+        );
+        assert_eq!(
+            psbt.set_global(key(0x01, vec![0x00; 10]), vec![0; 4]),
+            Err(GlobalKeyError::InvalidKeyData(0x01))
+        );
+        assert_eq!(psbt.tx_version, 1);
+        psbt.set_global(key(0x02, vec![]), u32::MAX.to_le_bytes().to_vec())
+            .unwrap();
+        assert_eq!(psbt.tx_version, u32::MAX);
+    }
 }
@@ -12,11 +12,13 @@
 // along with this software.
 // If not, see <https://opensource.org/licenses/Apache-2.0>.
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
+use bitcoin::blockdata::script::Instruction;
 use bitcoin::blockdata::transaction::NonStandardSighashType;
 use bitcoin::hashes::{hash160, ripemd160, sha256, sha256d};
-use bitcoin::psbt::PsbtSighashType;
+use bitcoin::psbt::{PsbtSighashType, TapTree};
+use bitcoin::secp256k1::{Secp256k1, Verification};
 use bitcoin::util::bip32::KeySource;
 use bitcoin::util::sighash;
 use bitcoin::util::taproot::{ControlBlock, LeafVersion, TapBranchHash, TapLeafHash};
@@ -24,12 +26,76 @@ use bitcoin::{
     secp256k1, EcdsaSig, EcdsaSighashType, OutPoint, PublicKey, SchnorrSig, SchnorrSighashType,
     Script, Transaction, TxIn, TxOut, Witness, XOnlyPublicKey,
 };
-use descriptors::locks::{LockHeight, LockTime, LockTimestamp, SeqNo};
+use bitcoin_hd::{
+    Bip43, DerivationAccount, DerivePatternError, DerivePublicKey, SegmentIndexes, UnhardenedIndex,
+    HARDENED_INDEX_BOUNDARY,
+};
+use descriptors::locks::{LockHeight, LockTime, LockTimestamp, SeqNo, TimeLockInterval};
+use descriptors::DescriptorClass;
 #[cfg(feature = "serde")]
 use serde_with::{hex::Hex, As, Same};
 
 use crate::v0::InputV0;
-use crate::{raw, InputMatchError, TxinError};
+use crate::{raw, InputMatchError, RelativeLockError, ScriptError, TxinError};
+
+const PSBT_IN_POR_COMMITMENT: u8 = 0x09;
+
+/// Derives key from the `account` at the `terminal` path and adds it to the
+/// taproot key derivation data together with hashes of the `tree` leaves whose
+/// scripts push the key. Leaf hashes are kept in lexicographic order and
+/// without duplicates, as required by BIP-371.
+pub(crate) fn insert_tap_key_origin<C: Verification>(
+    origins: &mut BTreeMap<XOnlyPublicKey, (Vec<TapLeafHash>, KeySource)>,
+    secp: &Secp256k1<C>,
+    account: &DerivationAccount,
+    terminal: impl AsRef<[UnhardenedIndex]>,
+    tree: Option<&TapTree>,
+) -> Result<XOnlyPublicKey, DerivePatternError> {
+    let (pubkey, key_source) = account.bip32_derivation(secp, terminal)?;
+    let pubkey = XOnlyPublicKey::from(pubkey);
+    let key = pubkey.serialize();
+    let (leaves, _) = origins.entry(pubkey).or_insert((vec![], key_source));
+    let mut hashes = leaves.drain(..).collect::<BTreeSet<_>>();
+    hashes.extend(
+        tree.into_iter()
+            .flat_map(TapTree::script_leaves)
+            .filter(|leaf| {
+                leaf.script()
+                    .instructions()
+                    .any(|instr| matches!(instr, Ok(Instruction::PushBytes(data)) if data == key))
+            })
+            .map(|leaf| leaf.leaf_hash()),
+    );
+    *leaves = hashes.into_iter().collect();
+    Ok(pubkey)
+}
+
+/// Moves BIP-127 proof-of-reserves commitment out of the unknown keys. Values
+/// which are not valid UTF-8 strings, as required by BIP-127, are left as
+/// unknown keys.
+fn take_por_commitment(unknown: &mut BTreeMap<raw::Key, Vec<u8>>) -> Option<String> {
+    let key = raw::Key {
+        type_value: PSBT_IN_POR_COMMITMENT,
+        key: vec![],
+    };
+    let commitment = String::from_utf8(unknown.get(&key)?.clone()).ok()?;
+    unknown.remove(&key);
+    Some(commitment)
+}
+
+/// Puts BIP-127 proof-of-reserves commitment back into the unknown keys. An
+/// existing raw proof-of-reserves entry is preserved and never overwritten.
+fn put_por_commitment(unknown: &mut BTreeMap<raw::Key, Vec<u8>>, commitment: Option<String>) {
+    if let Some(commitment) = commitment {
+        let key = raw::Key {
+            type_value: PSBT_IN_POR_COMMITMENT,
+            key: vec![],
+        };
+        unknown
+            .entry(key)
+            .or_insert_with(|| commitment.into_bytes());
+    }
+}
 
 // TODO: Do manual serde implementation to check the deserialized values
 #[derive(Clone, Eq, PartialEq, Debug, Default)]
@@ -97,8 +163,6 @@ pub struct Input {
     /// other scripts necessary for this input to pass validation.
     pub final_script_witness: Option<Witness>,
 
-    /// TODO: Proof of reserves commitment
-
     /// RIPEMD160 hash to preimage map.
     #[cfg_attr(feature = "serde", serde(with = "As::<BTreeMap<Same, Hex>>"))]
     pub ripemd160_preimages: BTreeMap<ripemd160::Hash, Vec<u8>>,
@@ -147,6 +211,10 @@ pub struct Input {
     /// Unknown key-value pairs for this input.
     #[cfg_attr(feature = "serde", serde(with = "As::<BTreeMap<Same, Hex>>"))]
     pub unknown: BTreeMap<raw::Key, Vec<u8>>,
+
+    /// The UTF-8 encoded commitment message string for the proof-of-reserves
+    /// (BIP-127).
+    pub por_commitment: Option<String>,
 }
 
 impl Input {
@@ -174,11 +242,12 @@ impl Input {
         Ok(input)
     }
 
-    pub fn with(index: usize, v0: InputV0, txin: TxIn) -> Self {
+    pub fn with(index: usize, mut v0: InputV0, txin: TxIn) -> Self {
         let sequence = match txin.sequence {
             u32::MAX => None,
             other => Some(other.into()),
         };
+        let por_commitment = take_por_commitment(&mut v0.unknown);
 
         Input {
             index,
@@ -207,9 +276,36 @@ impl Input {
             tap_merkle_root: v0.tap_merkle_root,
             proprietary: v0.proprietary,
             unknown: v0.unknown,
+            por_commitment,
         }
     }
 
+    /// Constructs input spending segwit output, for which only the spent
+    /// [`TxOut`] is known (and not the full previous transaction). The key
+    /// derivation information is filled from the provided `account` derived
+    /// at the `terminal` path.
+    ///
+    /// Since the signature hash for segwit inputs (BIP-143 and BIP-341) commits
+    /// only to the spent output amount and script, the resulting input can be
+    /// signed without `non_witness_utxo` being present.
+    pub fn with_witness_utxo<C: Verification>(
+        secp: &Secp256k1<C>,
+        index: usize,
+        outpoint: OutPoint,
+        txout: TxOut,
+        account: &DerivationAccount,
+        terminal: impl AsRef<[UnhardenedIndex]>,
+    ) -> Result<Self, DerivePatternError> {
+        let (pubkey, key_source) = account.bip32_derivation(secp, terminal)?;
+        Ok(Input {
+            index,
+            previous_outpoint: outpoint,
+            witness_utxo: Some(txout),
+            bip32_derivation: bmap! { pubkey => key_source },
+            ..Input::default()
+        })
+    }
+
     #[inline]
     pub fn index(&self) -> usize { self.index }
 
@@ -220,6 +316,182 @@ impl Input {
             .or_else(|| self.required_height_locktime.map(LockTime::from))
     }
 
+    /// Returns BIP32 key source (master key fingerprint and derivation path)
+    /// for the `pubkey`, if present in the input ECDSA key derivation data.
+    #[inline]
+    pub fn key_source(&self, pubkey: &secp256k1::PublicKey) -> Option<&KeySource> {
+        self.bip32_derivation.get(pubkey)
+    }
+
+    /// Returns hashes of the tapscript leaves in which the x-only `pubkey` is
+    /// used, together with its BIP32 key source, if present in the input
+    /// taproot key derivation data.
+    #[inline]
+    pub fn tap_key_source(
+        &self,
+        pubkey: &XOnlyPublicKey,
+    ) -> Option<&(Vec<TapLeafHash>, KeySource)> {
+        self.tap_key_origins.get(pubkey)
+    }
+
+    /// Adds taproot key derivation data for the key derived from the
+    /// `account` at the `terminal` path. The key is listed together with
+    /// hashes of the `tree` leaves using it, which hardware signers require
+    /// to sign script path spendings; without `tree` the key is treated as
+    /// used only in the key path. Leaf hashes already present for the key are
+    /// preserved.
+    ///
+    /// Returns the derived x-only public key.
+    pub fn push_tap_key_origin<C: Verification>(
+        &mut self,
+        secp: &Secp256k1<C>,
+        account: &DerivationAccount,
+        terminal: impl AsRef<[UnhardenedIndex]>,
+        tree: Option<&TapTree>,
+    ) -> Result<XOnlyPublicKey, DerivePatternError> {
+        insert_tap_key_origin(&mut self.tap_key_origins, secp, account, terminal, tree)
+    }
+
+    /// Classifies the way the input is spent, inferring it from the spent
+    /// output `scriptPubkey` and, for P2SH outputs, from the redeem script,
+    /// witness script or final witness, whichever is present.
+    ///
+    /// Returns `None` if the spent output is unknown, if it is a P2SH output
+    /// without information about the wrapped script, or if it is a witness
+    /// program of an unknown version.
+    pub fn spend_type(&self) -> Option<DescriptorClass> {
+        let script = &self.input_prevout().ok()?.script_pubkey;
+        if script.is_v1_p2tr() {
+            Some(DescriptorClass::TaprootC0)
+        } else if script.is_v0_p2wpkh() || script.is_v0_p2wsh() {
+            Some(DescriptorClass::SegwitV0)
+        } else if script.is_witness_program() {
+            None
+        } else if script.is_p2sh() {
+            match self.redeem_script {
+                Some(ref redeem_script)
+                    if redeem_script.is_v0_p2wpkh() || redeem_script.is_v0_p2wsh() =>
+                {
+                    Some(DescriptorClass::NestedV0)
+                }
+                Some(_) => Some(DescriptorClass::PreSegwit),
+                None if self.witness_script.is_some() || self.final_script_witness.is_some() => {
+                    Some(DescriptorClass::NestedV0)
+                }
+                None if self.final_script_sig.is_some() => Some(DescriptorClass::PreSegwit),
+                None => None,
+            }
+        } else {
+            Some(DescriptorClass::PreSegwit)
+        }
+    }
+
+    /// Detects whether the input spends an output paying to the key derived
+    /// from the `account` at some index not greater than `max_index`,
+    /// returning the matching index.
+    ///
+    /// The script type is detected from the spent output `scriptPubkey`:
+    /// P2PKH, P2WPKH, P2WPKH-in-P2SH and P2TR are supported. For P2TR the
+    /// derived key is used as a taproot internal key, tweaked with the input
+    /// `tap_merkle_root`, if present.
+    ///
+    /// For accounts with a multipath terminal step (like `/<0;1>/*` or
+    /// `/{0-1}/*`) each of the branches is checked separately. Terminal paths
+    /// ending with two wildcards (`/*/*`) are checked with both `0` and `1`
+    /// values at the first of them, i.e. with receive and change indexes.
+    /// Keys which can't be derived at some index are skipped.
+    ///
+    /// Returns `None` if the spent output is unknown, has unsupported script
+    /// type or does not match any of the derived keys.
+    pub fn is_mine<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        account: &DerivationAccount,
+        max_index: u32,
+    ) -> Option<UnhardenedIndex> {
+        let script = &self.input_prevout().ok()?.script_pubkey;
+        let scheme = if script.is_p2pkh() {
+            Some(Bip43::Bip44)
+        } else if script.is_p2sh() {
+            Some(Bip43::Bip49)
+        } else if script.is_v0_p2wpkh() {
+            Some(Bip43::Bip84)
+        } else if script.is_v1_p2tr() {
+            None
+        } else {
+            return None;
+        };
+        let branches = account.split_multipath().ok()?;
+        for index in 0..=max_index.min(HARDENED_INDEX_BOUNDARY - 1) {
+            let index = UnhardenedIndex::from_index(index).expect("index is unhardened");
+            for branch in &branches {
+                let patterns = match branch.derive_pattern_len() {
+                    1 => vec![vec![index]],
+                    2 => vec![vec![UnhardenedIndex::zero(), index], vec![
+                        UnhardenedIndex::one(),
+                        index,
+                    ]],
+                    _ => continue,
+                };
+                for pat in patterns {
+                    let derived = match scheme {
+                        Some(scheme) => match branch.script_pubkey(secp, scheme, pat) {
+                            Ok(script) => script,
+                            Err(_) => continue,
+                        },
+                        None => {
+                            let pubkey = match branch.derive_public_key(secp, pat) {
+                                Ok(pubkey) => pubkey,
+                                Err(_) => continue,
+                            };
+                            let internal_key = XOnlyPublicKey::from(pubkey);
+                            Script::new_v1_p2tr(secp, internal_key, self.tap_merkle_root)
+                        }
+                    };
+                    if &derived == script {
+                        return Some(index);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Sets BIP68 relative time lock of `blocks` number of blocks by updating
+    /// the input sequence number.
+    #[inline]
+    pub fn set_relative_locktime_blocks(&mut self, blocks: u16) {
+        self.sequence_number = Some(SeqNo::from_height(blocks));
+    }
+
+    /// Sets BIP68 relative time lock of at least `seconds` by updating the
+    /// input sequence number. Since the sequence number encodes time in
+    /// 512-second intervals, the value is rounded up to the next interval.
+    ///
+    /// # Errors
+    ///
+    /// If the number of intervals does not fit into 16 bits of the sequence
+    /// number.
+    pub fn set_relative_locktime_time(&mut self, seconds: u32) -> Result<(), RelativeLockError> {
+        let intervals = (seconds / 512 + (seconds % 512 != 0) as u32)
+            .try_into()
+            .map_err(|_| RelativeLockError::TimeOverflow(seconds))?;
+        self.sequence_number = Some(SeqNo::from_intervals(intervals));
+        Ok(())
+    }
+
+    /// Returns the effective sequence number of the input, which is the final
+    /// sequence number (0xffffffff) if it is not specified.
+    #[inline]
+    pub fn sequence(&self) -> u32 { self.sequence_number.unwrap_or_default().into_consensus() }
+
+    /// Returns BIP68 relative time lock encoded in the input sequence number,
+    /// if any.
+    #[inline]
+    pub fn relative_locktime(&self) -> Option<TimeLockInterval> {
+        self.sequence_number.and_then(SeqNo::time_lock_interval)
+    }
+
     /// Obtains the [`EcdsaSighashType`] for this input if one is specified. If
     /// no sighash type is specified, returns [`EcdsaSighashType::All`].
     ///
@@ -265,11 +537,89 @@ impl Input {
         }
     }
 
+    /// Sets spent output information for the input.
+    ///
+    /// Errors with [`InputMatchError::WitnessUtxoMismatch`] if the input
+    /// already contains full spent transaction in `non_witness_utxo` and its
+    /// output referenced by the input does not match `txout`. In this case
+    /// the input is left unmodified.
+    pub fn set_witness_utxo(&mut self, txout: TxOut) -> Result<(), InputMatchError> {
+        if let Some(tx) = &self.non_witness_utxo {
+            if tx.output.get(self.previous_outpoint.vout as usize) != Some(&txout) {
+                return Err(InputMatchError::WitnessUtxoMismatch);
+            }
+        }
+        self.witness_utxo = Some(txout);
+        Ok(())
+    }
+
+    /// Sets full spent transaction information for the input.
+    ///
+    /// Since the transaction is authenticated by its txid, it takes precedence
+    /// over the existing `witness_utxo`, which gets cleared if it does not
+    /// match the transaction output spent by the input.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the transaction id does not match the input previous outpoint,
+    /// or if the transaction does not have the output spent by the input. In
+    /// these cases the input is left unmodified.
+    pub fn set_non_witness_utxo(&mut self, tx: Transaction) -> Result<(), InputMatchError> {
+        let txid = self.previous_outpoint.txid;
+        if tx.txid() != txid {
+            return Err(InputMatchError::NoTxidMatch(txid));
+        }
+        let vout = self.previous_outpoint.vout;
+        let prevout = tx
+            .output
+            .get(vout as usize)
+            .ok_or(InputMatchError::UnmatchedInputNumber(vout))?;
+        if self.witness_utxo.as_ref() != Some(prevout) {
+            self.witness_utxo = None;
+        }
+        self.non_witness_utxo = Some(tx);
+        Ok(())
+    }
+
+    /// Checks that the redeem script hashes to the P2SH scriptPubKey of the
+    /// spent output, and the witness script hashes to its P2WSH witness
+    /// program (which may be nested into the redeem script).
+    ///
+    /// If neither `witness_utxo` nor `non_witness_utxo` is present, there is
+    /// nothing to validate against and the function succeeds.
+    pub fn validate_scripts(&self) -> Result<(), ScriptError> {
+        let prevout = match self.input_prevout() {
+            Ok(prevout) => prevout,
+            Err(InputMatchError::NoInputTx) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        let script_pubkey = &prevout.script_pubkey;
+
+        let program = match &self.redeem_script {
+            Some(_) if !script_pubkey.is_p2sh() => return Err(ScriptError::UnexpectedRedeemScript),
+            Some(redeem_script) if redeem_script.to_p2sh() != *script_pubkey => {
+                return Err(ScriptError::RedeemScriptMismatch)
+            }
+            Some(redeem_script) => redeem_script,
+            None => script_pubkey,
+        };
+
+        match &self.witness_script {
+            None => Ok(()),
+            Some(_) if program.is_p2sh() => Err(ScriptError::NoRedeemScript),
+            Some(_) if !program.is_v0_p2wsh() => Err(ScriptError::UnexpectedWitnessScript),
+            Some(witness_script) if witness_script.to_v0_p2wsh() != *program => {
+                Err(ScriptError::WitnessScriptMismatch)
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
     pub fn to_unsigned_txin(&self) -> TxIn {
         TxIn {
             previous_output: self.previous_outpoint,
             script_sig: empty!(),
-            sequence: self.sequence_number.unwrap_or_default().into_consensus(),
+            sequence: self.sequence(),
             witness: empty!(),
         }
     }
@@ -278,7 +628,7 @@ impl Input {
         TxIn {
             previous_output: self.previous_outpoint,
             script_sig: self.final_script_sig.as_ref().cloned().unwrap_or_default(),
-            sequence: self.sequence_number.unwrap_or_default().into_consensus(),
+            sequence: self.sequence(),
             witness: self
                 .final_script_witness
                 .as_ref()
@@ -287,7 +637,8 @@ impl Input {
         }
     }
 
-    pub fn split(self) -> (InputV0, TxIn) {
+    pub fn split(mut self) -> (InputV0, TxIn) {
+        put_por_commitment(&mut self.unknown, self.por_commitment);
         (
             InputV0 {
                 non_witness_utxo: self.non_witness_utxo,
@@ -323,7 +674,8 @@ impl Input {
 }
 
 impl From<Input> for InputV0 {
-    fn from(input: Input) -> Self {
+    fn from(mut input: Input) -> Self {
+        put_por_commitment(&mut input.unknown, input.por_commitment);
         InputV0 {
             non_witness_utxo: input.non_witness_utxo,
             witness_utxo: input.witness_utxo,
@@ -349,3 +701,429 @@ impl From<Input> for InputV0 {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bitcoin::blockdata::opcodes::all::OP_CHECKSIG;
+    use bitcoin::blockdata::script::Builder;
+    use bitcoin::util::bip32::{DerivationPath, Fingerprint};
+
+    use super::*;
+
+    fn witness_script() -> Script {
+        let pk = PublicKey::from_str(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        Builder::new()
+            .push_key(&pk)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    fn input(script_pubkey: Script) -> Input {
+        Input {
+            witness_utxo: Some(TxOut {
+                value: 10_000,
+                script_pubkey,
+            }),
+            ..Input::default()
+        }
+    }
+
+    #[test]
+    fn por_commitment_roundtrip() {
+        use bitcoin::Transaction;
+
+        use crate::serialize::{Deserialize, Serialize};
+        use crate::{Psbt, PsbtVersion};
+
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn::default(), TxIn::default()],
+            output: vec![],
+        };
+        let por_key = raw::Key {
+            type_value: PSBT_IN_POR_COMMITMENT,
+            key: vec![],
+        };
+        for version in [PsbtVersion::V0, PsbtVersion::V2] {
+            let mut psbt = Psbt::with(tx.clone(), version).unwrap();
+            psbt.inputs[0].por_commitment = Some(s!("Proof of reserves"));
+            psbt.inputs[1]
+                .unknown
+                .insert(por_key.clone(), vec![0xff, 0xfe]);
+
+            let data = psbt.serialize();
+            let decoded = Psbt::deserialize(&data).unwrap();
+            assert_eq!(
+                decoded.inputs[0].por_commitment,
+                Some(s!("Proof of reserves"))
+            );
+            assert!(decoded.inputs[0].unknown.is_empty());
+            // Values which are not UTF-8 strings are kept as unknown keys
+            assert_eq!(decoded.inputs[1].por_commitment, None);
+            assert_eq!(
+                decoded.inputs[1].unknown.get(&por_key),
+                Some(&vec![0xff, 0xfe])
+            );
+            assert_eq!(decoded, psbt);
+            assert_eq!(decoded.serialize(), data);
+        }
+    }
+
+    #[test]
+    fn por_commitment_conflict() {
+        let por_key = raw::Key {
+            type_value: PSBT_IN_POR_COMMITMENT,
+            key: vec![],
+        };
+        let mut input = Input::default();
+        input.unknown.insert(por_key.clone(), vec![0xff, 0xfe]);
+        input.por_commitment = Some(s!("Proof of reserves"));
+
+        let v0 = InputV0::from(input);
+        assert_eq!(v0.unknown.get(&por_key), Some(&vec![0xff, 0xfe]));
+    }
+
+    #[test]
+    fn is_mine() {
+        let secp = Secp256k1::verification_only();
+        let account = DerivationAccount::from_str(
+            "[73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/<0;1>/*",
+        )
+        .unwrap();
+        let branches = account.split_multipath().unwrap();
+        let index = UnhardenedIndex::from(3u8);
+
+        for scheme in [Bip43::Bip44, Bip43::Bip49, Bip43::Bip84, Bip43::Bip86] {
+            let script = branches[1].script_pubkey(&secp, scheme, [index]).unwrap();
+            assert_eq!(
+                input(script.clone()).is_mine(&secp, &account, 10),
+                Some(index)
+            );
+            assert_eq!(input(script).is_mine(&secp, &account, 2), None);
+        }
+
+        let mut txin = input(
+            branches[0]
+                .script_pubkey(&secp, Bip43::Bip86, [index])
+                .unwrap(),
+        );
+        txin.tap_merkle_root = Some(TapBranchHash::default());
+        assert_eq!(txin.is_mine(&secp, &account, 10), None);
+
+        assert_eq!(
+            input(witness_script().to_v0_p2wsh()).is_mine(&secp, &account, 10),
+            None
+        );
+        assert_eq!(Input::default().is_mine(&secp, &account, 10), None);
+    }
+
+    #[test]
+    fn spend_type() {
+        let pk = PublicKey::from_str(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        let wpkh = Script::new_v0_p2wpkh(&pk.wpubkey_hash().unwrap());
+        let xonly = XOnlyPublicKey::from(pk.inner);
+        let secp = Secp256k1::verification_only();
+
+        assert_eq!(Input::default().spend_type(), None);
+        assert_eq!(
+            input(Script::new_p2pkh(&pk.pubkey_hash())).spend_type(),
+            Some(DescriptorClass::PreSegwit)
+        );
+        assert_eq!(
+            input(wpkh.clone()).spend_type(),
+            Some(DescriptorClass::SegwitV0)
+        );
+        assert_eq!(
+            input(witness_script().to_v0_p2wsh()).spend_type(),
+            Some(DescriptorClass::SegwitV0)
+        );
+        assert_eq!(
+            input(Script::new_v1_p2tr(&secp, xonly, None)).spend_type(),
+            Some(DescriptorClass::TaprootC0)
+        );
+        assert_eq!(
+            input(Script::from_str("5202abcd").unwrap()).spend_type(),
+            None
+        );
+
+        let mut nested = input(wpkh.to_p2sh());
+        assert_eq!(nested.spend_type(), None);
+        nested.redeem_script = Some(wpkh);
+        assert_eq!(nested.spend_type(), Some(DescriptorClass::NestedV0));
+        nested.redeem_script = None;
+        nested.witness_script = Some(witness_script());
+        assert_eq!(nested.spend_type(), Some(DescriptorClass::NestedV0));
+
+        let mut legacy = input(witness_script().to_p2sh());
+        legacy.redeem_script = Some(witness_script());
+        assert_eq!(legacy.spend_type(), Some(DescriptorClass::PreSegwit));
+    }
+
+    #[test]
+    fn key_source() {
+        let pk = PublicKey::from_str(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap()
+        .inner;
+        let xonly = XOnlyPublicKey::from(pk);
+        let source = (
+            Fingerprint::from_str("73c5da0a").unwrap(),
+            DerivationPath::from_str("m/84'/0'/0'/0/0").unwrap(),
+        );
+        let tap_source = (
+            Fingerprint::from_str("73c5da0a").unwrap(),
+            DerivationPath::from_str("m/86'/0'/0'/0/0").unwrap(),
+        );
+        let leaf_hash = TapLeafHash::from_script(&witness_script(), LeafVersion::TapScript);
+
+        let mut input = Input::default();
+        assert_eq!(input.key_source(&pk), None);
+        assert_eq!(input.tap_key_source(&xonly), None);
+
+        input.bip32_derivation.insert(pk, source.clone());
+        input
+            .tap_key_origins
+            .insert(xonly, (vec![leaf_hash], tap_source.clone()));
+        assert_eq!(input.key_source(&pk), Some(&source));
+        assert_eq!(
+            input.tap_key_source(&xonly),
+            Some(&(vec![leaf_hash], tap_source))
+        );
+    }
+
+    #[test]
+    fn relative_locktime() {
+        let mut input = Input::default();
+        assert_eq!(input.relative_locktime(), None);
+
+        input.set_relative_locktime_blocks(144);
+        assert_eq!(input.sequence_number, Some(SeqNo::from_consensus(144)));
+        assert_eq!(
+            input.relative_locktime(),
+            Some(TimeLockInterval::Height(144))
+        );
+
+        input.set_relative_locktime_time(1024).unwrap();
+        assert_eq!(
+            input.sequence_number,
+            Some(SeqNo::from_consensus(0x0040_0002))
+        );
+        assert_eq!(input.relative_locktime(), Some(TimeLockInterval::Time(2)));
+
+        input.set_relative_locktime_time(3600).unwrap();
+        assert_eq!(input.relative_locktime(), Some(TimeLockInterval::Time(8)));
+
+        input.set_relative_locktime_time(0xFFFF * 512).unwrap();
+        assert_eq!(
+            input.relative_locktime(),
+            Some(TimeLockInterval::Time(0xFFFF))
+        );
+        assert_eq!(
+            input.set_relative_locktime_time(0xFFFF * 512 + 1),
+            Err(RelativeLockError::TimeOverflow(0xFFFF * 512 + 1))
+        );
+        assert_eq!(
+            input.relative_locktime(),
+            Some(TimeLockInterval::Time(0xFFFF))
+        );
+
+        input.sequence_number = Some(SeqNo::rbf());
+        assert_eq!(input.relative_locktime(), None);
+    }
+
+    #[test]
+    fn set_utxo() {
+        let prev_tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn::default()],
+            output: vec![
+                TxOut {
+                    value: 10_000,
+                    script_pubkey: witness_script().to_v0_p2wsh(),
+                },
+                TxOut {
+                    value: 20_000,
+                    script_pubkey: witness_script().to_p2sh(),
+                },
+            ],
+        };
+        let mut input = Input {
+            previous_outpoint: OutPoint::new(prev_tx.txid(), 1),
+            ..Input::default()
+        };
+
+        // stale witness utxo gets replaced with the authenticated transaction
+        input.set_witness_utxo(prev_tx.output[0].clone()).unwrap();
+        input.set_non_witness_utxo(prev_tx.clone()).unwrap();
+        assert_eq!(input.witness_utxo, None);
+        assert_eq!(input.input_prevout(), Ok(&prev_tx.output[1]));
+
+        assert_eq!(
+            input.set_witness_utxo(prev_tx.output[0].clone()),
+            Err(InputMatchError::WitnessUtxoMismatch)
+        );
+        assert_eq!(input.witness_utxo, None);
+        input.set_witness_utxo(prev_tx.output[1].clone()).unwrap();
+        input.set_non_witness_utxo(prev_tx.clone()).unwrap();
+        assert_eq!(input.witness_utxo, Some(prev_tx.output[1].clone()));
+
+        let mut other_tx = prev_tx.clone();
+        other_tx.lock_time = 1;
+        assert_eq!(
+            input.set_non_witness_utxo(other_tx),
+            Err(InputMatchError::NoTxidMatch(prev_tx.txid()))
+        );
+        assert_eq!(input.non_witness_utxo, Some(prev_tx.clone()));
+
+        input.previous_outpoint.vout = 2;
+        input.non_witness_utxo = None;
+        assert_eq!(
+            input.set_non_witness_utxo(prev_tx),
+            Err(InputMatchError::UnmatchedInputNumber(2))
+        );
+        assert_eq!(input.non_witness_utxo, None);
+    }
+
+    #[test]
+    fn p2wsh_scripts() {
+        let script = witness_script();
+        let mut input = input(script.to_v0_p2wsh());
+        assert_eq!(input.validate_scripts(), Ok(()));
+
+        input.witness_script = Some(script.clone());
+        assert_eq!(input.validate_scripts(), Ok(()));
+
+        input.witness_script = Some(Builder::new().push_int(1).into_script());
+        assert_eq!(
+            input.validate_scripts(),
+            Err(ScriptError::WitnessScriptMismatch)
+        );
+
+        input.witness_script = Some(script.clone());
+        input.redeem_script = Some(script.to_v0_p2wsh());
+        assert_eq!(
+            input.validate_scripts(),
+            Err(ScriptError::UnexpectedRedeemScript)
+        );
+    }
+
+    #[test]
+    fn p2sh_p2wsh_scripts() {
+        let script = witness_script();
+        let redeem_script = script.to_v0_p2wsh();
+        let mut input = input(redeem_script.to_p2sh());
+        input.witness_script = Some(script.clone());
+        assert_eq!(input.validate_scripts(), Err(ScriptError::NoRedeemScript));
+
+        input.redeem_script = Some(redeem_script);
+        assert_eq!(input.validate_scripts(), Ok(()));
+
+        input.redeem_script = Some(script.clone());
+        assert_eq!(
+            input.validate_scripts(),
+            Err(ScriptError::RedeemScriptMismatch)
+        );
+
+        input.witness_utxo = Some(TxOut {
+            value: 10_000,
+            script_pubkey: script.to_p2sh(),
+        });
+        assert_eq!(
+            input.validate_scripts(),
+            Err(ScriptError::UnexpectedWitnessScript)
+        );
+
+        input.witness_utxo = None;
+        assert_eq!(input.validate_scripts(), Ok(()));
+    }
+
+    #[test]
+    fn push_tap_key_origin() {
+        use amplify::hex::FromHex;
+        use bitcoin::blockdata::opcodes::all::OP_CHECKSIGVERIFY;
+        use bitcoin::util::taproot::TaprootBuilder;
+
+        use crate::serialize::Serialize;
+        use crate::{Psbt, PsbtVersion};
+
+        let secp = Secp256k1::verification_only();
+        let account = DerivationAccount::from_str(
+            "[73c5da0a/86h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*",
+        )
+        .unwrap();
+        let key = |index: u8| {
+            XOnlyPublicKey::from(account.derive_public_key(&secp, [index.into()]).unwrap())
+        };
+        let single = Builder::new()
+            .push_slice(&key(0).serialize())
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        let multi = Builder::new()
+            .push_slice(&key(1).serialize())
+            .push_opcode(OP_CHECKSIGVERIFY)
+            .push_slice(&key(0).serialize())
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        let single_hash = TapLeafHash::from_script(&single, LeafVersion::TapScript);
+        let multi_hash = TapLeafHash::from_script(&multi, LeafVersion::TapScript);
+        let builder = TaprootBuilder::new()
+            .add_leaf(1, single)
+            .unwrap()
+            .add_leaf(1, multi)
+            .unwrap();
+        let tree = TapTree::from_builder(builder).unwrap();
+
+        let mut input = Input::default();
+        let terminal = [UnhardenedIndex::one()];
+        assert_eq!(
+            input.push_tap_key_origin(&secp, &account, terminal, Some(&tree)),
+            Ok(key(1))
+        );
+        let mut leaves = vec![single_hash, multi_hash];
+        leaves.sort();
+        // Repeated key derivation does not duplicate leaf hashes
+        for index in [0u8, 0, 2] {
+            let terminal = [UnhardenedIndex::from(index)];
+            let tree = if index == 2 { None } else { Some(&tree) };
+            input
+                .push_tap_key_origin(&secp, &account, terminal, tree)
+                .unwrap();
+        }
+        let source = |index: u8| account.key_source([UnhardenedIndex::from(index)]).unwrap();
+        assert_eq!(input.tap_key_origins, bmap! {
+            key(0) => (leaves, source(0)),
+            key(1) => (vec![multi_hash], source(1)),
+            key(2) => (vec![], source(2))
+        });
+
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn::default()],
+            output: vec![],
+        };
+        let mut psbt = Psbt::with(tx, PsbtVersion::V2).unwrap();
+        psbt.inputs[0].tap_key_origins = bmap! { key(1) => (vec![multi_hash], source(1)) };
+        // Key type with x-only key, value with leaf hash count, leaf hashes,
+        // master key fingerprint and derivation path m/86'/0'/0'/0/1
+        let mut pair = vec![0x21, 0x16];
+        pair.extend(key(1).serialize());
+        pair.extend([0x39, 0x01]);
+        pair.extend(&multi_hash[..]);
+        pair.extend(Vec::from_hex("73c5da0a").unwrap());
+        pair.extend(Vec::from_hex("5600008000000080000000800000000001000000").unwrap());
+        let data = psbt.serialize();
+        assert!(data.windows(pair.len()).any(|window| window == pair));
+    }
+}
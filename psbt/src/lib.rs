@@ -38,6 +38,8 @@ extern crate serde_crate as serde;
 #[macro_use]
 extern crate strict_encoding;
 
+use std::str::FromStr;
+
 mod errors;
 mod global;
 mod input;
@@ -47,16 +49,22 @@ pub mod commit;
 #[cfg(feature = "miniscript")]
 pub mod construct;
 mod proprietary;
+mod reader;
 #[cfg(feature = "sign")]
 pub mod sign;
 mod util;
+mod v2;
 
 pub use bitcoin::psbt::raw::ProprietaryKey;
 pub use bitcoin::psbt::{raw, serialize, Error, PsbtParseError, PsbtSighashType};
-pub use errors::{FeeError, InputMatchError, TxError, TxinError};
+pub use errors::{
+    CombineError, DecodeError, FeeError, FinalizedTxError, GlobalKeyError, IndexError,
+    InputMatchError, JoinError, KeyHexError, KeySourceConflict, PsbtVersionParseError,
+    RelativeLockError, ScriptError, TxError, TxinError, TxoutError, ValidationError,
+};
 pub use global::Psbt;
 pub use input::Input;
-pub use output::Output;
+pub use output::{Output, MAX_OP_RETURN_DATA};
 pub(crate) mod v0 {
     pub use bitcoin::psbt::{
         Input as InputV0, Output as OutputV0, PartiallySignedTransaction as PsbtV0,
@@ -69,14 +77,20 @@ pub use commit::{
     PSBT_TAPRET_PREFIX,
 };
 pub use proprietary::{
-    ProprietaryKeyDescriptor, ProprietaryKeyError, ProprietaryKeyLocation, ProprietaryKeyType,
+    LabelTooLong, ProprietaryKeyDescriptor, ProprietaryKeyError, ProprietaryKeyExt,
+    ProprietaryKeyLocation, ProprietaryKeyType, RawKeyExt, MAX_LABEL_LEN, PSBT_IN_SOURCE_ACCOUNT,
+    PSBT_LNPBP_PREFIX, PSBT_OUT_LABEL,
 };
-pub use util::lex_order;
+pub use reader::PsbtReader;
 #[cfg(feature = "miniscript")]
 pub use util::DeductionError;
+pub use util::{
+    fingerprint_eq_ct, lex_order, InputPlan, PsbtSummary, SignerCandidate, SignerKey, SigningPlan,
+    SigningRequirement, SigningStatus, SpendPath,
+};
 
 /// Version of the PSBT (V0 stands for BIP174-defined version; V2 - for BIP370).
-#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
 #[derive(StrictEncode, StrictDecode)]
 #[strict_encoding(repr = u32)]
 #[cfg_attr(
@@ -87,11 +101,63 @@ pub use util::DeductionError;
 #[repr(u32)]
 pub enum PsbtVersion {
     /// Version defined by BIP174.
+    #[display("v0")]
     V0 = 0x0,
     /// Version defined by BIP370.
+    #[display("v2")]
     V2 = 0x2,
 }
 
 impl Default for PsbtVersion {
     fn default() -> Self { PsbtVersion::V2 }
 }
+
+impl PsbtVersion {
+    /// Returns version number as it is serialized in `PSBT_GLOBAL_VERSION`.
+    #[inline]
+    pub fn as_u32(self) -> u32 { self as u32 }
+}
+
+impl FromStr for PsbtVersion {
+    type Err = PsbtVersionParseError;
+
+    /// Parses version from either `0`/`2` or `v0`/`v2` strings
+    /// (case-insensitive).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "0" | "v0" => Ok(PsbtVersion::V0),
+            "2" | "v2" => Ok(PsbtVersion::V2),
+            _ => Err(PsbtVersionParseError(s.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn psbt_version_str() {
+        for (s, version) in [
+            ("0", PsbtVersion::V0),
+            ("v0", PsbtVersion::V0),
+            ("V0", PsbtVersion::V0),
+            ("2", PsbtVersion::V2),
+            ("v2", PsbtVersion::V2),
+            ("V2", PsbtVersion::V2),
+        ] {
+            assert_eq!(PsbtVersion::from_str(s), Ok(version));
+        }
+        for version in [PsbtVersion::V0, PsbtVersion::V2] {
+            assert_eq!(PsbtVersion::from_str(&version.to_string()), Ok(version));
+        }
+        assert_eq!(PsbtVersion::V0.to_string(), "v0");
+        assert_eq!(PsbtVersion::V2.as_u32(), 2);
+        for s in ["1", "v1", "3", "", "v", "02"] {
+            assert_eq!(
+                PsbtVersion::from_str(s),
+                Err(PsbtVersionParseError(s.to_owned()))
+            );
+        }
+    }
+}
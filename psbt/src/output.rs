@@ -15,14 +15,19 @@
 use std::collections::BTreeMap;
 
 use bitcoin::psbt::TapTree;
+use bitcoin::secp256k1::{Secp256k1, Verification};
 use bitcoin::util::bip32::KeySource;
 use bitcoin::util::taproot::TapLeafHash;
-use bitcoin::{secp256k1, Script, TxOut, XOnlyPublicKey};
+use bitcoin::{consensus, secp256k1, Amount, Script, TxOut, XOnlyPublicKey};
+use bitcoin_hd::{
+    DerivationAccount, DerivePatternError, SegmentIndexes, UnhardenedIndex, HARDENED_INDEX_BOUNDARY,
+};
 #[cfg(feature = "serde")]
 use serde_with::{hex::Hex, As, Same};
 
-use crate::raw;
+use crate::input::insert_tap_key_origin;
 use crate::v0::OutputV0;
+use crate::{raw, TxoutError};
 
 // TODO: Do manual serde implementation to check the deserialized values
 #[derive(Clone, Eq, PartialEq, Debug, Default)]
@@ -76,18 +81,47 @@ pub struct Output {
     pub unknown: BTreeMap<raw::Key, Vec<u8>>,
 }
 
+/// Maximum size of the data embedded into `OP_RETURN` output which is relayed
+/// by the nodes under the default standardness policy.
+pub const MAX_OP_RETURN_DATA: usize = 80;
+
+/// Checks that the output amount does not exceed 21 million BTC.
+fn check_amount(index: usize, amount: u64) -> Result<(), TxoutError> {
+    if amount > Amount::MAX_MONEY.as_sat() {
+        return Err(TxoutError::AmountOutOfRange(index, amount));
+    }
+    Ok(())
+}
+
 impl Output {
-    pub fn new(index: usize, txout: TxOut) -> Self {
-        Output {
+    pub fn new(index: usize, txout: TxOut) -> Result<Self, TxoutError> {
+        check_amount(index, txout.value)?;
+        Ok(Output {
             index,
             amount: txout.value,
             script: txout.script_pubkey,
             ..Output::default()
+        })
+    }
+
+    /// Constructs zero-value output with `OP_RETURN <data>` script, embedding
+    /// the provided data into the transaction.
+    ///
+    /// Errors with [`TxoutError::OpReturnTooLong`] if the data exceed
+    /// [`MAX_OP_RETURN_DATA`] bytes, making the transaction non-standard.
+    pub fn op_return(index: usize, data: &[u8]) -> Result<Self, TxoutError> {
+        if data.len() > MAX_OP_RETURN_DATA {
+            return Err(TxoutError::OpReturnTooLong(index, data.len()));
         }
+        Output::new(index, TxOut {
+            value: 0,
+            script_pubkey: Script::new_op_return(data),
+        })
     }
 
-    pub fn with(index: usize, v0: OutputV0, txout: TxOut) -> Self {
-        Output {
+    pub fn with(index: usize, v0: OutputV0, txout: TxOut) -> Result<Self, TxoutError> {
+        check_amount(index, txout.value)?;
+        Ok(Output {
             index,
             amount: txout.value,
             script: txout.script_pubkey,
@@ -99,12 +133,129 @@ impl Output {
             tap_key_origins: v0.tap_key_origins,
             proprietary: v0.proprietary,
             unknown: v0.unknown,
-        }
+        })
     }
 
     #[inline]
     pub fn index(&self) -> usize { self.index }
 
+    /// Adds BIP32 derivation information for the public key, allowing signers
+    /// to recognize and verify outputs controlled by the wallet (like change
+    /// outputs). Returns previous key source, if it was present for the key.
+    #[inline]
+    pub fn set_derivation(
+        &mut self,
+        pubkey: secp256k1::PublicKey,
+        source: KeySource,
+    ) -> Option<KeySource> {
+        self.bip32_derivation.insert(pubkey, source)
+    }
+
+    /// Computes the minimal amount of this output which is not considered dust
+    /// by Bitcoin Core standardness rules at the given dust relay fee rate
+    /// (in satoshis per virtual byte; Bitcoin Core uses 3 sat/vB by default).
+    ///
+    /// The threshold is the cost of creating the output together with the
+    /// cost of an input spending it later, which is 67 vbytes for witness
+    /// programs and 148 bytes for other script types. Unspendable `OP_RETURN`
+    /// outputs have zero threshold.
+    pub fn dust_threshold(&self, dust_relay_feerate: f64) -> u64 {
+        if self.script.is_op_return() {
+            return 0;
+        }
+        let spend_size = if self.script.is_witness_program() {
+            32 + 4 + 1 + 107 / 4 + 4
+        } else {
+            32 + 4 + 1 + 107 + 4
+        };
+        let size = consensus::serialize(&self.to_txout()).len() + spend_size;
+        (size as f64 * dust_relay_feerate) as u64
+    }
+
+    /// Checks whether the output amount is below the dust threshold (see
+    /// [`Output::dust_threshold`]).
+    #[inline]
+    pub fn is_dust(&self, dust_relay_feerate: f64) -> bool {
+        self.amount < self.dust_threshold(dust_relay_feerate)
+    }
+
+    /// Detects whether this is a taproot output paying to the key derived from
+    /// the `account` at some index not greater than `max_index`, returning the
+    /// matching index. For accounts with a multipath terminal step (like
+    /// `/<0;1>/*`) the last branch is used, which is the change branch by a
+    /// BIP-44 convention. Terminal paths ending with two wildcards (`/*/*`)
+    /// are derived with the `1` change index at the first of them, matching
+    /// the change derivation used by the [`construct`](crate::construct)
+    /// module.
+    ///
+    /// For each index the derived key is used as a taproot internal key, which
+    /// is tweaked with the merkle root of the output `tap_tree` (or without any
+    /// script tree, if it is absent) and the resulting P2TR script is compared
+    /// with the output `scriptPubkey`. If the output specifies taproot internal
+    /// key or key origin for the matching key, they must be consistent with
+    /// the derived key; otherwise the output is not recognized.
+    pub fn is_my_taproot_change<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        account: &DerivationAccount,
+        max_index: u32,
+    ) -> Option<UnhardenedIndex> {
+        if !self.script.is_v1_p2tr() {
+            return None;
+        }
+        let branch = account.split_multipath().ok()?.pop()?;
+        let change = match branch.derive_pattern_len() {
+            1 => None,
+            2 => Some(UnhardenedIndex::one()),
+            _ => return None,
+        };
+        let mut merkle_root = None;
+        for index in 0..=max_index.min(HARDENED_INDEX_BOUNDARY - 1) {
+            let index = UnhardenedIndex::from_index(index).expect("index is unhardened");
+            let pat = change.into_iter().chain([index]).collect::<Vec<_>>();
+            let (pubkey, key_source) = branch.bip32_derivation(secp, pat).ok()?;
+            let internal_key = XOnlyPublicKey::from(pubkey);
+            if matches!(self.tap_internal_key, Some(key) if key != internal_key) {
+                continue;
+            }
+            // Merkle root does not depend on the internal key, so we compute it
+            // only once
+            if merkle_root.is_none() {
+                merkle_root = Some(self.tap_tree.as_ref().and_then(|tree| {
+                    tree.to_builder()
+                        .finalize(secp, internal_key)
+                        .expect("tap tree is always complete")
+                        .merkle_root()
+                }));
+            }
+            let script = Script::new_v1_p2tr(secp, internal_key, merkle_root.flatten());
+            if script != self.script {
+                continue;
+            }
+            return match self.tap_key_origins.get(&internal_key) {
+                Some((_, origin)) if *origin != key_source => None,
+                _ => Some(index),
+            };
+        }
+        None
+    }
+
+    /// Adds taproot key derivation data for the key derived from the
+    /// `account` at the `terminal` path, listing hashes of the output
+    /// [`Output::tap_tree`] leaves using the key (see
+    /// [`Input::push_tap_key_origin`](crate::Input::push_tap_key_origin)).
+    ///
+    /// Returns the derived x-only public key.
+    pub fn push_tap_key_origin<C: Verification>(
+        &mut self,
+        secp: &Secp256k1<C>,
+        account: &DerivationAccount,
+        terminal: impl AsRef<[UnhardenedIndex]>,
+    ) -> Result<XOnlyPublicKey, DerivePatternError> {
+        let tree = self.tap_tree.as_ref();
+        insert_tap_key_origin(&mut self.tap_key_origins, secp, account, terminal, tree)
+    }
+
     pub fn to_txout(&self) -> TxOut {
         TxOut {
             value: self.amount,
@@ -153,3 +304,166 @@ impl From<Output> for OutputV0 {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use core::str::FromStr;
+
+    use bitcoin::blockdata::opcodes::all::OP_PUSHDATA1;
+    use bitcoin::psbt::TapTree;
+    use bitcoin::util::taproot::TaprootBuilder;
+    use bitcoin_hd::{Bip43, DerivePublicKey};
+
+    use super::*;
+
+    #[test]
+    fn taproot_change() {
+        let secp = Secp256k1::verification_only();
+        let account = DerivationAccount::from_str(
+            "[73c5da0a/86h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/<0;1>/*",
+        )
+        .unwrap();
+        let change = account.split_multipath().unwrap().pop().unwrap();
+        let index = UnhardenedIndex::from(5u8);
+        let output = |script: Script| {
+            Output::new(0, TxOut {
+                value: 1000,
+                script_pubkey: script,
+            })
+            .unwrap()
+        };
+
+        let mut out = output(change.script_pubkey(&secp, Bip43::Bip86, [index]).unwrap());
+        assert_eq!(out.is_my_taproot_change(&secp, &account, 10), Some(index));
+        assert_eq!(out.is_my_taproot_change(&secp, &change, 5), Some(index));
+        assert_eq!(out.is_my_taproot_change(&secp, &account, 4), None);
+
+        // Receive branch key is not a change
+        let receive = account.split_multipath().unwrap().remove(0);
+        let other = output(receive.script_pubkey(&secp, Bip43::Bip86, [index]).unwrap());
+        assert_eq!(other.is_my_taproot_change(&secp, &account, 10), None);
+        // Non-taproot output
+        let other = output(change.script_pubkey(&secp, Bip43::Bip84, [index]).unwrap());
+        assert_eq!(other.is_my_taproot_change(&secp, &account, 10), None);
+
+        let pubkey = change.derive_public_key(&secp, [index]).unwrap();
+        let internal_key = XOnlyPublicKey::from(pubkey);
+        out.tap_internal_key = Some(internal_key);
+        assert_eq!(out.is_my_taproot_change(&secp, &account, 10), Some(index));
+        out.tap_internal_key = Some(XOnlyPublicKey::from(
+            change
+                .derive_public_key(&secp, [UnhardenedIndex::from(4u8)])
+                .unwrap(),
+        ));
+        assert_eq!(out.is_my_taproot_change(&secp, &account, 10), None);
+        out.tap_internal_key = Some(internal_key);
+
+        let key_source = change.key_source([index]).unwrap();
+        out.tap_key_origins
+            .insert(internal_key, (vec![], (key_source.0, vec![].into())));
+        assert_eq!(out.is_my_taproot_change(&secp, &account, 10), None);
+        out.tap_key_origins
+            .insert(internal_key, (vec![], key_source));
+        assert_eq!(out.is_my_taproot_change(&secp, &account, 10), Some(index));
+
+        // Script tree commitment
+        let builder = TaprootBuilder::new()
+            .add_leaf(0, Script::from_str("51").unwrap())
+            .unwrap();
+        let merkle_root = builder
+            .clone()
+            .finalize(&secp, internal_key)
+            .unwrap()
+            .merkle_root();
+        out.script = Script::new_v1_p2tr(&secp, internal_key, merkle_root);
+        assert_eq!(out.is_my_taproot_change(&secp, &account, 10), None);
+        out.tap_tree = Some(TapTree::from_builder(builder).unwrap());
+        assert_eq!(out.is_my_taproot_change(&secp, &account, 10), Some(index));
+
+        // Change branch selected by derive pattern
+        let account = DerivationAccount::from_str(
+            "[73c5da0a/86h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/{0-1}/*",
+        )
+        .unwrap();
+        let out = output(
+            account
+                .script_pubkey(&secp, Bip43::Bip86, [UnhardenedIndex::one(), index])
+                .unwrap(),
+        );
+        assert_eq!(out.is_my_taproot_change(&secp, &account, 10), Some(index));
+        let out = output(
+            account
+                .script_pubkey(&secp, Bip43::Bip86, [UnhardenedIndex::zero(), index])
+                .unwrap(),
+        );
+        assert_eq!(out.is_my_taproot_change(&secp, &account, 10), None);
+    }
+
+    #[test]
+    fn push_tap_key_origin() {
+        use bitcoin::blockdata::opcodes::all::OP_CHECKSIG;
+        use bitcoin::blockdata::script::Builder;
+        use bitcoin::util::taproot::LeafVersion;
+
+        let secp = Secp256k1::verification_only();
+        let account = DerivationAccount::from_str(
+            "[73c5da0a/86h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/1/*",
+        )
+        .unwrap();
+        let index = UnhardenedIndex::from(5u8);
+        let pubkey = XOnlyPublicKey::from(account.derive_public_key(&secp, [index]).unwrap());
+        let script = Builder::new()
+            .push_slice(&pubkey.serialize())
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        let leaf_hash = TapLeafHash::from_script(&script, LeafVersion::TapScript);
+        let builder = TaprootBuilder::new()
+            .add_leaf(1, script)
+            .unwrap()
+            .add_leaf(1, Script::from_str("51").unwrap())
+            .unwrap();
+
+        let mut out = Output {
+            tap_tree: Some(TapTree::from_builder(builder).unwrap()),
+            ..Output::default()
+        };
+        assert_eq!(
+            out.push_tap_key_origin(&secp, &account, [index]),
+            Ok(pubkey)
+        );
+        assert_eq!(out.tap_key_origins, bmap! {
+            pubkey => (vec![leaf_hash], account.key_source([index]).unwrap())
+        });
+    }
+
+    #[test]
+    fn amount_out_of_range() {
+        let max_money = Amount::MAX_MONEY.as_sat();
+        let txout = |value| TxOut {
+            value,
+            script_pubkey: Script::new_op_return(&[]),
+        };
+        assert_eq!(Output::new(1, txout(max_money)).unwrap().amount, max_money);
+        assert_eq!(
+            Output::new(1, txout(max_money + 1)),
+            Err(TxoutError::AmountOutOfRange(1, max_money + 1))
+        );
+    }
+
+    #[test]
+    fn op_return() {
+        let output = Output::op_return(2, &[0xA5; MAX_OP_RETURN_DATA]).unwrap();
+        assert_eq!(output.index, 2);
+        assert_eq!(output.amount, 0);
+        assert!(output.script.is_op_return());
+        // OP_RETURN, OP_PUSHDATA1 and the length byte precede the data
+        assert_eq!(output.script.len(), 3 + MAX_OP_RETURN_DATA);
+        assert_eq!(output.script[1], OP_PUSHDATA1.into_u8());
+        assert_eq!(output.script[3..], [0xA5; MAX_OP_RETURN_DATA]);
+
+        assert_eq!(
+            Output::op_return(2, &[0xA5; MAX_OP_RETURN_DATA + 1]),
+            Err(TxoutError::OpReturnTooLong(2, MAX_OP_RETURN_DATA + 1))
+        );
+    }
+}
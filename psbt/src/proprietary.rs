@@ -14,10 +14,23 @@
 
 use core::fmt::{self, Display, Formatter};
 use core::str::FromStr;
+use std::collections::BTreeMap;
 
 use amplify::hex::{FromHex, ToHex};
+use bitcoin_hd::{HardenedIndex, SegmentIndexes};
 
-use crate::raw::ProprietaryKey;
+use crate::raw::{self, ProprietaryKey};
+use crate::{Input, KeyHexError, Output, Psbt};
+
+/// PSBT proprietary key prefix used for LNP/BP wallet-specific keys.
+pub const PSBT_LNPBP_PREFIX: &[u8] = b"LNPBP";
+/// Proprietary key subtype holding human-readable UTF-8 label of the output.
+pub const PSBT_OUT_LABEL: u8 = 0x00;
+/// Proprietary key subtype holding index of the wallet account the input is
+/// spent from, serialized as a 4-byte little-endian hardened index.
+pub const PSBT_IN_SOURCE_ACCOUNT: u8 = 0x01;
+/// Maximum length of the output label in bytes.
+pub const MAX_LABEL_LEN: usize = 256;
 
 #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error)]
 #[display(doc_comments)]
@@ -53,6 +66,23 @@ pub enum ProprietaryKeyError {
 
     /// output at index {0} exceeds the number of outputs {1}
     OutputOutOfRange(u16, usize),
+
+    /// proprietary key prefix must not be empty.
+    EmptyPrefix,
+}
+
+/// Error indicating that the output label exceeds [`MAX_LABEL_LEN`].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Error)]
+pub struct LabelTooLong(pub usize);
+
+impl Display for LabelTooLong {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "output label of {} bytes exceeds the maximum length of {} bytes",
+            self.0, MAX_LABEL_LEN
+        )
+    }
 }
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
@@ -108,6 +138,78 @@ impl FromStr for ProprietaryKeyType {
     }
 }
 
+/// Extension trait for constructing proprietary keys with validation of their
+/// components.
+pub trait ProprietaryKeyExt {
+    /// Constructs proprietary key from its `prefix`, `subtype` and key data.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`ProprietaryKeyError::EmptyPrefix`] if the `prefix` is
+    /// empty.
+    fn with(
+        prefix: impl AsRef<[u8]>,
+        subtype: u8,
+        key: impl Into<Vec<u8>>,
+    ) -> Result<ProprietaryKey, ProprietaryKeyError> {
+        let prefix = prefix.as_ref();
+        if prefix.is_empty() {
+            return Err(ProprietaryKeyError::EmptyPrefix);
+        }
+        Ok(ProprietaryKey {
+            prefix: prefix.to_vec(),
+            subtype,
+            key: key.into(),
+        })
+    }
+
+    /// Encodes proprietary key as a hexadecimal string of its full PSBT key,
+    /// i.e. `0xFC` type byte followed by compact-size prefix length, prefix,
+    /// subtype and key data.
+    fn to_hex(&self) -> String;
+
+    /// Parses proprietary key from the hexadecimal string produced by
+    /// [`ProprietaryKeyExt::to_hex`].
+    fn from_hex(s: &str) -> Result<ProprietaryKey, KeyHexError> {
+        Ok(ProprietaryKey::from_key(raw::Key::from_hex(s)?)?)
+    }
+}
+
+impl ProprietaryKeyExt for ProprietaryKey {
+    #[inline]
+    fn to_hex(&self) -> String { self.to_key().to_hex() }
+}
+
+/// Extension trait for hex encoding of raw PSBT keys, allowing persisting of
+/// individual keys independently from the rest of PSBT.
+pub trait RawKeyExt: Sized {
+    /// Encodes key as a hexadecimal string of its type byte followed by the
+    /// key data.
+    fn to_hex(&self) -> String;
+
+    /// Parses key from the hexadecimal string produced by
+    /// [`RawKeyExt::to_hex`].
+    fn from_hex(s: &str) -> Result<Self, KeyHexError>;
+}
+
+impl RawKeyExt for raw::Key {
+    fn to_hex(&self) -> String {
+        let mut data = Vec::with_capacity(self.key.len() + 1);
+        data.push(self.type_value);
+        data.extend(&self.key);
+        data.to_hex()
+    }
+
+    fn from_hex(s: &str) -> Result<Self, KeyHexError> {
+        let data = Vec::<u8>::from_hex(s)?;
+        let (type_value, key) = data.split_first().ok_or(KeyHexError::Empty)?;
+        Ok(raw::Key {
+            type_value: *type_value,
+            key: key.to_vec(),
+        })
+    }
+}
+
 // --proprietary-key "input(1) DBC(1) 8536ba03:~"
 #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
 pub struct ProprietaryKeyDescriptor {
@@ -188,3 +290,280 @@ impl Display for ProprietaryKeyDescriptor {
         )
     }
 }
+
+impl Psbt {
+    /// Returns mutable reference to the global map of proprietary keys.
+    #[inline]
+    pub fn proprietary_mut(&mut self) -> &mut BTreeMap<ProprietaryKey, Vec<u8>> {
+        &mut self.proprietary
+    }
+
+    /// Adds proprietary key-value pair described by
+    /// [`ProprietaryKeyDescriptor`] to the global map or to the map of a
+    /// specific input or output. Errors if the input or output referenced
+    /// by the descriptor is not present.
+    pub fn push_proprietary(
+        &mut self,
+        key: ProprietaryKeyDescriptor,
+    ) -> Result<(), ProprietaryKeyError> {
+        let value = key.value.as_ref().cloned().unwrap_or_default();
+        let map = match key.location {
+            ProprietaryKeyLocation::Global => &mut self.proprietary,
+            ProprietaryKeyLocation::Input(pos) => {
+                &mut self
+                    .input_mut(pos as usize)
+                    .map_err(|err| ProprietaryKeyError::InputOutOfRange(pos, err.len))?
+                    .proprietary
+            }
+            ProprietaryKeyLocation::Output(pos) => {
+                &mut self
+                    .output_mut(pos as usize)
+                    .map_err(|err| ProprietaryKeyError::OutputOutOfRange(pos, err.len))?
+                    .proprietary
+            }
+        };
+        map.insert(key.into(), value);
+        Ok(())
+    }
+}
+
+impl Output {
+    fn label_key() -> ProprietaryKey {
+        ProprietaryKey {
+            prefix: PSBT_LNPBP_PREFIX.to_vec(),
+            subtype: PSBT_OUT_LABEL,
+            key: vec![],
+        }
+    }
+
+    /// Returns human-readable output label from the [`PSBT_OUT_LABEL`]
+    /// proprietary key, if present. Labels which are not valid UTF-8 strings
+    /// are ignored.
+    pub fn label(&self) -> Option<String> {
+        self.proprietary
+            .get(&Output::label_key())
+            .and_then(|data| String::from_utf8(data.clone()).ok())
+    }
+
+    /// Sets human-readable output label, putting it into the
+    /// [`PSBT_OUT_LABEL`] proprietary key.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`LabelTooLong`] if the label is longer than
+    /// [`MAX_LABEL_LEN`] bytes.
+    pub fn set_label(&mut self, label: &str) -> Result<(), LabelTooLong> {
+        if label.len() > MAX_LABEL_LEN {
+            return Err(LabelTooLong(label.len()));
+        }
+        self.proprietary
+            .insert(Output::label_key(), label.as_bytes().to_vec());
+        Ok(())
+    }
+}
+
+impl Input {
+    fn source_account_key() -> ProprietaryKey {
+        ProprietaryKey {
+            prefix: PSBT_LNPBP_PREFIX.to_vec(),
+            subtype: PSBT_IN_SOURCE_ACCOUNT,
+            key: vec![],
+        }
+    }
+
+    /// Returns index of the account the input is spent from, as recorded in
+    /// the [`PSBT_IN_SOURCE_ACCOUNT`] proprietary key. Malformed values are
+    /// ignored.
+    pub fn source_account(&self) -> Option<HardenedIndex> {
+        let data = self.proprietary.get(&Input::source_account_key())?;
+        let index = u32::from_le_bytes(<[u8; 4]>::try_from(data.as_slice()).ok()?);
+        HardenedIndex::from_index(index).ok()
+    }
+
+    /// Records index of the account the input is spent from in the
+    /// [`PSBT_IN_SOURCE_ACCOUNT`] proprietary key, allowing to route signing of
+    /// multi-account PSBTs to the key of that account.
+    pub fn set_source_account(&mut self, account: HardenedIndex) {
+        self.proprietary.insert(
+            Input::source_account_key(),
+            account.first_index().to_le_bytes().to_vec(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serialize::{Deserialize, Serialize};
+    use crate::PsbtVersion;
+
+    #[test]
+    fn proprietary_key_with() {
+        let key = ProprietaryKey::with(b"LNPBP", 7, vec![0xde, 0xad]).unwrap();
+        assert_eq!(key, ProprietaryKey {
+            prefix: b"LNPBP".to_vec(),
+            subtype: 7,
+            key: vec![0xde, 0xad],
+        });
+        assert_eq!(ProprietaryKey::from_key(key.to_key()).unwrap(), key);
+        assert_eq!(
+            ProprietaryKey::with("", 7, vec![]),
+            Err(ProprietaryKeyError::EmptyPrefix)
+        );
+
+        let mut psbt = Psbt::default();
+        psbt.proprietary_mut().insert(key.clone(), vec![1, 2, 3]);
+        let psbt = Psbt::deserialize(&psbt.serialize()).unwrap();
+        assert_eq!(psbt.proprietary.get(&key), Some(&vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn raw_key_hex() {
+        let key = raw::Key {
+            type_value: 0x0e,
+            key: vec![0xde, 0xad, 0xbe, 0xef],
+        };
+        assert_eq!(RawKeyExt::to_hex(&key), "0edeadbeef");
+        assert_eq!(
+            <raw::Key as RawKeyExt>::from_hex("0edeadbeef").unwrap(),
+            key
+        );
+
+        let key = raw::Key {
+            type_value: 0x00,
+            key: vec![],
+        };
+        assert_eq!(RawKeyExt::to_hex(&key), "00");
+        assert_eq!(<raw::Key as RawKeyExt>::from_hex("00").unwrap(), key);
+
+        assert_eq!(
+            <raw::Key as RawKeyExt>::from_hex(""),
+            Err(KeyHexError::Empty)
+        );
+        assert_eq!(
+            <raw::Key as RawKeyExt>::from_hex("0g"),
+            Err(KeyHexError::Hex)
+        );
+        assert_eq!(
+            <raw::Key as RawKeyExt>::from_hex("0ed"),
+            Err(KeyHexError::Hex)
+        );
+    }
+
+    #[test]
+    fn proprietary_key_hex() {
+        let key = ProprietaryKey::with(b"LNPBP", 7, vec![0xde, 0xad]).unwrap();
+        let hex = ProprietaryKeyExt::to_hex(&key);
+        assert_eq!(hex, "fc054c4e50425007dead");
+        assert_eq!(
+            <ProprietaryKey as ProprietaryKeyExt>::from_hex(&hex).unwrap(),
+            key
+        );
+        assert_eq!(
+            <raw::Key as RawKeyExt>::from_hex(&hex).unwrap(),
+            key.to_key()
+        );
+
+        let key = ProprietaryKey::with(b"DBC", 0, vec![]).unwrap();
+        let hex = ProprietaryKeyExt::to_hex(&key);
+        assert_eq!(hex, "fc0344424300");
+        assert_eq!(
+            <ProprietaryKey as ProprietaryKeyExt>::from_hex(&hex).unwrap(),
+            key
+        );
+
+        // Not a proprietary key type
+        assert_eq!(
+            <ProprietaryKey as ProprietaryKeyExt>::from_hex("0e054c4e50425007dead"),
+            Err(KeyHexError::InvalidProprietaryKey)
+        );
+        // Prefix length exceeds the key data
+        assert_eq!(
+            <ProprietaryKey as ProprietaryKeyExt>::from_hex("fc0a4c4e50425007"),
+            Err(KeyHexError::InvalidProprietaryKey)
+        );
+        // Missing subtype
+        assert_eq!(
+            <ProprietaryKey as ProprietaryKeyExt>::from_hex("fc054c4e504250"),
+            Err(KeyHexError::InvalidProprietaryKey)
+        );
+        assert_eq!(
+            <ProprietaryKey as ProprietaryKeyExt>::from_hex(""),
+            Err(KeyHexError::Empty)
+        );
+    }
+
+    #[test]
+    fn output_label() {
+        let tx = bitcoin::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![bitcoin::TxOut {
+                value: 1000,
+                script_pubkey: Default::default(),
+            }],
+        };
+        let mut psbt = Psbt::with(tx, PsbtVersion::V0).unwrap();
+        assert_eq!(psbt.outputs[0].label(), None);
+
+        psbt.outputs[0].set_label("Savings ☂").unwrap();
+        assert_eq!(psbt.outputs[0].label().as_deref(), Some("Savings ☂"));
+        assert_eq!(
+            psbt.outputs[0].set_label(&"x".repeat(MAX_LABEL_LEN + 1)),
+            Err(LabelTooLong(MAX_LABEL_LEN + 1))
+        );
+        assert_eq!(
+            LabelTooLong(MAX_LABEL_LEN + 1).to_string(),
+            format!(
+                "output label of {} bytes exceeds the maximum length of {} bytes",
+                MAX_LABEL_LEN + 1,
+                MAX_LABEL_LEN
+            )
+        );
+        assert_eq!(psbt.outputs[0].label().as_deref(), Some("Savings ☂"));
+
+        let hex = ProprietaryKeyExt::to_hex(&Output::label_key());
+        assert_eq!(hex, "fc054c4e50425000");
+
+        for version in [PsbtVersion::V0, PsbtVersion::V2] {
+            psbt.psbt_version = version;
+            let psbt = Psbt::deserialize(&psbt.serialize()).unwrap();
+            assert_eq!(psbt.outputs[0].label().as_deref(), Some("Savings ☂"));
+        }
+    }
+
+    #[test]
+    fn input_source_account() {
+        let tx = bitcoin::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![],
+        };
+        let mut psbt = Psbt::with(tx, PsbtVersion::V2).unwrap();
+        let unsigned_tx = psbt.to_unsigned_tx();
+        assert_eq!(psbt.inputs[0].source_account(), None);
+
+        let account = HardenedIndex::from(7u8);
+        psbt.inputs[0].set_source_account(account);
+        assert_eq!(psbt.inputs[0].source_account(), Some(account));
+        assert_eq!(psbt.to_unsigned_tx(), unsigned_tx);
+        assert_eq!(
+            psbt.inputs[0].proprietary[&Input::source_account_key()],
+            vec![7, 0, 0, 0]
+        );
+
+        for version in [PsbtVersion::V0, PsbtVersion::V2] {
+            psbt.psbt_version = version;
+            let psbt = Psbt::deserialize(&psbt.serialize()).unwrap();
+            assert_eq!(psbt.inputs[0].source_account(), Some(account));
+            assert_eq!(psbt.to_unsigned_tx(), unsigned_tx);
+        }
+
+        psbt.inputs[0]
+            .proprietary
+            .insert(Input::source_account_key(), vec![7]);
+        assert_eq!(psbt.inputs[0].source_account(), None);
+    }
+}
@@ -0,0 +1,236 @@
+// Descriptor wallet library extending bitcoin & miniscript functionality
+// by LNP/BP Association (https://lnp-bp.org)
+// Written in 2020-2022 by
+//     Dr. Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the Apache-2.0 License
+// along with this software.
+// If not, see <https://opensource.org/licenses/Apache-2.0>.
+
+//! Incremental PSBT decoding, allowing to process PSBT inputs and outputs
+//! one-by-one without keeping the whole deserialized structure in memory.
+
+use std::io;
+
+use bitcoin::consensus::encode::{self, Decodable, Encodable};
+use bitcoin::Transaction;
+
+use crate::global::PSBT_GLOBAL_UNSIGNED_TX;
+use crate::v0::{InputV0, OutputV0, PsbtV0};
+use crate::{raw, v2, Input, Output, Psbt, PsbtVersion};
+
+/// Incremental PSBT decoder.
+///
+/// On construction, the reader decodes PSBT magic and the global map, and then
+/// yields PSBT inputs and outputs one by one with [`PsbtReader::read_input`]
+/// and [`PsbtReader::read_output`]. The number of inputs and outputs is defined
+/// by the unsigned transaction from the global map for PSBTv0, and by
+/// `PSBT_GLOBAL_INPUT_COUNT` and `PSBT_GLOBAL_OUTPUT_COUNT` fields for PSBTv2.
+///
+/// All inputs must be read before reading outputs.
+#[derive(Debug)]
+pub struct PsbtReader<R: io::Read> {
+    reader: R,
+    global: Psbt,
+    /// Unsigned transaction of PSBTv0, which is absent in PSBTv2.
+    unsigned_tx: Option<Transaction>,
+    input_count: usize,
+    output_count: usize,
+    next_input: usize,
+    next_output: usize,
+}
+
+impl<R: io::Read> PsbtReader<R> {
+    /// Decodes PSBT magic and global map from the provided reader, returning
+    /// PSBT reader ready to decode PSBT inputs.
+    pub fn new(mut reader: R) -> Result<Self, encode::Error> {
+        let magic: [u8; 4] = Decodable::consensus_decode(&mut reader)?;
+        if *b"psbt" != magic {
+            return Err(crate::Error::InvalidMagic.into());
+        }
+        if 0xff_u8 != u8::consensus_decode(&mut reader)? {
+            return Err(crate::Error::InvalidSeparator.into());
+        }
+
+        let mut data = b"psbt\xff".to_vec();
+        let mut pairs = vec![];
+        loop {
+            match raw::Pair::consensus_decode(&mut reader) {
+                Ok(pair) => {
+                    pair.consensus_encode(&mut data)?;
+                    pairs.push(pair);
+                }
+                Err(encode::Error::Psbt(crate::Error::NoMorePairs)) => break,
+                Err(err) => return Err(err),
+            }
+        }
+        data.push(0x00);
+
+        let (global, unsigned_tx, input_count, output_count) = match v2::decode_version(&data)? {
+            PsbtVersion::V0 => {
+                // The global map is validated and decoded by the eager PSBT
+                // decoder applied to the global map followed by empty input
+                // and output maps.
+                let pair = pairs
+                    .iter()
+                    .find(|pair| {
+                        pair.key.type_value == PSBT_GLOBAL_UNSIGNED_TX && pair.key.key.is_empty()
+                    })
+                    .ok_or(crate::Error::MustHaveUnsignedTx)?;
+                let unsigned_tx = encode::deserialize::<Transaction>(&pair.value)?;
+                let (input_count, output_count) =
+                    (unsigned_tx.input.len(), unsigned_tx.output.len());
+                data.resize(data.len() + input_count + output_count, 0x00);
+                let mut v0 = encode::deserialize::<PsbtV0>(&data)?;
+                v0.unsigned_tx.input = vec![];
+                v0.unsigned_tx.output = vec![];
+                v0.inputs = vec![];
+                v0.outputs = vec![];
+                (
+                    Psbt::try_from(v0)?,
+                    Some(unsigned_tx),
+                    input_count,
+                    output_count,
+                )
+            }
+            PsbtVersion::V2 => {
+                let (global, input_count, output_count) = v2::decode_global_pairs(pairs)?;
+                (global, None, input_count, output_count)
+            }
+        };
+
+        Ok(PsbtReader {
+            reader,
+            global,
+            unsigned_tx,
+            input_count,
+            output_count,
+            next_input: 0,
+            next_output: 0,
+        })
+    }
+
+    /// Returns PSBT global data. The returned [`Psbt`] has no inputs and
+    /// outputs, which must be read with [`PsbtReader::read_input`] and
+    /// [`PsbtReader::read_output`].
+    #[inline]
+    pub fn global(&self) -> &Psbt { &self.global }
+
+    /// Returns total number of inputs in the PSBT.
+    #[inline]
+    pub fn input_count(&self) -> usize { self.input_count }
+
+    /// Returns total number of outputs in the PSBT.
+    #[inline]
+    pub fn output_count(&self) -> usize { self.output_count }
+
+    /// Decodes next PSBT input, returning `None` if all inputs were already
+    /// read.
+    pub fn read_input(&mut self) -> Result<Option<Input>, encode::Error> {
+        let index = self.next_input;
+        if index >= self.input_count {
+            return Ok(None);
+        }
+        let input = match self.unsigned_tx {
+            Some(ref tx) => {
+                let v0 = InputV0::consensus_decode(&mut self.reader)?;
+                Input::with(index, v0, tx.input[index].clone())
+            }
+            None => v2::decode_input(index, &mut self.reader)?,
+        };
+        self.next_input += 1;
+        Ok(Some(input))
+    }
+
+    /// Decodes next PSBT output, returning `None` if all outputs were already
+    /// read.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`encode::Error::ParseFailed`] if not all of the PSBT inputs
+    /// were read before.
+    pub fn read_output(&mut self) -> Result<Option<Output>, encode::Error> {
+        if self.next_input < self.input_count() {
+            return Err(encode::Error::ParseFailed(
+                "all PSBT inputs must be read before PSBT outputs",
+            ));
+        }
+        let index = self.next_output;
+        if index >= self.output_count {
+            return Ok(None);
+        }
+        let output = match self.unsigned_tx {
+            Some(ref tx) => {
+                let v0 = OutputV0::consensus_decode(&mut self.reader)?;
+                Output::with(index, v0, tx.output[index].clone())?
+            }
+            None => v2::decode_output(index, &mut self.reader)?,
+        };
+        self.next_output += 1;
+        Ok(Some(output))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::serialize::{Deserialize, Serialize};
+
+    fn check_streamed_equals_eager(psbt: &Psbt) {
+        let data = psbt.serialize();
+
+        let mut reader = PsbtReader::new(&data[..]).unwrap();
+        assert_eq!(reader.input_count(), 1);
+        assert_eq!(reader.output_count(), 2);
+        assert!(reader.read_output().is_err());
+
+        let mut streamed = reader.global().clone();
+        while let Some(input) = reader.read_input().unwrap() {
+            streamed.inputs.push(input);
+        }
+        while let Some(output) = reader.read_output().unwrap() {
+            streamed.outputs.push(output);
+        }
+        assert_eq!(reader.read_input().unwrap(), None);
+        assert_eq!(&streamed, psbt);
+        assert_eq!(streamed, Psbt::deserialize(&data).unwrap());
+
+        assert!(PsbtReader::new(&data[1..]).is_err());
+        let mut reader = PsbtReader::new(&data[..data.len() - 1]).unwrap();
+        assert!(reader.read_input().is_ok());
+        assert!(reader.read_output().is_ok());
+        assert!(reader.read_output().is_err());
+    }
+
+    #[test]
+    fn streamed_equals_eager() {
+        let mut psbt = Psbt::from_str(
+            "70736274ff0100750200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42\
+             171bf60000000000feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa\
+             7652088ac00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787b32e130000\
+             0100fda5010100000000010289a3c71eab4d20e0371bbba4cc698fa295c9463afa2e397f8533ccb62f9\
+             567e50100000017160014be18d152a9b012039daf3da7de4f53349eecb985ffffffff86f8aa43a71dff1\
+             448893a530a7237ef6b4608bbb2dd2d0171e63aec6a4890b40100000017160014fe3e9ef1a745e974d90\
+             2c4355943abcb34bd5353ffffffff0200c2eb0b000000001976a91485cff1097fd9e008bb34af709c621\
+             97b38978a4888ac72fef84e2c00000017a914339725ba21efd62ac753a9bcd067d6c7a6a39d058702473\
+             04402202712be22e0270f394f568311dc7ca9a68970b8025fdd3b240229f07f8a5f3a240220018b38d7d\
+             cd314e734c9276bd6fb40f673325bc4baa144c800d2f2f02db2765c012103d2e15674941bad4a996372c\
+             b87e1856d3652606d98562fe39c5e9e7e413f210502483045022100d12b852d85dcd961d2f5f4ab66065\
+             4df6eedcc794c0c33ce5cc309ffb5fce58d022067338a8e0e1725c197fb1a88af59f51e44e4255b20167\
+             c8684031c05d1f2592a01210223b72beef0965d10be0778efecd61fcac6f79a4ea169393380734464f84\
+             f2ab300000000000000",
+        )
+        .unwrap();
+        check_streamed_equals_eager(&psbt);
+
+        psbt.psbt_version = PsbtVersion::V2;
+        check_streamed_equals_eager(&psbt);
+    }
+}
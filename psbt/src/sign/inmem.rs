@@ -26,6 +26,7 @@ use bitcoin_hd::{Bip43, DerivationStandard};
 use miniscript::Descriptor;
 
 use super::{SecretProvider, SecretProviderError};
+use crate::fingerprint_eq_ct;
 
 /// Account-specific extended private key, kept in memory with information about
 /// account path derivation from the master key.
@@ -210,18 +211,19 @@ where
         pubkey: PublicKey,
     ) -> Result<SecretKey, SecretProviderError> {
         for account in &self.accounts {
-            let derivation = if account.account_fingerprint() == fingerprint {
+            let derivation = if fingerprint_eq_ct(&account.account_fingerprint(), &fingerprint) {
                 derivation.clone()
-            } else if account.master_fingerprint() == fingerprint {
-                let mut iter = account.derivation.into_iter();
-                let remaining_derivation = derivation
-                    .into_iter()
-                    .skip_while(|child| Some(*child) == iter.next());
-                let remaining_derivation = remaining_derivation.cloned().collect();
-                if iter.count() > 0 {
-                    continue;
+            } else if fingerprint_eq_ct(&account.master_fingerprint(), &fingerprint) {
+                // Only the part of the path below the account key is derived;
+                // paths not going through the account key are skipped without
+                // any derivation
+                match derivation
+                    .as_ref()
+                    .strip_prefix(account.derivation.as_ref())
+                {
+                    Some(remaining_derivation) => DerivationPath::from(remaining_derivation),
+                    None => continue,
                 }
-                remaining_derivation
             } else {
                 continue;
             };
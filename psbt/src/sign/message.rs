@@ -0,0 +1,410 @@
+// Descriptor wallet library extending bitcoin & miniscript functionality
+// by LNP/BP Association (https://lnp-bp.org)
+// Written in 2020-2022 by
+//     Dr. Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the Apache-2.0 License
+// along with this software.
+// If not, see <https://opensource.org/licenses/Apache-2.0>.
+
+//! Signing and verification of arbitrary messages according to BIP-322 using
+//! the "simple" signature format.
+
+use bitcoin::blockdata::opcodes::all::{OP_PUSHBYTES_0, OP_RETURN};
+use bitcoin::blockdata::script;
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{
+    self, KeyPair, Message, Secp256k1, SecretKey, Signing, Verification, XOnlyPublicKey,
+};
+use bitcoin::util::bip32::{ExtendedPrivKey, ExtendedPubKey};
+use bitcoin::util::schnorr::TapTweak;
+use bitcoin::util::sighash::{self, Prevouts, SighashCache};
+use bitcoin::{
+    base64, Address, EcdsaSig, EcdsaSighashType, OutPoint, PublicKey, SchnorrSig,
+    SchnorrSighashType, Script, Transaction, TxIn, TxOut, Witness,
+};
+use bitcoin_hd::{Bip43, DerivationAccount, DerivePatternError, SegmentIndexes, UnhardenedIndex};
+
+use super::SignOptions;
+
+const MESSAGE_TAG: &[u8] = b"BIP0322-signed-message";
+
+/// Errors signing and verifying messages according to BIP-322
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum MessageError {
+    /// derivation scheme {0} is not supported by BIP-322 simple signatures,
+    /// which require BIP-84 or BIP-86 single-key addresses
+    UnsupportedScheme(Bip43),
+
+    /// extended private key does not correspond to the account extended
+    /// public key
+    XprvMismatch,
+
+    /// index {0} is outside of the unhardened index range
+    HardenedIndex(u32),
+
+    /// unable to derive signing key: {0}
+    #[from]
+    DerivePattern(DerivePatternError),
+
+    /// unable to derive private key: elliptic curve prime field order (`p`)
+    /// overflow or derivation resulting at the point-at-infinity
+    SecpPrivkeyDerivation,
+
+    /// unable to compute signature hash: {0}
+    #[from]
+    Sighash(sighash::Error),
+
+    /// address type is not supported by BIP-322 simple signatures
+    UnsupportedAddress,
+
+    /// signature is not a valid BIP-322 simple signature encoding
+    MalformedSignature,
+
+    /// signature does not match the address and the message
+    InvalidSignature,
+}
+
+/// Computes BIP-322 tagged hash of the message.
+fn message_hash(message: &str) -> sha256::Hash {
+    let tag = sha256::Hash::hash(MESSAGE_TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tag[..]);
+    engine.input(&tag[..]);
+    engine.input(message.as_bytes());
+    sha256::Hash::from_engine(engine)
+}
+
+/// Constructs BIP-322 virtual `to_spend` transaction committing to the
+/// message and the challenge `scriptPubkey`.
+fn to_spend(message: &str, script_pubkey: &Script) -> Transaction {
+    let script_sig = script::Builder::new()
+        .push_opcode(OP_PUSHBYTES_0)
+        .push_slice(&message_hash(message)[..])
+        .into_script();
+    Transaction {
+        version: 0,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig,
+            sequence: 0,
+            witness: empty!(),
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: script_pubkey.clone(),
+        }],
+    }
+}
+
+/// Constructs BIP-322 virtual `to_sign` transaction spending the output of
+/// the `to_spend` transaction, without the witness.
+fn to_sign(to_spend: &Transaction) -> Transaction {
+    Transaction {
+        version: 0,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(to_spend.txid(), 0),
+            script_sig: empty!(),
+            sequence: 0,
+            witness: empty!(),
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: script::Builder::new().push_opcode(OP_RETURN).into_script(),
+        }],
+    }
+}
+
+/// Signs `message` with the secret key according to BIP-322, producing the
+/// base64-encoded "simple" signature for the single-key address of the
+/// derivation `scheme`. Only segwit schemes, i.e. BIP-84 (P2WPKH) and BIP-86
+/// (P2TR key path spending), are supported, since the simple signature format
+/// has no place for the `scriptSig`.
+///
+/// # Errors
+///
+/// Errors with [`MessageError::UnsupportedScheme`] for unsupported schemes.
+pub fn sign_message_with_key<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    seckey: &SecretKey,
+    scheme: Bip43,
+    message: &str,
+    options: &SignOptions,
+) -> Result<String, MessageError> {
+    let pubkey = PublicKey::new(secp256k1::PublicKey::from_secret_key(secp, seckey));
+    let keypair = KeyPair::from_secret_key(secp, *seckey);
+    let script_pubkey = match scheme {
+        Bip43::Bip84 => Script::new_v0_p2wpkh(
+            &pubkey
+                .wpubkey_hash()
+                .expect("secp256k1 public keys are always compressed"),
+        ),
+        Bip43::Bip86 => Script::new_v1_p2tr(secp, XOnlyPublicKey::from_keypair(&keypair), None),
+        _ => return Err(MessageError::UnsupportedScheme(scheme)),
+    };
+    let to_spend = to_spend(message, &script_pubkey);
+    let mut to_sign = to_sign(&to_spend);
+    let mut sig_hasher = SighashCache::new(&to_sign);
+    let witness = if scheme == Bip43::Bip84 {
+        let script_code = Script::new_p2pkh(&pubkey.pubkey_hash());
+        let sighash = sig_hasher
+            .segwit_signature_hash(0, &script_code, 0, EcdsaSighashType::All)
+            .expect("BIP-322 transaction always has the signed input");
+        let msg = Message::from_slice(&sighash[..]).expect("sighash generation is broken");
+        let sig = EcdsaSig::sighash_all(options.sign_ecdsa(secp, &msg, seckey));
+        vec![sig.to_vec(), pubkey.to_bytes()]
+    } else {
+        let sighash = sig_hasher.taproot_key_spend_signature_hash(
+            0,
+            &Prevouts::All(&to_spend.output),
+            SchnorrSighashType::Default,
+        )?;
+        let msg = Message::from_slice(&sighash[..]).expect("sighash generation is broken");
+        let keypair = keypair.tap_tweak(secp, None).into_inner();
+        let sig = SchnorrSig {
+            sig: options.sign_schnorr(secp, &msg, &keypair),
+            hash_ty: SchnorrSighashType::Default,
+        };
+        vec![sig.to_vec()]
+    };
+    to_sign.input[0].witness = Witness::from_vec(witness);
+    Ok(base64::encode(&serialize(&to_sign.input[0].witness)))
+}
+
+/// Extension trait for signing messages with the keys derived from the
+/// accounts (see [`sign_message_with_key`]).
+pub trait SignMessage {
+    /// Signs `message` according to BIP-322 with the key derived at `index`
+    /// from the account-level extended private key `xprv`, producing the
+    /// base64-encoded "simple" signature for the address of the derivation
+    /// `scheme`. ECDSA signatures are ground to low `R` values, as done by
+    /// Bitcoin Core.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`MessageError::XprvMismatch`] if `xprv` does not
+    /// correspond to the account extended public key, with
+    /// [`MessageError::DerivePattern`] if the account derive pattern is not a
+    /// single index and with [`MessageError::UnsupportedScheme`] if the scheme
+    /// is not supported.
+    fn sign_message<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        xprv: &ExtendedPrivKey,
+        scheme: Bip43,
+        index: u32,
+        message: &str,
+    ) -> Result<String, MessageError>;
+}
+
+impl SignMessage for DerivationAccount {
+    fn sign_message<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        xprv: &ExtendedPrivKey,
+        scheme: Bip43,
+        index: u32,
+        message: &str,
+    ) -> Result<String, MessageError> {
+        let xpub = ExtendedPubKey::from_priv(secp, xprv);
+        if (xpub.public_key, xpub.chain_code)
+            != (self.account_xpub.public_key, self.account_xpub.chain_code)
+        {
+            return Err(MessageError::XprvMismatch);
+        }
+        let index =
+            UnhardenedIndex::from_index(index).map_err(|_| MessageError::HardenedIndex(index))?;
+        let path = self.to_terminal_derivation_path([index])?;
+        let seckey = xprv
+            .derive_priv(secp, &path)
+            .map_err(|_| MessageError::SecpPrivkeyDerivation)?
+            .private_key;
+        let options = SignOptions {
+            low_r: true,
+            ..SignOptions::with_random_aux()
+        };
+        sign_message_with_key(secp, &seckey, scheme, message, &options)
+    }
+}
+
+/// Verifies BIP-322 "simple" signature of the `message` for the `address`.
+/// Supports P2WPKH and P2TR key path spending addresses.
+///
+/// # Errors
+///
+/// Errors with [`MessageError::UnsupportedAddress`] for other address types,
+/// with [`MessageError::MalformedSignature`] if the signature can't be decoded
+/// into a witness of the address type and with
+/// [`MessageError::InvalidSignature`] if the signature does not match the
+/// address and the message.
+pub fn verify_message<C: Verification>(
+    secp: &Secp256k1<C>,
+    address: &Address,
+    message: &str,
+    signature: &str,
+) -> Result<(), MessageError> {
+    let script_pubkey = address.script_pubkey();
+    if !script_pubkey.is_v0_p2wpkh() && !script_pubkey.is_v1_p2tr() {
+        return Err(MessageError::UnsupportedAddress);
+    }
+    let witness = base64::decode(signature)
+        .ok()
+        .and_then(|data| deserialize::<Witness>(&data).ok())
+        .ok_or(MessageError::MalformedSignature)?
+        .to_vec();
+
+    let to_spend = to_spend(message, &script_pubkey);
+    let to_sign = to_sign(&to_spend);
+    let mut sig_hasher = SighashCache::new(&to_sign);
+    match &witness[..] {
+        [sig, pubkey] if script_pubkey.is_v0_p2wpkh() => {
+            let sig = EcdsaSig::from_slice(sig).map_err(|_| MessageError::MalformedSignature)?;
+            let pubkey =
+                PublicKey::from_slice(pubkey).map_err(|_| MessageError::MalformedSignature)?;
+            match pubkey.wpubkey_hash() {
+                Some(hash) if Script::new_v0_p2wpkh(&hash) == script_pubkey => {}
+                _ => return Err(MessageError::InvalidSignature),
+            }
+            let script_code = Script::new_p2pkh(&pubkey.pubkey_hash());
+            let sighash = sig_hasher
+                .segwit_signature_hash(0, &script_code, 0, sig.hash_ty)
+                .expect("BIP-322 transaction always has the signed input");
+            let msg = Message::from_slice(&sighash[..]).expect("sighash generation is broken");
+            secp.verify_ecdsa(&msg, &sig.sig, &pubkey.inner)
+                .map_err(|_| MessageError::InvalidSignature)
+        }
+        [sig] if script_pubkey.is_v1_p2tr() => {
+            let sig = SchnorrSig::from_slice(sig).map_err(|_| MessageError::MalformedSignature)?;
+            let output_key = XOnlyPublicKey::from_slice(&script_pubkey[2..])
+                .map_err(|_| MessageError::InvalidSignature)?;
+            let sighash = sig_hasher
+                .taproot_key_spend_signature_hash(0, &Prevouts::All(&to_spend.output), sig.hash_ty)
+                .map_err(|_| MessageError::MalformedSignature)?;
+            let msg = Message::from_slice(&sighash[..]).expect("sighash generation is broken");
+            secp.verify_schnorr(&sig.sig, &msg, &output_key)
+                .map_err(|_| MessageError::InvalidSignature)
+        }
+        _ => Err(MessageError::MalformedSignature),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bitcoin::hashes::hex::ToHex;
+    use bitcoin::Network;
+
+    use super::*;
+
+    // Test vectors from BIP-322
+    const ADDRESS: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+    const PRIVKEY: &str = "L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k";
+    const EMPTY_SIG: &str = "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+    const HELLO_SIG: &str = "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/\
+                             ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/\
+                             EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+
+    #[test]
+    fn virtual_txes() {
+        let script_pubkey = Address::from_str(ADDRESS).unwrap().script_pubkey();
+        for (message, hash, to_spend_txid, to_sign_txid) in [
+            (
+                "",
+                "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1",
+                "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7",
+                "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6",
+            ),
+            (
+                "Hello World",
+                "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a",
+                "b79d196740ad5217771c1098fc4a4b51e0535c32236c71f1ea4d61a2d603352b",
+                "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf",
+            ),
+        ] {
+            assert_eq!(message_hash(message).to_hex(), hash);
+            let to_spend = to_spend(message, &script_pubkey);
+            assert_eq!(to_spend.txid().to_hex(), to_spend_txid);
+            assert_eq!(to_sign(&to_spend).txid().to_hex(), to_sign_txid);
+        }
+    }
+
+    #[test]
+    fn bip322_vectors() {
+        let secp = Secp256k1::new();
+        let address = Address::from_str(ADDRESS).unwrap();
+        let seckey = bitcoin::PrivateKey::from_wif(PRIVKEY).unwrap().inner;
+        let options = SignOptions {
+            low_r: true,
+            ..SignOptions::default()
+        };
+        for (message, signature) in [("", EMPTY_SIG), ("Hello World", HELLO_SIG)] {
+            assert_eq!(
+                sign_message_with_key(&secp, &seckey, Bip43::Bip84, message, &options).unwrap(),
+                signature
+            );
+            verify_message(&secp, &address, message, signature).unwrap();
+        }
+        assert!(matches!(
+            verify_message(&secp, &address, "Hello World", EMPTY_SIG),
+            Err(MessageError::InvalidSignature)
+        ));
+        assert!(matches!(
+            verify_message(&secp, &address, "", "AAAA"),
+            Err(MessageError::MalformedSignature)
+        ));
+        let legacy = Address::from_str("1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA").unwrap();
+        assert!(matches!(
+            verify_message(&secp, &legacy, "", EMPTY_SIG),
+            Err(MessageError::UnsupportedAddress)
+        ));
+    }
+
+    #[test]
+    fn account_sign_message() {
+        let secp = Secp256k1::new();
+        let master = ExtendedPrivKey::new_master(Network::Bitcoin, &[1u8; 32]).unwrap();
+        for (scheme, path) in [(Bip43::Bip84, "m/84'/0'/0'"), (Bip43::Bip86, "m/86'/0'/0'")] {
+            let xprv = master
+                .derive_priv(
+                    &secp,
+                    &bitcoin::util::bip32::DerivationPath::from_str(path).unwrap(),
+                )
+                .unwrap();
+            let account = DerivationAccount::from_str(&format!(
+                "{}/0/*",
+                ExtendedPubKey::from_priv(&secp, &xprv)
+            ))
+            .unwrap();
+            let address = account
+                .address(
+                    &secp,
+                    scheme,
+                    [UnhardenedIndex::from(5u8)],
+                    Network::Bitcoin,
+                )
+                .unwrap();
+            let signature = account
+                .sign_message(&secp, &xprv, scheme, 5, "message")
+                .unwrap();
+            verify_message(&secp, &address, "message", &signature).unwrap();
+            assert!(matches!(
+                verify_message(&secp, &address, "other message", &signature),
+                Err(MessageError::InvalidSignature)
+            ));
+            assert!(matches!(
+                account.sign_message(&secp, &master, scheme, 5, "message"),
+                Err(MessageError::XprvMismatch)
+            ));
+        }
+    }
+}
@@ -16,16 +16,83 @@
 
 // TODO: Add Hash secret provider and hash secret satisfaction
 
-use bitcoin::secp256k1::{KeyPair, PublicKey, Secp256k1, SecretKey, Signing, XOnlyPublicKey};
+use bitcoin::secp256k1::rand::{self, RngCore};
+use bitcoin::secp256k1::{
+    ecdsa, schnorr, KeyPair, Message, PublicKey, Secp256k1, SecretKey, Signing, XOnlyPublicKey,
+};
 use bitcoin::util::bip32::{DerivationPath, Fingerprint};
 
 mod inmem;
 #[cfg(feature = "miniscript")]
+mod message;
+#[cfg(feature = "miniscript")]
 mod signer;
+#[cfg(feature = "miniscript")]
+mod verify;
 
 pub use inmem::{MemoryKeyProvider, MemorySigningAccount};
 #[cfg(feature = "miniscript")]
-pub use signer::{SignAll, SignError, SignInputError};
+pub use message::{sign_message_with_key, verify_message, MessageError, SignMessage};
+#[cfg(feature = "miniscript")]
+pub use signer::{PsbtSigner, SignAll, SignError, SignInputError};
+#[cfg(feature = "miniscript")]
+pub use verify::VerifyError;
+
+/// Options controlling creation of signatures.
+///
+/// ECDSA signatures always use deterministic RFC6979 nonces.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct SignOptions {
+    /// Auxiliary randomness used in BIP-340 Schnorr signing (taproot). If
+    /// `None`, no auxiliary randomness is used and signatures are fully
+    /// deterministic, which is useful for producing reproducible test
+    /// vectors.
+    pub aux_rand: Option<[u8; 32]>,
+
+    /// Grind ECDSA signatures to have low `R` value, reducing signature size
+    /// by one byte (as done by Bitcoin Core).
+    pub low_r: bool,
+}
+
+impl SignOptions {
+    /// Constructs options using fresh auxiliary randomness for Schnorr
+    /// signatures and no low-`R` grinding for ECDSA signatures.
+    pub fn with_random_aux() -> SignOptions {
+        let mut aux_rand = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut aux_rand);
+        SignOptions {
+            aux_rand: Some(aux_rand),
+            low_r: false,
+        }
+    }
+
+    /// Creates ECDSA signature according to the options.
+    pub fn sign_ecdsa<C: Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        msg: &Message,
+        seckey: &SecretKey,
+    ) -> ecdsa::Signature {
+        if self.low_r {
+            secp.sign_ecdsa_low_r(msg, seckey)
+        } else {
+            secp.sign_ecdsa(msg, seckey)
+        }
+    }
+
+    /// Creates BIP-340 Schnorr signature according to the options.
+    pub fn sign_schnorr<C: Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        msg: &Message,
+        keypair: &KeyPair,
+    ) -> schnorr::Signature {
+        match self.aux_rand {
+            Some(ref aux_rand) => secp.sign_schnorr_with_aux_rand(msg, keypair, aux_rand),
+            None => secp.sign_schnorr_no_aux_rand(msg, keypair),
+        }
+    }
+}
 
 /// Errors returned by secret providers (see [`SecretProvider`])
 #[derive(
@@ -15,6 +15,7 @@
 //! Functions, errors and traits specific for PSBT signer role.
 
 use core::ops::Deref;
+use std::rc::Rc;
 
 use amplify::Wrapper;
 use bitcoin::hashes::Hash;
@@ -25,13 +26,13 @@ use bitcoin::util::sighash::{self, Prevouts, ScriptPath, SighashCache};
 use bitcoin::util::taproot::TapLeafHash;
 use bitcoin::{
     EcdsaSig, EcdsaSighashType, PubkeyHash, PublicKey, SchnorrSig, SchnorrSighashType, Script,
-    Transaction, TxOut,
+    Sighash, Transaction, TxOut,
 };
 use bitcoin_scripts::PubkeyScript;
 use descriptors::{self, CompositeDescrType};
 use miniscript::{Miniscript, ToPublicKey};
 
-use super::SecretProvider;
+use super::{SecretProvider, SignOptions};
 use crate::{DeductionError, Input, InputMatchError, Psbt};
 
 /// Errors happening during whole PSBT signing process
@@ -132,6 +133,14 @@ pub enum SignInputError {
     /// trying to add to aggregated signature another signature with non-unique
     /// nonce value (previous `s` value is {0}, added nonce value is {1:02x?}).
     RepeatedSigNonce(String, Box<[u8]>),
+
+    /// input spending {0} does not belong to the transaction known to the
+    /// signer
+    UnknownInput(bitcoin::OutPoint),
+
+    /// unable to derive signing key or script: {0}
+    #[from]
+    Derive(bitcoin_hd::DeriveError),
 }
 
 impl std::error::Error for SignInputError {
@@ -157,6 +166,8 @@ impl std::error::Error for SignInputError {
             SignInputError::NonStandardSighashType { .. } => None,
             SignInputError::RepeatedSig(..) => None,
             SignInputError::RepeatedSigNonce(..) => None,
+            SignInputError::UnknownInput(_) => None,
+            SignInputError::Derive(err) => Some(err),
         }
     }
 }
@@ -197,20 +208,66 @@ pub trait SignAll {
     /// i.e. a transaction with one P2TR input having a single key may result
     /// in multiple signatures, one per each listed spending P2TR leaf.
     fn sign_all<C>(&mut self, provider: &impl SecretProvider<C>) -> Result<usize, SignError>
+    where
+        C: Signing + Verification,
+    {
+        self.sign_all_with(provider, &SignOptions::with_random_aux())
+    }
+
+    /// Signs all PSBT inputs using all known keys provided by
+    /// [`SecretProvider`], like [`SignAll::sign_all`], using specific
+    /// [`SignOptions`] (for instance, deterministic Schnorr nonces with no
+    /// auxiliary randomness or low-`R` ECDSA signature grinding).
+    fn sign_all_with<C>(
+        &mut self,
+        provider: &impl SecretProvider<C>,
+        options: &SignOptions,
+    ) -> Result<usize, SignError>
     where
         C: Signing + Verification;
 }
 
 impl SignAll for Psbt {
-    fn sign_all<C: Signing + Verification>(
+    fn sign_all_with<C: Signing + Verification>(
         &mut self,
         provider: &impl SecretProvider<C>,
+        options: &SignOptions,
     ) -> Result<usize, SignError> {
-        let tx = self.clone().into_unsigned_tx();
+        let mut signer = self.signer()?;
         let mut signature_count = 0usize;
-        let mut sig_hasher = SighashCache::new(&tx);
+        for input in &mut self.inputs {
+            signature_count += signer
+                .sign_input(input, provider, options)
+                .map_err(|err| SignError::with_input_no(err, input.index()))?;
+        }
+        Ok(signature_count)
+    }
+}
+
+/// Signer for the inputs of a single PSBT transaction, created with
+/// [`Psbt::signer`].
+///
+/// Signature hash computation for each of the inputs requires hashes of all
+/// transaction inputs and outputs (like `hashPrevouts`, `hashSequence` and
+/// `hashOutputs` from BIP-143 and their BIP-341 counterparts). The signer
+/// caches them, such that they are computed only once per transaction and not
+/// per each signed input.
+pub struct PsbtSigner {
+    tx: Rc<Transaction>,
+    sig_hasher: SighashCache<Rc<Transaction>>,
+    prevouts: Vec<TxOut>,
+}
 
-        let txout_list = self
+impl Psbt {
+    /// Constructs signer for the PSBT inputs, caching transaction-wide data
+    /// used in signature hash computation.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the information about some of the spent outputs is missed
+    /// from the PSBT, since it is required for signing taproot inputs.
+    pub fn signer(&self) -> Result<PsbtSigner, SignError> {
+        let prevouts = self
             .inputs
             .iter()
             .map(|input| {
@@ -221,22 +278,42 @@ impl SignAll for Psbt {
                     .map_err(|err| SignError::with_input_no(err, input.index()))
             })
             .collect::<Result<Vec<_>, _>>()?;
-        let prevouts = Prevouts::All(txout_list.as_ref());
+        let tx = Rc::new(self.to_unsigned_tx());
+        Ok(PsbtSigner {
+            sig_hasher: SighashCache::new(tx.clone()),
+            tx,
+            prevouts,
+        })
+    }
+}
 
-        for input in &mut self.inputs {
-            let count = input
-                .sign_input_pretr(provider, &mut sig_hasher)
-                .map_err(|err| SignError::with_input_no(err, input.index()))?;
-            if count == 0 {
-                signature_count += input
-                    .sign_input_tr(provider, &mut sig_hasher, &prevouts)
-                    .map_err(|err| SignError::with_input_no(err, input.index()))?;
-            } else {
-                signature_count += count;
-            }
+impl PsbtSigner {
+    /// Signs a single PSBT input using all known keys provided by
+    /// [`SecretProvider`], like [`SignAll::sign_all_with`] does for all of the
+    /// inputs. The input must belong to the PSBT the signer was created from.
+    ///
+    /// # Returns
+    ///
+    /// Number of created signatures or error.
+    pub fn sign_input<C>(
+        &mut self,
+        input: &mut Input,
+        provider: &impl SecretProvider<C>,
+        options: &SignOptions,
+    ) -> Result<usize, SignInputError>
+    where
+        C: Signing + Verification,
+    {
+        let txin = self.tx.input.get(input.index());
+        if txin.map(|txin| txin.previous_output) != Some(input.previous_outpoint) {
+            return Err(SignInputError::UnknownInput(input.previous_outpoint));
         }
-
-        Ok(signature_count)
+        let count = input.sign_input_pretr(provider, &mut self.sig_hasher, options)?;
+        if count > 0 {
+            return Ok(count);
+        }
+        let prevouts = Prevouts::All(&self.prevouts);
+        input.sign_input_tr(provider, &mut self.sig_hasher, &prevouts, options)
     }
 }
 
@@ -257,6 +334,7 @@ impl Input {
         &mut self,
         provider: &impl SecretProvider<C>,
         sig_hasher: &mut SighashCache<R>,
+        options: &SignOptions,
     ) -> Result<usize, SignInputError>
     where
         C: Signing,
@@ -271,7 +349,7 @@ impl Input {
                 Err(_) => continue,
             };
 
-            if self.sign_input_with(provider, sig_hasher, pubkey, seckey)? {
+            if self.sign_input_with(provider, sig_hasher, pubkey, seckey, options)? {
                 signature_count += 1;
             }
         }
@@ -299,6 +377,7 @@ impl Input {
         provider: &impl SecretProvider<C>,
         sig_hasher: &mut SighashCache<R>,
         prevouts: &Prevouts<TxOut>,
+        options: &SignOptions,
     ) -> Result<usize, SignInputError>
     where
         C: Signing + Verification,
@@ -314,22 +393,22 @@ impl Input {
             };
 
             signature_count += self.sign_taproot_input_with(
-                provider, sig_hasher, pubkey, keypair, &leaves, prevouts,
+                provider, sig_hasher, pubkey, keypair, &leaves, prevouts, options,
             )?;
         }
 
         Ok(signature_count)
     }
 
-    fn sign_input_with<C, R>(
-        &mut self,
-        provider: &impl SecretProvider<C>,
+    /// Computes signature hash for a pre-taproot input using the provided
+    /// sighash type. Returns `None` for taproot inputs, which use a different
+    /// signature hash algorithm.
+    pub(super) fn ecdsa_sighash<R>(
+        &self,
         sig_hasher: &mut SighashCache<R>,
-        pubkey: secp256k1::PublicKey,
-        mut seckey: secp256k1::SecretKey,
-    ) -> Result<bool, SignInputError>
+        sighash_type: EcdsaSighashType,
+    ) -> Result<Option<Sighash>, SignInputError>
     where
-        C: Signing,
         R: Deref<Target = Transaction>,
     {
         // Extract & check previous output information
@@ -343,15 +422,6 @@ impl Input {
         let redeem_script = self.redeem_script.as_ref();
 
         // Compute sighash
-        let sighash_type = self
-            .sighash_type
-            .map(|sht| sht.ecdsa_hash_ty())
-            .transpose()
-            .map_err(|err| SignInputError::NonStandardSighashType {
-                sighash_type: err.0,
-                index,
-            })?
-            .unwrap_or(EcdsaSighashType::All);
         let sighash = match (self.composite_descr_type()?, witness_script) {
             (CompositeDescrType::Wsh, Some(witness_script))
                 if prevout.script_pubkey != witness_script.to_v0_p2wsh() =>
@@ -365,10 +435,7 @@ impl Input {
             {
                 return Err(SignInputError::ScriptPubkeyMismatch)
             }
-            (CompositeDescrType::Tr, _) => {
-                // skipping taproot spendings: they are handled by a separate function
-                return Ok(false);
-            }
+            (CompositeDescrType::Tr, _) => return Ok(None),
             (CompositeDescrType::Wpkh, _) | (CompositeDescrType::ShWpkh, _) => {
                 let pubkey_hash = PubkeyHash::from_slice(&script_pubkey[2..22])
                     .expect("PubkeyHash hash length failure");
@@ -389,6 +456,37 @@ impl Input {
             }
         };
 
+        Ok(Some(sighash))
+    }
+
+    fn sign_input_with<C, R>(
+        &mut self,
+        provider: &impl SecretProvider<C>,
+        sig_hasher: &mut SighashCache<R>,
+        pubkey: secp256k1::PublicKey,
+        mut seckey: secp256k1::SecretKey,
+        options: &SignOptions,
+    ) -> Result<bool, SignInputError>
+    where
+        C: Signing,
+        R: Deref<Target = Transaction>,
+    {
+        let index = self.index();
+        let sighash_type = self
+            .sighash_type
+            .map(|sht| sht.ecdsa_hash_ty())
+            .transpose()
+            .map_err(|err| SignInputError::NonStandardSighashType {
+                sighash_type: err.0,
+                index,
+            })?
+            .unwrap_or(EcdsaSighashType::All);
+        let sighash = match self.ecdsa_sighash(sig_hasher, sighash_type)? {
+            Some(sighash) => sighash,
+            // skipping taproot spendings: they are handled by a separate function
+            None => return Ok(false),
+        };
+
         // Apply past P2C tweaks
         if let Some(tweak) = self.p2c_tweak(pubkey) {
             seckey
@@ -397,7 +495,8 @@ impl Input {
         }
 
         // Do the signature
-        let signature = provider.secp_context().sign_ecdsa(
+        let signature = options.sign_ecdsa(
+            provider.secp_context(),
             &bitcoin::secp256k1::Message::from_slice(&sighash[..])
                 .expect("Sighash generation is broken"),
             &seckey,
@@ -413,6 +512,7 @@ impl Input {
         Ok(true)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn sign_taproot_input_with<C, R>(
         &mut self,
         provider: &impl SecretProvider<C>,
@@ -421,6 +521,7 @@ impl Input {
         mut keypair: KeyPair,
         leaves: &[TapLeafHash],
         prevouts: &Prevouts<TxOut>,
+        options: &SignOptions,
     ) -> Result<usize, SignInputError>
     where
         C: Signing + Verification,
@@ -463,6 +564,13 @@ impl Input {
             return Err(SignInputError::TaprootPrevoutsMissed);
         }
 
+        // Key spending sighash is computed before any of the signatures is
+        // added, such that a sighash type which can't be used with the
+        // transaction (like `SIGHASH_SINGLE` without a corresponding output)
+        // does not leave the input partially signed
+        let sighash =
+            sig_hasher.taproot_signature_hash(index, prevouts, None, None, sighash_type)?;
+
         // Apply past P2C tweaks
         if let Some(tweak) = self.p2c_tweak(pubkey.to_public_key().inner) {
             keypair
@@ -487,7 +595,8 @@ impl Input {
                     ScriptPath::with_defaults(script),
                     sighash_type,
                 )?;
-                let signature = provider.secp_context().sign_schnorr(
+                let signature = options.sign_schnorr(
+                    provider.secp_context(),
                     &bitcoin::secp256k1::Message::from_slice(&sighash[..])
                         .expect("taproot Sighash generation is broken"),
                     &keypair,
@@ -502,10 +611,9 @@ impl Input {
         }
 
         // Sign taproot key spendings
-        let sighash =
-            sig_hasher.taproot_signature_hash(index, prevouts, None, None, sighash_type)?;
         let tweaked_keypair = keypair.tap_tweak(provider.secp_context(), self.tap_merkle_root);
-        let signature = provider.secp_context().sign_schnorr(
+        let signature = options.sign_schnorr(
+            provider.secp_context(),
             &bitcoin::secp256k1::Message::from_slice(&sighash[..])
                 .expect("taproot Sighash generation is broken"),
             &tweaked_keypair.into_inner(),
@@ -568,3 +676,324 @@ impl Input {
         Ok(signature_count)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bitcoin::secp256k1::SECP256K1;
+    use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+    use bitcoin::{Network, OutPoint};
+    use bitcoin_hd::{DeriveDescriptor, SegmentIndexes, UnhardenedIndex};
+    use descriptors::locks::SeqNo;
+    use descriptors::InputDescriptor;
+    use miniscript::DescriptorTrait;
+
+    use super::*;
+    use crate::sign::{MemoryKeyProvider, MemorySigningAccount};
+
+    fn unsigned_psbt(derivation: &str) -> (Psbt, MemorySigningAccount) {
+        unsigned_psbt_with_inputs(derivation, 1)
+    }
+
+    fn unsigned_psbt_with_inputs(derivation: &str, count: u16) -> (Psbt, MemorySigningAccount) {
+        let master = ExtendedPrivKey::new_master(Network::Bitcoin, &[1u8; 32]).unwrap();
+        let derivation = DerivationPath::from_str(derivation).unwrap();
+        let account_xpriv = master.derive_priv(SECP256K1, &derivation).unwrap();
+        let account = MemorySigningAccount::with(
+            SECP256K1,
+            ExtendedPubKey::from_priv(SECP256K1, &master).identifier(),
+            derivation,
+            account_xpriv,
+        );
+        let descriptor = account.recommended_descriptor().unwrap();
+
+        let terminals = (0..count)
+            .map(|index| vec![UnhardenedIndex::zero(), UnhardenedIndex::from(index)])
+            .collect::<Vec<_>>();
+        let output = |terminal: &[UnhardenedIndex]| TxOut {
+            value: 100_000,
+            script_pubkey: match descriptor {
+                miniscript::Descriptor::Tr(_) => DescriptorTrait::script_pubkey(
+                    &DeriveDescriptor::<XOnlyPublicKey>::derive_descriptor(
+                        &descriptor,
+                        SECP256K1,
+                        terminal,
+                    )
+                    .unwrap(),
+                ),
+                _ => DescriptorTrait::script_pubkey(
+                    &DeriveDescriptor::<PublicKey>::derive_descriptor(
+                        &descriptor,
+                        SECP256K1,
+                        terminal,
+                    )
+                    .unwrap(),
+                ),
+            },
+        };
+        let prev_tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: terminals.iter().map(|terminal| output(terminal)).collect(),
+        };
+        let txid = prev_tx.txid();
+        let inputs = terminals
+            .into_iter()
+            .enumerate()
+            .map(|(vout, terminal)| InputDescriptor {
+                outpoint: OutPoint::new(txid, vout as u32),
+                terminal: terminal.into_iter().collect(),
+                seq_no: SeqNo::unencumbered(true),
+                tweak: None,
+                sighash_type: EcdsaSighashType::All,
+            })
+            .collect::<Vec<_>>();
+        let outputs = [(PubkeyScript::from(Script::new()), 99_000 * count as u64)];
+        let psbt = Psbt::construct(
+            &descriptor,
+            &inputs,
+            &outputs,
+            UnhardenedIndex::zero(),
+            1_000 * count as u64,
+            None,
+            &bmap! { txid => prev_tx },
+        )
+        .unwrap();
+        (psbt, account)
+    }
+
+    fn sign(psbt: &Psbt, account: &MemorySigningAccount, options: SignOptions) -> Psbt {
+        let mut provider = MemoryKeyProvider::with(SECP256K1, false);
+        provider.add_account(account.clone());
+        let mut psbt = psbt.clone();
+        assert_eq!(psbt.sign_all_with(&provider, &options).unwrap(), 1);
+        psbt
+    }
+
+    #[test]
+    fn single_derivation_per_key() {
+        use std::cell::RefCell;
+
+        use bitcoin::secp256k1::{self, All, Secp256k1, SecretKey};
+        use bitcoin::util::bip32::Fingerprint;
+
+        use crate::sign::SecretProviderError;
+
+        struct CountingProvider<'secp> {
+            inner: MemoryKeyProvider<'secp, All>,
+            requests: RefCell<Vec<DerivationPath>>,
+        }
+
+        impl<'secp> SecretProvider<All> for CountingProvider<'secp> {
+            fn secp_context(&self) -> &Secp256k1<All> { self.inner.secp_context() }
+
+            fn secret_key(
+                &self,
+                fingerprint: Fingerprint,
+                derivation: &DerivationPath,
+                pubkey: secp256k1::PublicKey,
+            ) -> Result<SecretKey, SecretProviderError> {
+                self.requests.borrow_mut().push(derivation.clone());
+                self.inner.secret_key(fingerprint, derivation, pubkey)
+            }
+
+            fn key_pair(
+                &self,
+                fingerprint: Fingerprint,
+                derivation: &DerivationPath,
+                pubkey: XOnlyPublicKey,
+            ) -> Result<KeyPair, SecretProviderError> {
+                self.requests.borrow_mut().push(derivation.clone());
+                self.inner.key_pair(fingerprint, derivation, pubkey)
+            }
+
+            fn use_musig(&self) -> bool { false }
+        }
+
+        let (mut psbt, account) = unsigned_psbt("m/84'/0'/0'");
+        let (pubkey, (fingerprint, derivation)) =
+            psbt.inputs[0].bip32_derivation.iter().next().unwrap();
+        assert_eq!(*fingerprint, account.master_fingerprint());
+        assert_eq!(derivation.len(), 5);
+        let derivation = derivation.clone();
+
+        let mut inner = MemoryKeyProvider::with(SECP256K1, false);
+        inner.add_account(account.clone());
+        let provider = CountingProvider {
+            inner,
+            requests: default!(),
+        };
+        assert_eq!(
+            provider.inner.secret_key(
+                account.master_fingerprint(),
+                &DerivationPath::from_str("m/84'/0'/5'/0/0").unwrap(),
+                *pubkey
+            ),
+            Err(SecretProviderError::AccountUnknown(
+                account.master_fingerprint(),
+                *pubkey
+            ))
+        );
+
+        assert_eq!(psbt.sign_all(&provider).unwrap(), 1);
+        assert_eq!(provider.requests.into_inner(), vec![derivation]);
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 1);
+    }
+
+    #[test]
+    fn cached_signer() {
+        let options = SignOptions::default();
+        for derivation in ["m/84'/0'/0'", "m/86'/0'/0'"] {
+            let (psbt, account) = unsigned_psbt_with_inputs(derivation, 50);
+            let mut provider = MemoryKeyProvider::with(SECP256K1, false);
+            provider.add_account(account);
+
+            let mut cached = psbt.clone();
+            assert_eq!(cached.sign_all_with(&provider, &options).unwrap(), 50);
+
+            // Signing each input with a new signer recomputes all of the
+            // transaction hashes, but must give the same signatures
+            let mut uncached = psbt.clone();
+            for input in &mut uncached.inputs {
+                let mut signer = psbt.signer().unwrap();
+                assert_eq!(signer.sign_input(input, &provider, &options).unwrap(), 1);
+            }
+            assert_eq!(cached, uncached);
+            assert!(cached
+                .inputs
+                .iter()
+                .all(|input| input.partial_sigs.len() + input.tap_key_sig.iter().count() == 1));
+        }
+
+        let (psbt, account) = unsigned_psbt("m/84'/0'/0'");
+        let mut provider = MemoryKeyProvider::with(SECP256K1, false);
+        provider.add_account(account);
+        let mut input = psbt.inputs[0].clone();
+        input.previous_outpoint.vout = 1;
+        assert!(matches!(
+            psbt.signer().unwrap().sign_input(&mut input, &provider, &options),
+            Err(SignInputError::UnknownInput(outpoint)) if outpoint == input.previous_outpoint
+        ));
+    }
+
+    #[test]
+    fn deterministic_taproot_sigs() {
+        let (psbt, account) = unsigned_psbt("m/86'/0'/0'");
+        let options = SignOptions {
+            aux_rand: Some([7u8; 32]),
+            low_r: false,
+        };
+        let sig1 = sign(&psbt, &account, options).inputs[0].tap_key_sig;
+        let sig2 = sign(&psbt, &account, options).inputs[0].tap_key_sig;
+        assert!(sig1.is_some());
+        assert_eq!(sig1, sig2);
+
+        let no_aux1 = sign(&psbt, &account, SignOptions::default()).inputs[0].tap_key_sig;
+        let no_aux2 = sign(&psbt, &account, SignOptions::default()).inputs[0].tap_key_sig;
+        assert_eq!(no_aux1, no_aux2);
+        assert_ne!(no_aux1, sig1);
+    }
+
+    #[test]
+    fn taproot_sighash_types() {
+        let (psbt, account) = unsigned_psbt("m/86'/0'/0'");
+        let tx = psbt.clone().into_unsigned_tx();
+        let prevout = psbt.inputs[0].input_prevout().unwrap().clone();
+        let output_key = XOnlyPublicKey::from_slice(&prevout.script_pubkey[2..]).unwrap();
+
+        for sighash_type in [
+            SchnorrSighashType::Default,
+            SchnorrSighashType::All,
+            SchnorrSighashType::None,
+            SchnorrSighashType::Single,
+            SchnorrSighashType::AllPlusAnyoneCanPay,
+            SchnorrSighashType::NonePlusAnyoneCanPay,
+            SchnorrSighashType::SinglePlusAnyoneCanPay,
+        ] {
+            let mut psbt = psbt.clone();
+            psbt.inputs[0].sighash_type = Some(sighash_type.into());
+            let sig = sign(&psbt, &account, SignOptions::default()).inputs[0]
+                .tap_key_sig
+                .unwrap();
+            assert_eq!(sig.hash_ty, sighash_type);
+
+            let serialized = sig.to_vec();
+            if sighash_type == SchnorrSighashType::Default {
+                assert_eq!(serialized.len(), 64);
+            } else {
+                assert_eq!(serialized.len(), 65);
+                assert_eq!(serialized[64], sighash_type as u8);
+            }
+            assert_eq!(SchnorrSig::from_slice(&serialized).unwrap(), sig);
+
+            let sighash = SighashCache::new(&tx)
+                .taproot_key_spend_signature_hash(
+                    0,
+                    &Prevouts::All(std::slice::from_ref(&prevout)),
+                    sighash_type,
+                )
+                .unwrap();
+            let msg = secp256k1::Message::from_slice(&sighash[..]).unwrap();
+            SECP256K1
+                .verify_schnorr(&sig.sig, &msg, &output_key)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn taproot_sighash_single_without_output() {
+        let (mut psbt, account) = unsigned_psbt("m/86'/0'/0'");
+        psbt.outputs.clear();
+        let mut provider = MemoryKeyProvider::with(SECP256K1, false);
+        provider.add_account(account);
+
+        for sighash_type in [
+            SchnorrSighashType::Single,
+            SchnorrSighashType::SinglePlusAnyoneCanPay,
+        ] {
+            let mut psbt = psbt.clone();
+            psbt.inputs[0].sighash_type = Some(sighash_type.into());
+            let err = psbt
+                .sign_all_with(&provider, &SignOptions::default())
+                .unwrap_err();
+            assert_eq!(err.input_index, 0);
+            assert!(matches!(
+                err.error,
+                SignInputError::TaprootSighashError(
+                    sighash::Error::SingleWithoutCorrespondingOutput { index: 0, .. }
+                )
+            ));
+            assert_eq!(psbt.inputs[0].tap_key_sig, None);
+            assert!(psbt.inputs[0].tap_script_sigs.is_empty());
+        }
+
+        psbt.inputs[0].sighash_type = Some(SchnorrSighashType::None.into());
+        assert_eq!(
+            psbt.sign_all_with(&provider, &SignOptions::default())
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn low_r_ecdsa_sigs() {
+        let (psbt, account) = unsigned_psbt("m/84'/0'/0'");
+        let options = SignOptions {
+            aux_rand: None,
+            low_r: true,
+        };
+        let signed1 = sign(&psbt, &account, options);
+        let signed2 = sign(&psbt, &account, options);
+        assert_eq!(
+            signed1.inputs[0].partial_sigs,
+            signed2.inputs[0].partial_sigs
+        );
+
+        let sig = signed1.inputs[0].partial_sigs.values().next().unwrap();
+        let compact = sig.sig.serialize_compact();
+        assert!(compact[0] < 0x80);
+        assert!(sig.sig.serialize_der().len() <= 70);
+    }
+}
@@ -0,0 +1,382 @@
+// Descriptor wallet library extending bitcoin & miniscript functionality
+// by LNP/BP Association (https://lnp-bp.org)
+// Written in 2020-2022 by
+//     Dr. Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the Apache-2.0 License
+// along with this software.
+// If not, see <https://opensource.org/licenses/Apache-2.0>.
+
+//! Verification of partial signatures present in PSBT.
+
+use bitcoin::secp256k1::{Message, Secp256k1, Verification, XOnlyPublicKey};
+use bitcoin::util::sighash::{Prevouts, SighashCache};
+use bitcoin::SchnorrSighashType;
+use miniscript::ToPublicKey;
+
+use super::SignInputError;
+use crate::{Psbt, SignerKey};
+
+/// Errors happening during verification of a PSBT partial signature
+#[derive(Debug, Display, From)]
+#[display(doc_comments)]
+pub enum VerifyError {
+    /// unable to compute signature hash: {0}
+    #[from]
+    Sighash(SignInputError),
+
+    /// ECDSA partial signature is provided for a taproot input
+    TaprootInput,
+
+    /// BIP-340 signature is provided for an input which does not spend a
+    /// taproot output
+    NonTaprootInput,
+
+    /// error applying pay-to-contract tweak to the public key
+    P2cTweak,
+
+    /// signature does not match the public key and the transaction data
+    InvalidSignature,
+}
+
+impl std::error::Error for VerifyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VerifyError::Sighash(err) => Some(err),
+            VerifyError::TaprootInput => None,
+            VerifyError::NonTaprootInput => None,
+            VerifyError::P2cTweak => None,
+            VerifyError::InvalidSignature => None,
+        }
+    }
+}
+
+impl Psbt {
+    /// Verifies all partial signatures from all PSBT inputs: ECDSA signatures
+    /// against the public keys they are provided for, taking into account
+    /// pay-to-contract tweaks present in the inputs, and BIP-340 taproot
+    /// signatures against their BIP-341 signature hashes. Key path
+    /// signatures are verified with the output key from the spent
+    /// `scriptPubkey`; script path signatures with the keys they are provided
+    /// for.
+    ///
+    /// Signature hashes are computed using a single shared sighash cache. The
+    /// underlying `secp256k1` library does not provide batch verification, so
+    /// each of the signatures is verified independently.
+    ///
+    /// # Returns
+    ///
+    /// All found verification failures, indicating input number and public
+    /// key of each of the failed signatures. The key is `None` for taproot
+    /// key path signatures of inputs with unknown spent output, which also
+    /// lack taproot internal key.
+    pub fn verify_all_sigs<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+    ) -> Result<(), Vec<(usize, Option<SignerKey>, VerifyError)>> {
+        let tx = self.clone().into_unsigned_tx();
+        let mut sig_hasher = SighashCache::new(&tx);
+        let prevouts = self
+            .inputs
+            .iter()
+            .map(|input| input.input_prevout().ok().cloned())
+            .collect::<Option<Vec<_>>>();
+        let mut failures = vec![];
+
+        for input in &self.inputs {
+            let index = input.index();
+            for (pubkey, sig) in &input.partial_sigs {
+                let signer = Some(SignerKey::Ecdsa(*pubkey));
+                let sighash = match input.ecdsa_sighash(&mut sig_hasher, sig.hash_ty) {
+                    Ok(Some(sighash)) => sighash,
+                    Ok(None) => {
+                        failures.push((index, signer, VerifyError::TaprootInput));
+                        continue;
+                    }
+                    Err(err) => {
+                        failures.push((index, signer, err.into()));
+                        continue;
+                    }
+                };
+                let mut key = pubkey.inner;
+                if let Some(tweak) = input.p2c_tweak(key) {
+                    if key.add_exp_assign(secp, &tweak[..]).is_err() {
+                        failures.push((index, signer, VerifyError::P2cTweak));
+                        continue;
+                    }
+                }
+                let msg = Message::from_slice(&sighash[..]).expect("sighash generation is broken");
+                if secp.verify_ecdsa(&msg, &sig.sig, &key).is_err() {
+                    failures.push((index, signer, VerifyError::InvalidSignature));
+                }
+            }
+
+            let prevout = input.input_prevout();
+            let output_key = prevout.ok().and_then(|prevout| {
+                let script = &prevout.script_pubkey;
+                if !script.is_v1_p2tr() {
+                    return None;
+                }
+                XOnlyPublicKey::from_slice(&script[2..]).ok()
+            });
+            let key_path = input.tap_key_sig.map(|sig| {
+                let key = output_key.or(input.tap_internal_key);
+                (key, None, sig)
+            });
+            let script_path = input
+                .tap_script_sigs
+                .iter()
+                .map(|((key, leaf_hash), sig)| (Some(*key), Some(*leaf_hash), *sig));
+            for (key, leaf_hash, sig) in key_path.into_iter().chain(script_path) {
+                let signer = key.map(SignerKey::Schnorr);
+                let prevout = match prevout {
+                    Ok(prevout) => prevout,
+                    Err(err) => {
+                        let err = SignInputError::from(err);
+                        failures.push((index, signer, err.into()));
+                        continue;
+                    }
+                };
+                let (key, output_key) = match (key, output_key) {
+                    (Some(key), Some(output_key)) => (key, output_key),
+                    _ => {
+                        failures.push((index, signer, VerifyError::NonTaprootInput));
+                        continue;
+                    }
+                };
+                let sighash_prevouts = match (&prevouts, sig.hash_ty) {
+                    (
+                        _,
+                        SchnorrSighashType::AllPlusAnyoneCanPay
+                        | SchnorrSighashType::NonePlusAnyoneCanPay
+                        | SchnorrSighashType::SinglePlusAnyoneCanPay,
+                    ) => Prevouts::One(index, prevout.clone()),
+                    (Some(prevouts), _) => Prevouts::All(prevouts),
+                    (None, _) => {
+                        let err = SignInputError::TaprootPrevoutsMissed;
+                        failures.push((index, signer, err.into()));
+                        continue;
+                    }
+                };
+                let sighash = match leaf_hash {
+                    None => sig_hasher.taproot_key_spend_signature_hash(
+                        index,
+                        &sighash_prevouts,
+                        sig.hash_ty,
+                    ),
+                    Some(leaf_hash) => sig_hasher.taproot_script_spend_signature_hash(
+                        index,
+                        &sighash_prevouts,
+                        leaf_hash,
+                        sig.hash_ty,
+                    ),
+                };
+                let sighash = match sighash {
+                    Ok(sighash) => sighash,
+                    Err(err) => {
+                        let err = SignInputError::from(err);
+                        failures.push((index, signer, err.into()));
+                        continue;
+                    }
+                };
+                // Output key already commits to all of the tweaks, while script
+                // path keys may be tweaked with pay-to-contract commitments
+                let mut key = if leaf_hash.is_some() { key } else { output_key };
+                let tweak = leaf_hash.and_then(|_| input.p2c_tweak(key.to_public_key().inner));
+                if let Some(tweak) = tweak {
+                    if key.tweak_add_assign(secp, &tweak[..]).is_err() {
+                        failures.push((index, signer, VerifyError::P2cTweak));
+                        continue;
+                    }
+                }
+                let msg = Message::from_slice(&sighash[..]).expect("sighash generation is broken");
+                if secp.verify_schnorr(&sig.sig, &msg, &key).is_err() {
+                    failures.push((index, signer, VerifyError::InvalidSignature));
+                }
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bitcoin::schnorr::TapTweak;
+    use bitcoin::secp256k1::{KeyPair, SecretKey};
+    use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+    use bitcoin::util::taproot::{LeafVersion, TapLeafHash};
+    use bitcoin::{
+        EcdsaSig, EcdsaSighashType, Network, OutPoint, SchnorrSig, Script, Transaction, TxIn, TxOut,
+    };
+    use bitcoin_hd::{DeriveDescriptor, SegmentIndexes, UnhardenedIndex};
+    use bitcoin_scripts::PubkeyScript;
+    use descriptors::locks::SeqNo;
+    use descriptors::InputDescriptor;
+    use miniscript::DescriptorTrait;
+
+    use super::*;
+    use crate::sign::{MemoryKeyProvider, MemorySigningAccount, SignAll};
+    use crate::PsbtVersion;
+
+    #[test]
+    fn tampered_signature() {
+        let secp = Secp256k1::new();
+        let master = ExtendedPrivKey::new_master(Network::Bitcoin, &[1u8; 32]).unwrap();
+        let derivation = DerivationPath::from_str("m/84'/0'/0'").unwrap();
+        let account_xpriv = master.derive_priv(&secp, &derivation).unwrap();
+        let account = MemorySigningAccount::with(
+            &secp,
+            ExtendedPubKey::from_priv(&secp, &master).identifier(),
+            derivation,
+            account_xpriv,
+        );
+        let descriptor = account.recommended_descriptor().unwrap();
+
+        let terminals = [
+            vec![UnhardenedIndex::zero(), UnhardenedIndex::zero()],
+            vec![UnhardenedIndex::zero(), UnhardenedIndex::one()],
+        ];
+        let prev_tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: terminals
+                .iter()
+                .map(|terminal| TxOut {
+                    value: 100_000,
+                    script_pubkey: DescriptorTrait::script_pubkey(
+                        &DeriveDescriptor::<bitcoin::PublicKey>::derive_descriptor(
+                            &descriptor,
+                            &secp,
+                            terminal,
+                        )
+                        .unwrap(),
+                    ),
+                })
+                .collect(),
+        };
+        let txid = prev_tx.txid();
+        let inputs = terminals
+            .into_iter()
+            .enumerate()
+            .map(|(vout, terminal)| InputDescriptor {
+                outpoint: OutPoint::new(txid, vout as u32),
+                terminal: terminal.into_iter().collect(),
+                seq_no: SeqNo::unencumbered(true),
+                tweak: None,
+                sighash_type: EcdsaSighashType::All,
+            })
+            .collect::<Vec<_>>();
+        let outputs = [(PubkeyScript::from(Script::new()), 150_000)];
+        let mut psbt = Psbt::construct(
+            &descriptor,
+            &inputs,
+            &outputs,
+            UnhardenedIndex::zero(),
+            1_000,
+            None,
+            &bmap! { txid => prev_tx },
+        )
+        .unwrap();
+
+        let mut provider = MemoryKeyProvider::with(&secp, false);
+        provider.add_account(account);
+        assert_eq!(psbt.sign_all(&provider).unwrap(), 2);
+        assert!(psbt.verify_all_sigs(&secp).is_ok());
+
+        let (pubkey, sig) = psbt.inputs[1].partial_sigs.iter_mut().next().unwrap();
+        let pubkey = *pubkey;
+        let msg = Message::from_slice(&[1u8; 32]).unwrap();
+        let seckey = SecretKey::from_slice(&[2u8; 32]).unwrap();
+        *sig = EcdsaSig::sighash_all(secp.sign_ecdsa(&msg, &seckey));
+
+        let failures = psbt.verify_all_sigs(&secp).unwrap_err();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, 1);
+        assert_eq!(failures[0].1, Some(SignerKey::Ecdsa(pubkey)));
+        assert!(matches!(failures[0].2, VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn tampered_taproot_signatures() {
+        let secp = Secp256k1::new();
+        let keypair = KeyPair::from_secret_key(&secp, SecretKey::from_slice(&[3u8; 32]).unwrap());
+        let internal_key = XOnlyPublicKey::from_keypair(&keypair);
+        let prevout = TxOut {
+            value: 100_000,
+            script_pubkey: Script::new_v1_p2tr(&secp, internal_key, None),
+        };
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn::default()],
+            output: vec![TxOut {
+                value: 90_000,
+                script_pubkey: Script::new(),
+            }],
+        };
+        let mut psbt = Psbt::with(tx, PsbtVersion::V2).unwrap();
+        psbt.inputs[0].witness_utxo = Some(prevout.clone());
+        psbt.inputs[0].tap_internal_key = Some(internal_key);
+
+        let sign = |keypair: &KeyPair, sighash: &[u8]| SchnorrSig {
+            sig: secp.sign_schnorr_no_aux_rand(&Message::from_slice(sighash).unwrap(), keypair),
+            hash_ty: SchnorrSighashType::Default,
+        };
+        let unsigned_tx = psbt.to_unsigned_tx();
+        let mut sig_hasher = SighashCache::new(&unsigned_tx);
+        let prevouts = [prevout.clone()];
+        let prevouts = Prevouts::All(&prevouts);
+        let sighash_type = SchnorrSighashType::Default;
+        let sighash = sig_hasher
+            .taproot_key_spend_signature_hash(0, &prevouts, sighash_type)
+            .unwrap();
+        let tweaked = keypair.tap_tweak(&secp, None).into_inner();
+        psbt.inputs[0].tap_key_sig = Some(sign(&tweaked, &sighash[..]));
+        let leaf_hash = TapLeafHash::from_script(&Script::new(), LeafVersion::TapScript);
+        let sighash = sig_hasher
+            .taproot_script_spend_signature_hash(0, &prevouts, leaf_hash, sighash_type)
+            .unwrap();
+        let script_sig = sign(&keypair, &sighash[..]);
+        psbt.inputs[0]
+            .tap_script_sigs
+            .insert((internal_key, leaf_hash), script_sig);
+        assert!(psbt.verify_all_sigs(&secp).is_ok());
+
+        let output_key = XOnlyPublicKey::from_slice(&prevout.script_pubkey[2..]).unwrap();
+        psbt.inputs[0].tap_key_sig = Some(sign(&tweaked, &[1u8; 32]));
+        let failures = psbt.verify_all_sigs(&secp).unwrap_err();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].1, Some(SignerKey::Schnorr(output_key)));
+        assert!(matches!(failures[0].2, VerifyError::InvalidSignature));
+
+        psbt.inputs[0].tap_key_sig = None;
+        psbt.inputs[0]
+            .tap_script_sigs
+            .insert((internal_key, leaf_hash), sign(&keypair, &[1u8; 32]));
+        let failures = psbt.verify_all_sigs(&secp).unwrap_err();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].1, Some(SignerKey::Schnorr(internal_key)));
+        assert!(matches!(failures[0].2, VerifyError::InvalidSignature));
+
+        psbt.inputs[0].witness_utxo = None;
+        psbt.inputs[0].tap_internal_key = None;
+        psbt.inputs[0].tap_key_sig = Some(sign(&tweaked, &[1u8; 32]));
+        let failures = psbt.verify_all_sigs(&secp).unwrap_err();
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].1, None);
+        assert!(matches!(failures[0].2, VerifyError::Sighash(_)));
+    }
+}
@@ -0,0 +1,49 @@
+// Descriptor wallet library extending bitcoin & miniscript functionality
+// by LNP/BP Association (https://lnp-bp.org)
+// Written in 2020-2022 by
+//     Dr. Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the Apache-2.0 License
+// along with this software.
+// If not, see <https://opensource.org/licenses/Apache-2.0>.
+
+use bitcoin::util::bip32::Fingerprint;
+
+/// Compares two key fingerprints in constant time.
+///
+/// Key fingerprints from PSBT key sources are matched against the signer's
+/// own master and account fingerprints. When the PSBT comes from an untrusted
+/// party, an early-exit comparison (like the one performed by `==`) would leak
+/// through the timing how many leading bytes of the signer fingerprint were
+/// guessed correctly. Thus, this function always inspects all bytes of both
+/// fingerprints and must not be replaced with the `==` operator.
+pub fn fingerprint_eq_ct(a: &Fingerprint, b: &Fingerprint) -> bool {
+    a.as_bytes()
+        .iter()
+        .zip(b.as_bytes())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn fingerprint_eq() {
+        let fingerprint = Fingerprint::from_str("73c5da0a").unwrap();
+        assert!(fingerprint_eq_ct(&fingerprint, &fingerprint));
+        for other in ["73c5da0b", "f3c5da0a", "00000000", "8c3a25f5"] {
+            let other = Fingerprint::from_str(other).unwrap();
+            assert!(!fingerprint_eq_ct(&fingerprint, &other));
+            assert!(!fingerprint_eq_ct(&other, &fingerprint));
+        }
+    }
+}
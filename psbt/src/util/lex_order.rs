@@ -16,7 +16,7 @@
 
 use std::cmp::Ordering;
 
-use bitcoin::{self, secp256k1, Transaction, TxIn, TxOut};
+use bitcoin::{self, secp256k1, OutPoint, Transaction, TxIn, TxOut};
 
 use crate::v0::PsbtV0;
 use crate::{Input, Output, Psbt};
@@ -42,7 +42,9 @@ impl LexOrder for Vec<bitcoin::PublicKey> {
 }
 
 impl LexOrder for Vec<TxIn> {
-    fn lex_order(&mut self) { self.sort_by_key(|txin| txin.previous_output) }
+    fn lex_order(&mut self) {
+        self.sort_by(|a, b| outpoint_cmp(&a.previous_output, &b.previous_output))
+    }
 }
 
 impl LexOrder for Vec<TxOut> {
@@ -69,7 +71,7 @@ impl LexOrder for PsbtV0 {
             .into_iter()
             .zip(self.inputs.clone().into_iter())
             .collect::<Vec<(_, _)>>();
-        inputs.sort_by_key(|(k, _)| k.previous_output);
+        inputs.sort_by(|(a, _), (b, _)| outpoint_cmp(&a.previous_output, &b.previous_output));
 
         let mut outputs = tx
             .output
@@ -90,7 +92,7 @@ impl LexOrder for PsbtV0 {
 
 impl LexOrder for Vec<Input> {
     fn lex_order(&mut self) {
-        self.sort_by_key(|input| input.previous_outpoint);
+        self.sort_by(|a, b| outpoint_cmp(&a.previous_outpoint, &b.previous_outpoint));
         for (index, input) in self.iter_mut().enumerate() {
             input.index = index;
         }
@@ -113,6 +115,30 @@ impl LexOrder for Psbt {
     }
 }
 
+impl Psbt {
+    /// Sorts PSBT inputs and outputs according to BIP-69: inputs by the
+    /// previous transaction id (in its reversed byte order, as it is
+    /// displayed) and output index, and outputs by amount and then by
+    /// `scriptPubkey`. Per-input and per-output data are moved together with
+    /// the transaction inputs and outputs and their indexes are updated.
+    ///
+    /// Since signatures commit to the order of inputs and outputs, this must be
+    /// done before signing: the existing signatures get invalidated by the
+    /// reordering.
+    #[inline]
+    pub fn sort_bip69(&mut self) { self.lex_order() }
+}
+
+/// Compares outpoints according to BIP-69: by txid bytes in reversed order
+/// (matching the txid hex representation) and then by output index.
+fn outpoint_cmp(left: &OutPoint, right: &OutPoint) -> Ordering {
+    left.txid[..]
+        .iter()
+        .rev()
+        .cmp(right.txid[..].iter().rev())
+        .then(left.vout.cmp(&right.vout))
+}
+
 fn txout_cmp(left: &TxOut, right: &TxOut) -> Ordering {
     match (left.value, right.value) {
         (l, r) if l < r => Ordering::Less,
@@ -128,3 +154,96 @@ fn psbtout_cmp(left: &Output, right: &Output) -> Ordering {
         _ => left.script.cmp(&right.script),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use core::str::FromStr;
+
+    use bitcoin::{Script, Txid};
+
+    use super::*;
+    use crate::PsbtVersion;
+
+    #[test]
+    fn bip69() {
+        // Txids which order differs for the internal and displayed byte order
+        let low =
+            Txid::from_str("0e53ec5dfb2cb8a71fec32dc9a634a35b7e24799295ddd5278217822e0b31f57")
+                .unwrap();
+        let high =
+            Txid::from_str("f320832a9d2e2452af63154bc687493484a0e7745ebd3aaf9ca19eb80834ad01")
+                .unwrap();
+        assert!(low[..] > high[..]);
+        let script1 = Script::from_str(
+            "41046a0765b5865641ce08dd39690aade26dfbf5511430ca428a3089261361cef170e3929a68aee3d8d4848b0c5111b0a37b82b86ad559fd2a745b44d8e8d9dfdc0cac",
+        )
+        .unwrap();
+        let script2 = Script::from_str(
+            "41044a656f065871a353f216ca26cef8dde2f03e8c16202d2e8ad769f02032cb86a5eb5e56842e92e19141d60a01928f8dd2c875a390f67c1f6c94cfc617c0ea45afac",
+        )
+        .unwrap();
+        let txin = |txid, vout| TxIn {
+            previous_output: OutPoint::new(txid, vout),
+            ..TxIn::default()
+        };
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![txin(high, 0), txin(low, 1), txin(low, 0)],
+            output: vec![
+                TxOut {
+                    value: 2_400_000_000,
+                    script_pubkey: script1.clone(),
+                },
+                TxOut {
+                    value: 100_000_000,
+                    script_pubkey: script2.clone(),
+                },
+                TxOut {
+                    value: 100_000_000,
+                    script_pubkey: script1.clone(),
+                },
+            ],
+        };
+        let mut psbt = Psbt::with(tx, PsbtVersion::V2).unwrap();
+        for (no, input) in psbt.inputs.iter_mut().enumerate() {
+            input.por_commitment = Some(no.to_string());
+        }
+        psbt.sort_bip69();
+
+        let tx = psbt.to_unsigned_tx();
+        assert_eq!(tx.input, vec![txin(low, 0), txin(low, 1), txin(high, 0)]);
+        assert_eq!(
+            tx.output
+                .iter()
+                .map(|txout| (txout.value, &txout.script_pubkey))
+                .collect::<Vec<_>>(),
+            vec![
+                (100_000_000, &script2),
+                (100_000_000, &script1),
+                (2_400_000_000, &script1)
+            ]
+        );
+        assert_eq!(
+            psbt.inputs
+                .iter()
+                .map(|input| (input.index(), input.por_commitment.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![(0, Some("2")), (1, Some("1")), (2, Some("0"))]
+        );
+        assert!(psbt
+            .outputs
+            .iter()
+            .enumerate()
+            .all(|(no, output)| output.index() == no));
+
+        let mut tx = Transaction {
+            output: vec![],
+            ..tx
+        };
+        tx.input.reverse();
+        assert_eq!(tx.input[0].previous_output.txid, high);
+        tx.lex_order();
+        assert_eq!(tx.input[0].previous_output.txid, low);
+    }
+}
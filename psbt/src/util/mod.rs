@@ -14,7 +14,15 @@
 
 #[cfg(feature = "miniscript")]
 mod deduction;
+mod fingerprint;
 pub mod lex_order;
+mod plan;
+mod status;
+mod summary;
 
 #[cfg(feature = "miniscript")]
 pub use deduction::DeductionError;
+pub use fingerprint::fingerprint_eq_ct;
+pub use plan::{InputPlan, SignerCandidate, SignerKey, SigningPlan, SigningRequirement, SpendPath};
+pub use status::SigningStatus;
+pub use summary::PsbtSummary;
@@ -0,0 +1,400 @@
+// Descriptor wallet library extending bitcoin & miniscript functionality
+// by LNP/BP Association (https://lnp-bp.org)
+// Written in 2020-2022 by
+//     Dr. Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the Apache-2.0 License
+// along with this software.
+// If not, see <https://opensource.org/licenses/Apache-2.0>.
+
+use bitcoin::util::bip32::KeySource;
+use bitcoin::util::taproot::{LeafVersion, TapLeafHash};
+use bitcoin::{PublicKey, Script, XOnlyPublicKey};
+
+use super::status::parse_threshold;
+use crate::{Input, Psbt};
+
+/// Signing plan of a PSBT, listing keys which still have to sign each of its
+/// inputs (see [`Psbt::signing_plan`]).
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct SigningPlan {
+    /// Signing requirements of the PSBT inputs, in the order of the inputs.
+    pub inputs: Vec<InputPlan>,
+}
+
+/// Signing requirements of a PSBT input.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum InputPlan {
+    /// Input is finalized or has enough signatures to be finalized.
+    Complete,
+
+    /// Spending condition of the input is not known, so the required signers
+    /// can't be determined.
+    Unknown,
+
+    /// Input requires more signatures under any of the listed alternative
+    /// spending paths.
+    Incomplete(Vec<SigningRequirement>),
+}
+
+/// Signatures still required to satisfy a specific spending path of an input.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SigningRequirement {
+    /// Spending path the requirement applies to.
+    pub path: SpendPath,
+
+    /// Number of signatures which still have to be provided by the
+    /// `candidates`.
+    pub threshold: usize,
+
+    /// Keys participating in the spending path which have not signed yet.
+    pub candidates: Vec<SignerCandidate>,
+}
+
+/// Spending path of an input.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SpendPath {
+    /// ECDSA signatures for pre-taproot script (P2PKH, P2WPKH, bare
+    /// multisig, possibly wrapped into P2SH and P2WSH).
+    Ecdsa,
+
+    /// Taproot key path spending.
+    TapKey,
+
+    /// Taproot script path spending of the tapscript leaf with the given hash.
+    TapScript(TapLeafHash),
+}
+
+/// Key which may provide a signature required by an input.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SignerCandidate {
+    /// Public key of the signer.
+    pub key: SignerKey,
+
+    /// BIP32 key source (master key fingerprint and derivation path) of the
+    /// key, if present in the input key derivation data.
+    pub key_source: Option<KeySource>,
+}
+
+/// Public key of a signer.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SignerKey {
+    /// Key producing ECDSA signatures.
+    Ecdsa(PublicKey),
+
+    /// Key producing BIP-340 signatures.
+    Schnorr(XOnlyPublicKey),
+}
+
+impl Psbt {
+    /// Constructs signing plan for the PSBT, listing for each of the inputs the
+    /// keys which still have to sign it and the number of signatures yet to
+    /// be provided. The spending conditions are analyzed in the same way as
+    /// in [`Input::signing_status`].
+    pub fn signing_plan(&self) -> SigningPlan {
+        SigningPlan {
+            inputs: self.inputs.iter().map(Input::signing_plan).collect(),
+        }
+    }
+}
+
+impl Input {
+    /// Constructs signing plan of the input (see [`Psbt::signing_plan`]).
+    ///
+    /// Signatures already present in the input are subtracted from the
+    /// threshold and their keys are not listed as candidates. For taproot
+    /// inputs the key path, if the internal key is known, is listed before
+    /// the script path leaves.
+    pub fn signing_plan(&self) -> InputPlan {
+        if self.signing_status().is_complete() {
+            return InputPlan::Complete;
+        }
+
+        let prevout = match self.input_prevout() {
+            Ok(prevout) => prevout,
+            Err(_) => return InputPlan::Unknown,
+        };
+        let mut script = &prevout.script_pubkey;
+
+        if script.is_v1_p2tr() {
+            return self.taproot_signing_plan();
+        }
+        if script.is_p2sh() {
+            script = match self.redeem_script {
+                Some(ref redeem_script) => redeem_script,
+                None => return InputPlan::Unknown,
+            };
+        }
+        if script.is_v0_p2wsh() {
+            script = match self.witness_script {
+                Some(ref witness_script) => witness_script,
+                None => return InputPlan::Unknown,
+            };
+        }
+
+        let ecdsa_candidate = |key: PublicKey| SignerCandidate {
+            key: SignerKey::Ecdsa(key),
+            key_source: self.key_source(&key.inner).cloned(),
+        };
+        if script.is_p2pkh() || script.is_v0_p2wpkh() {
+            let candidates = self
+                .bip32_derivation
+                .keys()
+                .map(|pk| PublicKey::new(*pk))
+                .filter(|pk| {
+                    let p2wpkh = pk.wpubkey_hash().map(|hash| Script::new_v0_p2wpkh(&hash));
+                    *script == Script::new_p2pkh(&pk.pubkey_hash())
+                        || p2wpkh.as_ref() == Some(script)
+                })
+                .map(ecdsa_candidate)
+                .collect();
+            return InputPlan::Incomplete(vec![SigningRequirement {
+                path: SpendPath::Ecdsa,
+                threshold: 1,
+                candidates,
+            }]);
+        }
+
+        match parse_threshold(script) {
+            Some((required, keys)) => {
+                let keys = keys
+                    .into_iter()
+                    .filter_map(|key| PublicKey::from_slice(key).ok())
+                    .collect::<Vec<_>>();
+                let present = keys
+                    .iter()
+                    .filter(|pk| self.partial_sigs.contains_key(pk))
+                    .count();
+                let candidates = keys
+                    .into_iter()
+                    .filter(|pk| !self.partial_sigs.contains_key(pk))
+                    .map(ecdsa_candidate)
+                    .collect();
+                InputPlan::Incomplete(vec![SigningRequirement {
+                    path: SpendPath::Ecdsa,
+                    threshold: required.saturating_sub(present),
+                    candidates,
+                }])
+            }
+            None => InputPlan::Unknown,
+        }
+    }
+
+    fn taproot_signing_plan(&self) -> InputPlan {
+        let schnorr_candidate = |key: XOnlyPublicKey| SignerCandidate {
+            key: SignerKey::Schnorr(key),
+            key_source: self.tap_key_source(&key).map(|(_, source)| source.clone()),
+        };
+        let key_path = self
+            .tap_internal_key
+            .map(|internal_key| SigningRequirement {
+                path: SpendPath::TapKey,
+                threshold: 1,
+                candidates: vec![schnorr_candidate(internal_key)],
+            });
+        let script_paths = self
+            .tap_scripts
+            .values()
+            .filter(|(_, leaf_version)| *leaf_version == LeafVersion::TapScript)
+            .filter_map(|(script, leaf_version)| {
+                let leaf_hash = TapLeafHash::from_script(script, *leaf_version);
+                let (required, keys) = parse_threshold(script)?;
+                let keys = keys
+                    .into_iter()
+                    .filter_map(|key| XOnlyPublicKey::from_slice(key).ok())
+                    .collect::<Vec<_>>();
+                let signed =
+                    |pk: &XOnlyPublicKey| self.tap_script_sigs.contains_key(&(*pk, leaf_hash));
+                let present = keys.iter().filter(|pk| signed(pk)).count();
+                Some(SigningRequirement {
+                    path: SpendPath::TapScript(leaf_hash),
+                    threshold: required.saturating_sub(present),
+                    candidates: keys
+                        .into_iter()
+                        .filter(|pk| !signed(pk))
+                        .map(schnorr_candidate)
+                        .collect(),
+                })
+            });
+        let requirements = key_path.into_iter().chain(script_paths).collect::<Vec<_>>();
+        if requirements.is_empty() {
+            InputPlan::Unknown
+        } else {
+            InputPlan::Incomplete(requirements)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bitcoin::blockdata::opcodes::all::{OP_CHECKMULTISIG, OP_CHECKSIG};
+    use bitcoin::blockdata::script::Builder;
+    use bitcoin::secp256k1::{Message, SecretKey, SECP256K1};
+    use bitcoin::util::bip32::{DerivationPath, Fingerprint};
+    use bitcoin::{EcdsaSig, SchnorrSig, SchnorrSighashType, TxOut};
+
+    use super::*;
+
+    fn seckey(no: u8) -> SecretKey { SecretKey::from_slice(&[no; 32]).unwrap() }
+
+    fn pubkey(no: u8) -> PublicKey {
+        PublicKey::new(bitcoin::secp256k1::PublicKey::from_secret_key(
+            SECP256K1,
+            &seckey(no),
+        ))
+    }
+
+    fn key_source(no: u8) -> KeySource {
+        (
+            Fingerprint::from(&[no; 4][..]),
+            DerivationPath::from_str("m/48'/0'/0'/2'/0/0").unwrap(),
+        )
+    }
+
+    fn input(script_pubkey: Script) -> Input {
+        Input {
+            witness_utxo: Some(TxOut {
+                value: 10_000,
+                script_pubkey,
+            }),
+            ..Input::default()
+        }
+    }
+
+    #[test]
+    fn multisig_plan() {
+        let witness_script = Builder::new()
+            .push_int(2)
+            .push_key(&pubkey(1))
+            .push_key(&pubkey(2))
+            .push_key(&pubkey(3))
+            .push_int(3)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script();
+        let mut input = input(witness_script.to_v0_p2wsh());
+        assert_eq!(input.signing_plan(), InputPlan::Unknown);
+
+        input.witness_script = Some(witness_script);
+        for no in 1..=3 {
+            input
+                .bip32_derivation
+                .insert(pubkey(no).inner, key_source(no));
+        }
+        let msg = Message::from_slice(&[0xAB; 32]).unwrap();
+        let sig = EcdsaSig::sighash_all(SECP256K1.sign_ecdsa(&msg, &seckey(2)));
+        input.partial_sigs.insert(pubkey(2), sig);
+        let mut psbt = Psbt {
+            inputs: vec![input],
+            ..Psbt::default()
+        };
+        assert_eq!(psbt.signing_plan(), SigningPlan {
+            inputs: vec![InputPlan::Incomplete(vec![SigningRequirement {
+                path: SpendPath::Ecdsa,
+                threshold: 1,
+                candidates: vec![
+                    SignerCandidate {
+                        key: SignerKey::Ecdsa(pubkey(1)),
+                        key_source: Some(key_source(1)),
+                    },
+                    SignerCandidate {
+                        key: SignerKey::Ecdsa(pubkey(3)),
+                        key_source: Some(key_source(3)),
+                    },
+                ],
+            }])],
+        });
+
+        psbt.inputs[0].partial_sigs.insert(pubkey(3), sig);
+        assert_eq!(psbt.signing_plan().inputs, vec![InputPlan::Complete]);
+    }
+
+    #[test]
+    fn single_key_plan() {
+        let pk = pubkey(1);
+        let mut input = input(Script::new_v0_p2wpkh(&pk.wpubkey_hash().unwrap()));
+        input
+            .bip32_derivation
+            .insert(pubkey(2).inner, key_source(2));
+        input.bip32_derivation.insert(pk.inner, key_source(1));
+        assert_eq!(
+            input.signing_plan(),
+            InputPlan::Incomplete(vec![SigningRequirement {
+                path: SpendPath::Ecdsa,
+                threshold: 1,
+                candidates: vec![SignerCandidate {
+                    key: SignerKey::Ecdsa(pk),
+                    key_source: Some(key_source(1)),
+                }],
+            }])
+        );
+    }
+
+    #[test]
+    fn taproot_plan() {
+        let keys = (1..=2)
+            .map(|no| XOnlyPublicKey::from(pubkey(no).inner))
+            .collect::<Vec<_>>();
+        let leaf_script = Builder::new()
+            .push_slice(&keys[1].serialize())
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        let spend_info = bitcoin::util::taproot::TaprootBuilder::new()
+            .add_leaf(0, leaf_script.clone())
+            .unwrap()
+            .finalize(SECP256K1, keys[0])
+            .unwrap();
+        let control_block = spend_info
+            .control_block(&(leaf_script.clone(), LeafVersion::TapScript))
+            .unwrap();
+        let leaf_hash = TapLeafHash::from_script(&leaf_script, LeafVersion::TapScript);
+
+        let mut input = input(Script::new_v1_p2tr_tweaked(spend_info.output_key()));
+        assert_eq!(input.signing_plan(), InputPlan::Unknown);
+
+        input.tap_internal_key = Some(keys[0]);
+        input
+            .tap_scripts
+            .insert(control_block, (leaf_script, LeafVersion::TapScript));
+        input
+            .tap_key_origins
+            .insert(keys[0], (vec![], key_source(1)));
+        assert_eq!(
+            input.signing_plan(),
+            InputPlan::Incomplete(vec![
+                SigningRequirement {
+                    path: SpendPath::TapKey,
+                    threshold: 1,
+                    candidates: vec![SignerCandidate {
+                        key: SignerKey::Schnorr(keys[0]),
+                        key_source: Some(key_source(1)),
+                    }],
+                },
+                SigningRequirement {
+                    path: SpendPath::TapScript(leaf_hash),
+                    threshold: 1,
+                    candidates: vec![SignerCandidate {
+                        key: SignerKey::Schnorr(keys[1]),
+                        key_source: None,
+                    }],
+                },
+            ])
+        );
+
+        let sig = SchnorrSig {
+            sig: SECP256K1.sign_schnorr_no_aux_rand(
+                &Message::from_slice(&[0xAB; 32]).unwrap(),
+                &bitcoin::KeyPair::from_secret_key(SECP256K1, seckey(2)),
+            ),
+            hash_ty: SchnorrSighashType::Default,
+        };
+        input.tap_script_sigs.insert((keys[1], leaf_hash), sig);
+        assert_eq!(input.signing_plan(), InputPlan::Complete);
+    }
+}
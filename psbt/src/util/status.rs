@@ -0,0 +1,427 @@
+// Descriptor wallet library extending bitcoin & miniscript functionality
+// by LNP/BP Association (https://lnp-bp.org)
+// Written in 2020-2022 by
+//     Dr. Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the Apache-2.0 License
+// along with this software.
+// If not, see <https://opensource.org/licenses/Apache-2.0>.
+
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+
+use bitcoin::blockdata::opcodes::all::*;
+use bitcoin::blockdata::opcodes::All as Opcode;
+use bitcoin::blockdata::script::{read_scriptint, Instruction};
+use bitcoin::util::psbt::PsbtSighashType;
+use bitcoin::util::taproot::{LeafVersion, TapLeafHash};
+use bitcoin::{EcdsaSighashType, Script, XOnlyPublicKey};
+
+use crate::{Input, Psbt};
+
+/// Signing status of a PSBT input
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum SigningStatus {
+    /// input is finalized
+    Finalized,
+
+    /// input has {present} out of {required} signatures required for its
+    /// finalization
+    Signed {
+        /// Number of signatures present in the input
+        present: usize,
+        /// Number of signatures required to satisfy the spending condition
+        required: usize,
+    },
+
+    /// the spending condition of the input is not known, so the number of
+    /// required signatures can't be determined
+    Unknown,
+}
+
+impl SigningStatus {
+    /// Detects whether the input has enough signatures to be finalized (or is
+    /// already finalized).
+    pub fn is_complete(self) -> bool {
+        match self {
+            SigningStatus::Finalized => true,
+            SigningStatus::Signed { present, required } => present >= required,
+            SigningStatus::Unknown => false,
+        }
+    }
+}
+
+impl Psbt {
+    /// Detects whether all of the PSBT inputs have enough signatures to be
+    /// finalized (or are already finalized).
+    pub fn is_complete(&self) -> bool {
+        self.inputs
+            .iter()
+            .all(|input| input.signing_status().is_complete())
+    }
+
+    /// Collects sighash types used by all of the PSBT inputs (see
+    /// [`Input::sighash_types`]).
+    pub fn sighash_types(&self) -> BTreeSet<PsbtSighashType> {
+        self.inputs.iter().flat_map(Input::sighash_types).collect()
+    }
+
+    /// Detects whether the PSBT inputs use different sighash types, like
+    /// `SIGHASH_SINGLE` in one input and `SIGHASH_ALL` in the other, which
+    /// may result in unexpected transaction semantics.
+    #[inline]
+    pub fn has_mixed_sighash(&self) -> bool { self.sighash_types().len() > 1 }
+}
+
+impl Input {
+    /// Computes signing status of the input, i.e. the number of signatures
+    /// present and the number of signatures required to finalize it.
+    ///
+    /// For non-taproot inputs the threshold is read from the witness script,
+    /// redeem script or `scriptPubkey`, supporting single-key and bare
+    /// `OP_CHECKMULTISIG` spending conditions. For taproot inputs presence of
+    /// a key-path signature completes the input; otherwise script-path leaves
+    /// with single-key and `multi_a` spending conditions are analyzed and the
+    /// leaf closest to completion is reported.
+    pub fn signing_status(&self) -> SigningStatus {
+        if self.final_script_sig.is_some() || self.final_script_witness.is_some() {
+            return SigningStatus::Finalized;
+        }
+
+        let prevout = match self.input_prevout() {
+            Ok(prevout) => prevout,
+            Err(_) => return SigningStatus::Unknown,
+        };
+        let mut script = &prevout.script_pubkey;
+
+        if script.is_v1_p2tr() {
+            return self.taproot_signing_status();
+        }
+        if script.is_p2sh() {
+            script = match self.redeem_script {
+                Some(ref redeem_script) => redeem_script,
+                None => return SigningStatus::Unknown,
+            };
+        }
+        if script.is_v0_p2wsh() {
+            script = match self.witness_script {
+                Some(ref witness_script) => witness_script,
+                None => return SigningStatus::Unknown,
+            };
+        }
+
+        if script.is_p2pkh() || script.is_v0_p2wpkh() {
+            let present = self
+                .partial_sigs
+                .keys()
+                .filter(|pk| {
+                    *script == Script::new_p2pkh(&pk.pubkey_hash())
+                        || pk
+                            .wpubkey_hash()
+                            .map(|hash| *script == Script::new_v0_p2wpkh(&hash))
+                            .unwrap_or_default()
+                })
+                .count();
+            return SigningStatus::Signed {
+                present,
+                required: 1,
+            };
+        }
+
+        match parse_threshold(script) {
+            Some((required, keys)) => {
+                let present = keys
+                    .into_iter()
+                    .filter(|key| self.partial_sigs.keys().any(|pk| &pk.to_bytes() == key))
+                    .count();
+                SigningStatus::Signed { present, required }
+            }
+            None => SigningStatus::Unknown,
+        }
+    }
+
+    /// Collects sighash types of the signatures present in the input together
+    /// with the sighash type requested by the `PSBT_IN_SIGHASH_TYPE` field,
+    /// if any. Taproot `SIGHASH_DEFAULT` is reported as `SIGHASH_ALL`, since
+    /// both commit to the same transaction data.
+    pub fn sighash_types(&self) -> BTreeSet<PsbtSighashType> {
+        let ecdsa = self.partial_sigs.values().map(|sig| sig.hash_ty.into());
+        let schnorr = self
+            .tap_key_sig
+            .iter()
+            .chain(self.tap_script_sigs.values())
+            .map(|sig| sig.hash_ty.into());
+        ecdsa
+            .chain(schnorr)
+            .chain(self.sighash_type)
+            .map(|ty: PsbtSighashType| match ty.to_u32() {
+                0 => EcdsaSighashType::All.into(),
+                _ => ty,
+            })
+            .collect()
+    }
+
+    fn taproot_signing_status(&self) -> SigningStatus {
+        if self.tap_key_sig.is_some() {
+            return SigningStatus::Signed {
+                present: 1,
+                required: 1,
+            };
+        }
+
+        let key_path = (0usize, 1usize);
+        let best = self
+            .tap_scripts
+            .values()
+            .filter(|(_, leaf_version)| *leaf_version == LeafVersion::TapScript)
+            .filter_map(|(script, leaf_version)| {
+                let leaf_hash = TapLeafHash::from_script(script, *leaf_version);
+                let (required, keys) = parse_threshold(script)?;
+                let present = keys
+                    .into_iter()
+                    .filter_map(|key| XOnlyPublicKey::from_slice(key).ok())
+                    .filter(|pk| self.tap_script_sigs.contains_key(&(*pk, leaf_hash)))
+                    .count();
+                Some((present, required))
+            })
+            .chain([key_path])
+            .max_by_key(|(present, required)| {
+                (Reverse(required.saturating_sub(*present)), *present)
+            })
+            .unwrap_or(key_path);
+
+        SigningStatus::Signed {
+            present: best.0,
+            required: best.1,
+        }
+    }
+}
+
+/// Parses script consisting of a single-key `OP_CHECKSIG`, bare
+/// `OP_CHECKMULTISIG` or tapscript `multi_a` spending condition, returning
+/// the threshold value and the data of all public keys from the script.
+pub(super) fn parse_threshold(script: &Script) -> Option<(usize, Vec<&[u8]>)> {
+    let instructions = script.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+    match instructions.as_slice() {
+        [Instruction::PushBytes(key), Instruction::Op(op)] if *op == OP_CHECKSIG => {
+            Some((1, vec![key]))
+        }
+        [Instruction::Op(m), keys @ .., Instruction::Op(n), Instruction::Op(op)]
+            if *op == OP_CHECKMULTISIG =>
+        {
+            let (m, n) = (pushnum(*m)?, pushnum(*n)?);
+            let keys = keys
+                .iter()
+                .map(|instruction| match instruction {
+                    Instruction::PushBytes(key) => Some(*key),
+                    Instruction::Op(_) => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+            (keys.len() == n && m <= n).then(|| (m, keys))
+        }
+        [pairs @ .., threshold, Instruction::Op(op)] if *op == OP_NUMEQUAL => {
+            let threshold = match threshold {
+                Instruction::Op(op) => pushnum(*op)?,
+                Instruction::PushBytes(data) => read_scriptint(data).ok()?.try_into().ok()?,
+            };
+            let keys = pairs
+                .chunks(2)
+                .enumerate()
+                .map(|(no, pair)| match pair {
+                    [Instruction::PushBytes(key), Instruction::Op(op)]
+                        if (no == 0 && *op == OP_CHECKSIG) || (no > 0 && *op == OP_CHECKSIGADD) =>
+                    {
+                        Some(*key)
+                    }
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+            (!keys.is_empty() && threshold <= keys.len()).then(|| (threshold, keys))
+        }
+        _ => None,
+    }
+}
+
+fn pushnum(op: Opcode) -> Option<usize> {
+    let code = op.into_u8();
+    (OP_PUSHNUM_1.into_u8()..=OP_PUSHNUM_16.into_u8())
+        .contains(&code)
+        .then(|| (code - OP_PUSHNUM_1.into_u8() + 1) as usize)
+}
+
+#[cfg(test)]
+mod test {
+    use bitcoin::blockdata::script::Builder;
+    use bitcoin::secp256k1::{Message, SecretKey, SECP256K1};
+    use bitcoin::{EcdsaSig, PublicKey, SchnorrSig, SchnorrSighashType, TxOut};
+
+    use super::*;
+
+    fn seckey(no: u8) -> SecretKey { SecretKey::from_slice(&[no; 32]).unwrap() }
+
+    fn pubkey(no: u8) -> PublicKey {
+        PublicKey::new(bitcoin::secp256k1::PublicKey::from_secret_key(
+            SECP256K1,
+            &seckey(no),
+        ))
+    }
+
+    fn ecdsa_sig(no: u8) -> EcdsaSig {
+        let msg = Message::from_slice(&[0xAB; 32]).unwrap();
+        EcdsaSig::sighash_all(SECP256K1.sign_ecdsa(&msg, &seckey(no)))
+    }
+
+    fn input(script_pubkey: Script) -> Input {
+        Input {
+            witness_utxo: Some(TxOut {
+                value: 10_000,
+                script_pubkey,
+            }),
+            ..Input::default()
+        }
+    }
+
+    #[test]
+    fn multisig_status() {
+        let witness_script = Builder::new()
+            .push_int(2)
+            .push_key(&pubkey(1))
+            .push_key(&pubkey(2))
+            .push_key(&pubkey(3))
+            .push_int(3)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script();
+        let mut input = input(witness_script.to_v0_p2wsh());
+        assert_eq!(input.signing_status(), SigningStatus::Unknown);
+
+        input.witness_script = Some(witness_script);
+        assert_eq!(input.signing_status(), SigningStatus::Signed {
+            present: 0,
+            required: 2
+        });
+
+        input.partial_sigs.insert(pubkey(2), ecdsa_sig(2));
+        // Signatures with keys not present in the script must be ignored
+        input.partial_sigs.insert(pubkey(4), ecdsa_sig(4));
+        let mut psbt = Psbt {
+            inputs: vec![input],
+            ..Psbt::default()
+        };
+        assert_eq!(psbt.inputs[0].signing_status(), SigningStatus::Signed {
+            present: 1,
+            required: 2
+        });
+        assert!(!psbt.is_complete());
+
+        psbt.inputs[0].partial_sigs.insert(pubkey(3), ecdsa_sig(3));
+        assert_eq!(psbt.inputs[0].signing_status(), SigningStatus::Signed {
+            present: 2,
+            required: 2
+        });
+        assert!(psbt.is_complete());
+    }
+
+    #[test]
+    fn single_key_status() {
+        let pk = pubkey(1);
+        let mut input = input(Script::new_v0_p2wpkh(&pk.wpubkey_hash().unwrap()));
+        assert!(!input.signing_status().is_complete());
+        input.partial_sigs.insert(pk, ecdsa_sig(1));
+        assert!(input.signing_status().is_complete());
+
+        input.partial_sigs.clear();
+        input.final_script_witness = Some(bitcoin::Witness::default());
+        assert_eq!(input.signing_status(), SigningStatus::Finalized);
+    }
+
+    #[test]
+    fn taproot_status() {
+        let keys = (1..=3)
+            .map(|no| XOnlyPublicKey::from(pubkey(no).inner))
+            .collect::<Vec<_>>();
+        let sig = SchnorrSig {
+            sig: SECP256K1.sign_schnorr_no_aux_rand(
+                &Message::from_slice(&[0xAB; 32]).unwrap(),
+                &bitcoin::KeyPair::from_secret_key(SECP256K1, seckey(1)),
+            ),
+            hash_ty: SchnorrSighashType::Default,
+        };
+        let leaf_script = Builder::new()
+            .push_slice(&keys[0].serialize())
+            .push_opcode(OP_CHECKSIG)
+            .push_slice(&keys[1].serialize())
+            .push_opcode(OP_CHECKSIGADD)
+            .push_slice(&keys[2].serialize())
+            .push_opcode(OP_CHECKSIGADD)
+            .push_int(2)
+            .push_opcode(OP_NUMEQUAL)
+            .into_script();
+        assert_eq!(
+            parse_threshold(&leaf_script).map(|(k, keys)| (k, keys.len())),
+            Some((2, 3))
+        );
+
+        let builder = bitcoin::util::taproot::TaprootBuilder::new()
+            .add_leaf(0, leaf_script.clone())
+            .unwrap();
+        let spend_info = builder.finalize(SECP256K1, keys[0]).unwrap();
+        let control_block = spend_info
+            .control_block(&(leaf_script.clone(), LeafVersion::TapScript))
+            .unwrap();
+        let leaf_hash = TapLeafHash::from_script(&leaf_script, LeafVersion::TapScript);
+
+        let mut input = input(Script::new_v1_p2tr_tweaked(spend_info.output_key()));
+        assert_eq!(input.signing_status(), SigningStatus::Signed {
+            present: 0,
+            required: 1
+        });
+
+        input
+            .tap_scripts
+            .insert(control_block, (leaf_script, LeafVersion::TapScript));
+        input.tap_script_sigs.insert((keys[0], leaf_hash), sig);
+        assert_eq!(input.signing_status(), SigningStatus::Signed {
+            present: 1,
+            required: 2
+        });
+        input.tap_script_sigs.insert((keys[2], leaf_hash), sig);
+        assert!(input.signing_status().is_complete());
+
+        input.tap_script_sigs.clear();
+        input.tap_key_sig = Some(sig);
+        assert!(input.signing_status().is_complete());
+    }
+
+    #[test]
+    fn mixed_sighash() {
+        let mut psbt = Psbt {
+            inputs: vec![Input::default(), Input::default()],
+            ..Psbt::default()
+        };
+        assert!(psbt.sighash_types().is_empty());
+        assert!(!psbt.has_mixed_sighash());
+
+        psbt.inputs[0].partial_sigs.insert(pubkey(1), ecdsa_sig(1));
+        psbt.inputs[1].tap_key_sig = Some(SchnorrSig {
+            sig: bitcoin::secp256k1::schnorr::Signature::from_slice(&[1u8; 64]).unwrap(),
+            hash_ty: SchnorrSighashType::Default,
+        });
+        assert_eq!(psbt.sighash_types(), bset![EcdsaSighashType::All.into()]);
+        assert!(!psbt.has_mixed_sighash());
+
+        let mut sig = ecdsa_sig(2);
+        sig.hash_ty = EcdsaSighashType::Single;
+        psbt.inputs[0].partial_sigs.insert(pubkey(2), sig);
+        assert_eq!(psbt.sighash_types(), bset![
+            EcdsaSighashType::All.into(),
+            EcdsaSighashType::Single.into()
+        ]);
+        assert!(psbt.has_mixed_sighash());
+    }
+}
@@ -0,0 +1,319 @@
+// Descriptor wallet library extending bitcoin & miniscript functionality
+// by LNP/BP Association (https://lnp-bp.org)
+// Written in 2020-2022 by
+//     Dr. Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the Apache-2.0 License
+// along with this software.
+// If not, see <https://opensource.org/licenses/Apache-2.0>.
+
+use bitcoin::{SchnorrSighashType, Script, TxIn, Witness};
+
+use crate::{FinalizedTxError, Input, Psbt};
+
+/// Size of a DER-encoded ECDSA signature with a sighash type byte, at its
+/// maximum: 72 bytes of the DER encoding plus a single sighash type byte.
+const ECDSA_SIG_LEN: usize = 73;
+/// Size of a compressed public key.
+const PUBKEY_LEN: usize = 33;
+/// Size of a BIP-340 signature with the default sighash type; other sighash
+/// types take an extra byte.
+const SCHNORR_SIG_LEN: usize = 64;
+
+/// Summary of the PSBT transaction, aggregating its amounts, size and signing
+/// status (see [`Psbt::summary`]).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PsbtSummary {
+    /// Number of transaction inputs.
+    pub input_count: usize,
+
+    /// Number of transaction outputs.
+    pub output_count: usize,
+
+    /// Sum of the amounts spent by the inputs, if the spent outputs are known
+    /// for all of the inputs.
+    pub input_value: Option<u64>,
+
+    /// Sum of the output amounts.
+    pub output_value: u64,
+
+    /// Transaction fee, if the input value is known and is not less than the
+    /// output value.
+    pub fee: Option<u64>,
+
+    /// Estimated virtual size of the signed transaction (see
+    /// [`Psbt::estimate_vsize`]).
+    pub vsize: Option<usize>,
+
+    /// Fee rate in satoshis per virtual byte, if both fee and virtual size are
+    /// known.
+    pub feerate: Option<f64>,
+
+    /// Whether some of the inputs spend taproot outputs.
+    pub has_taproot_inputs: bool,
+
+    /// Whether all of the inputs are finalized.
+    pub is_finalized: bool,
+
+    /// Whether all of the inputs have enough signatures to be finalized (see
+    /// [`Psbt::is_complete`]).
+    pub is_complete: bool,
+}
+
+impl Psbt {
+    /// Summarizes the PSBT for displaying it to the user. Values which can't
+    /// be computed because of missing information (like spent outputs) are
+    /// reported as `None`.
+    pub fn summary(&self) -> PsbtSummary {
+        let input_value = self
+            .inputs
+            .iter()
+            .map(|input| input.input_prevout().ok().map(|prevout| prevout.value))
+            .sum::<Option<u64>>();
+        let output_value = self.outputs.iter().map(|output| output.amount).sum();
+        let fee = self.fee().ok();
+        let vsize = self.estimate_vsize();
+        PsbtSummary {
+            input_count: self.inputs.len(),
+            output_count: self.outputs.len(),
+            input_value,
+            output_value,
+            fee,
+            vsize,
+            feerate: fee.zip(vsize).map(|(fee, vsize)| fee as f64 / vsize as f64),
+            has_taproot_inputs: self.inputs.iter().any(Input::is_taproot),
+            is_finalized: self.is_finalized(),
+            is_complete: self.is_complete(),
+        }
+    }
+
+    /// Detects whether all of the PSBT inputs are finalized.
+    #[inline]
+    pub fn is_finalized(&self) -> bool { self.inputs.iter().all(Input::is_finalized) }
+
+    /// Estimates virtual size of the signed transaction. Finalized inputs are
+    /// measured with their final `scriptSig` and witness; for other inputs the
+    /// size of satisfaction is estimated for P2PKH, P2WPKH, P2WPKH-in-P2SH
+    /// and taproot key path spendings. Returns `None` if some of the inputs
+    /// use other spending conditions or lack information about the spent
+    /// output.
+    pub fn estimate_vsize(&self) -> Option<usize> {
+        let mut tx = self.to_unsigned_tx();
+        for (txin, input) in tx.input.iter_mut().zip(&self.inputs) {
+            *txin = input.estimate_signed_txin()?;
+        }
+        Some(tx.vsize())
+    }
+
+    /// Computes weight of the finalized transaction in weight units. Unlike
+    /// [`Psbt::estimate_vsize`], the weight is measured from the actual final
+    /// `scriptSig`s and witnesses of the inputs.
+    ///
+    /// Errors with [`FinalizedTxError::NotFinalized`] if some of the inputs are
+    /// not finalized.
+    pub fn finalized_weight(&self) -> Result<usize, FinalizedTxError> {
+        self.extract_finalized_tx().map(|tx| tx.weight())
+    }
+
+    /// Computes virtual size of the finalized transaction (see
+    /// [`Psbt::finalized_weight`]).
+    pub fn finalized_vsize(&self) -> Result<usize, FinalizedTxError> {
+        self.extract_finalized_tx().map(|tx| tx.vsize())
+    }
+
+    /// Computes fee rate of the finalized transaction in satoshis per virtual
+    /// byte (see [`Psbt::finalized_weight`]).
+    ///
+    /// Errors if some of the inputs are not finalized or if the fee can't be
+    /// computed.
+    pub fn finalized_feerate(&self) -> Result<f64, FinalizedTxError> {
+        let vsize = self.finalized_vsize()?;
+        Ok(self.fee()? as f64 / vsize as f64)
+    }
+}
+
+impl Input {
+    /// Detects whether the input is finalized, i.e. has final `scriptSig` or
+    /// witness.
+    #[inline]
+    pub fn is_finalized(&self) -> bool {
+        self.final_script_sig.is_some() || self.final_script_witness.is_some()
+    }
+
+    /// Detects whether the input spends taproot output.
+    pub fn is_taproot(&self) -> bool {
+        match self.input_prevout() {
+            Ok(prevout) => prevout.script_pubkey.is_v1_p2tr(),
+            Err(_) => self.tap_internal_key.is_some(),
+        }
+    }
+
+    fn estimate_signed_txin(&self) -> Option<TxIn> {
+        if self.is_finalized() {
+            return Some(self.extract_signed_txin());
+        }
+        let script = &self.input_prevout().ok()?.script_pubkey;
+        let ecdsa_witness = || Witness::from_vec(vec![vec![0; ECDSA_SIG_LEN], vec![0; PUBKEY_LEN]]);
+        let nested_p2wpkh = matches!(self.redeem_script, Some(ref s) if s.is_v0_p2wpkh());
+        let key_path = self.tap_key_sig.is_some() || self.tap_scripts.is_empty();
+        let (script_sig, witness) = if script.is_p2pkh() {
+            let len = 1 + ECDSA_SIG_LEN + 1 + PUBKEY_LEN;
+            (Script::from(vec![0; len]), Witness::default())
+        } else if script.is_v0_p2wpkh() {
+            (Script::default(), ecdsa_witness())
+        } else if script.is_p2sh() && nested_p2wpkh {
+            // Push of the 22-byte P2WPKH redeem script
+            (Script::from(vec![0; 23]), ecdsa_witness())
+        } else if script.is_v1_p2tr() && key_path {
+            // Non-default sighash type is appended to the signature
+            let len = match self.schnorr_hash_ty() {
+                Ok(SchnorrSighashType::Default) => SCHNORR_SIG_LEN,
+                _ => SCHNORR_SIG_LEN + 1,
+            };
+            let witness = Witness::from_vec(vec![vec![0; len]]);
+            (Script::default(), witness)
+        } else {
+            return None;
+        };
+        Some(TxIn {
+            script_sig,
+            witness,
+            ..self.to_unsigned_txin()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::str::FromStr;
+
+    use bitcoin::{OutPoint, Transaction, TxOut};
+
+    use super::*;
+    use crate::PsbtVersion;
+
+    fn psbt() -> Psbt {
+        let p2wpkh = Script::from_str("0014d0c59903c5bac2868760e90fd521a4665aa76520").unwrap();
+        let p2tr = Script::from_str(
+            "51200f0c8db753acbd17343a39c2f3f4e35e4be6da749f9e35137ab220e7b238a667",
+        )
+        .unwrap();
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![
+                TxIn {
+                    previous_output: OutPoint::new(Default::default(), 0),
+                    ..TxIn::default()
+                },
+                TxIn {
+                    previous_output: OutPoint::new(Default::default(), 1),
+                    ..TxIn::default()
+                },
+            ],
+            output: vec![TxOut {
+                value: 29_000,
+                script_pubkey: p2wpkh.clone(),
+            }],
+        };
+        let mut psbt = Psbt::with(tx, PsbtVersion::V2).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: 10_000,
+            script_pubkey: p2wpkh,
+        });
+        psbt.inputs[1].witness_utxo = Some(TxOut {
+            value: 20_000,
+            script_pubkey: p2tr,
+        });
+        psbt
+    }
+
+    #[test]
+    fn summary() {
+        let mut psbt = psbt();
+        assert_eq!(psbt.summary(), PsbtSummary {
+            input_count: 2,
+            output_count: 1,
+            input_value: Some(30_000),
+            output_value: 29_000,
+            fee: Some(1_000),
+            vsize: Some(168),
+            feerate: Some(1_000.0 / 168.0),
+            has_taproot_inputs: true,
+            is_finalized: false,
+            is_complete: false,
+        });
+
+        // Signature with non-default sighash type takes an extra byte
+        let sig_len = |input: &Input| {
+            let witness = input.estimate_signed_txin().unwrap().witness;
+            witness.to_vec()[0].len()
+        };
+        assert_eq!(sig_len(&psbt.inputs[1]), SCHNORR_SIG_LEN);
+        psbt.inputs[1].sighash_type = Some(SchnorrSighashType::All.into());
+        assert_eq!(sig_len(&psbt.inputs[1]), SCHNORR_SIG_LEN + 1);
+        psbt.inputs[1].sighash_type = None;
+
+        psbt.inputs[0].final_script_witness =
+            Some(Witness::from_vec(vec![vec![0; 71], vec![0; PUBKEY_LEN]]));
+        psbt.inputs[1].final_script_witness =
+            Some(Witness::from_vec(vec![vec![0; SCHNORR_SIG_LEN]]));
+        let summary = psbt.summary();
+        assert_eq!(summary.vsize, Some(167));
+        assert!(summary.is_finalized);
+        assert!(summary.is_complete);
+
+        psbt.inputs[1].witness_utxo = None;
+        psbt.inputs[1].final_script_witness = None;
+        let summary = psbt.summary();
+        assert_eq!(summary.input_value, None);
+        assert_eq!(summary.fee, None);
+        assert_eq!(summary.vsize, None);
+        assert_eq!(summary.feerate, None);
+        assert!(!summary.has_taproot_inputs);
+        assert!(!summary.is_finalized);
+        assert!(!summary.is_complete);
+    }
+
+    #[test]
+    fn finalized_weight() {
+        let mut psbt = psbt();
+        assert_eq!(
+            psbt.finalized_weight(),
+            Err(FinalizedTxError::NotFinalized(0))
+        );
+        psbt.inputs[0].final_script_witness =
+            Some(Witness::from_vec(vec![vec![0; 71], vec![0; PUBKEY_LEN]]));
+        assert_eq!(
+            psbt.finalized_vsize(),
+            Err(FinalizedTxError::NotFinalized(1))
+        );
+        psbt.inputs[1].final_script_witness =
+            Some(Witness::from_vec(vec![vec![0; SCHNORR_SIG_LEN]]));
+
+        // Non-witness data: version (4), input count (1), two inputs with empty
+        // scriptSig (2 * 41), output count (1), P2WPKH output (31), locktime (4)
+        let base_size = 4 + 1 + 2 * 41 + 1 + 31 + 4;
+        // Witness data: segwit marker and flag (2), P2WPKH witness with 71-byte
+        // signature (1 + 72 + 34), taproot key path witness (1 + 65)
+        let witness_size = 2 + (1 + 72 + 34) + (1 + 65);
+        let weight = base_size * 4 + witness_size;
+        assert_eq!(psbt.finalized_weight(), Ok(weight));
+        assert_eq!(psbt.finalized_vsize(), Ok((weight + 3) / 4));
+        assert_eq!(
+            psbt.finalized_feerate(),
+            Ok(1_000.0 / ((weight + 3) / 4) as f64)
+        );
+
+        psbt.inputs[1].witness_utxo = None;
+        assert!(matches!(
+            psbt.finalized_feerate(),
+            Err(FinalizedTxError::Fee(_))
+        ));
+    }
+}
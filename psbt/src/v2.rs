@@ -0,0 +1,521 @@
+// Descriptor wallet library extending bitcoin & miniscript functionality
+// by LNP/BP Association (https://lnp-bp.org)
+// Written in 2020-2022 by
+//     Dr. Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the Apache-2.0 License
+// along with this software.
+// If not, see <https://opensource.org/licenses/Apache-2.0>.
+
+//! Explicit handling of PSBT version field and BIP-370 (PSBTv2) encoding.
+//!
+//! PSBTv2-specific per-input and per-output fields are not known to the
+//! underlying BIP-174 map encoders from `bitcoin` crate, so they are passed
+//! through them as unknown key-value pairs.
+
+use std::collections::BTreeSet;
+use std::io::{self, Cursor};
+
+use bitcoin::consensus::encode::{self, Decodable, Encodable, VarInt};
+use bitcoin::{OutPoint, Script, TxIn, TxOut, Txid};
+use descriptors::locks::{LockHeight, LockTimestamp, SeqNo};
+
+use crate::global::{
+    PSBT_GLOBAL_FALLBACK_LOCKTIME, PSBT_GLOBAL_INPUT_COUNT, PSBT_GLOBAL_OUTPUT_COUNT,
+    PSBT_GLOBAL_TX_MODIFIABLE, PSBT_GLOBAL_TX_VERSION, PSBT_GLOBAL_UNSIGNED_TX,
+    PSBT_GLOBAL_VERSION, PSBT_GLOBAL_XPUB,
+};
+use crate::v0::{InputV0, OutputV0};
+use crate::{raw, DecodeError, Input, Output, Psbt, PsbtVersion};
+
+const PSBT_IN_PREVIOUS_TXID: u8 = 0x0e;
+const PSBT_IN_OUTPUT_INDEX: u8 = 0x0f;
+const PSBT_IN_SEQUENCE: u8 = 0x10;
+const PSBT_IN_REQUIRED_TIME_LOCKTIME: u8 = 0x11;
+const PSBT_IN_REQUIRED_HEIGHT_LOCKTIME: u8 = 0x12;
+const PSBT_OUT_AMOUNT: u8 = 0x03;
+const PSBT_OUT_SCRIPT: u8 = 0x04;
+
+fn key(type_value: u8) -> raw::Key {
+    raw::Key {
+        type_value,
+        key: vec![],
+    }
+}
+
+fn decode_map(d: &mut impl io::Read) -> Result<Vec<raw::Pair>, encode::Error> {
+    let mut keys = BTreeSet::new();
+    let mut pairs = vec![];
+    loop {
+        match raw::Pair::consensus_decode(&mut *d) {
+            Ok(pair) => {
+                if !keys.insert(pair.key.clone()) {
+                    return Err(crate::Error::DuplicateKey(pair.key).into());
+                }
+                pairs.push(pair);
+            }
+            Err(encode::Error::Psbt(crate::Error::NoMorePairs)) => return Ok(pairs),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn decode_globals(d: &mut impl io::Read) -> Result<Vec<raw::Pair>, encode::Error> {
+    let magic: [u8; 4] = Decodable::consensus_decode(&mut *d)?;
+    if *b"psbt" != magic {
+        return Err(crate::Error::InvalidMagic.into());
+    }
+    if 0xff_u8 != u8::consensus_decode(&mut *d)? {
+        return Err(crate::Error::InvalidSeparator.into());
+    }
+    decode_map(d)
+}
+
+/// Decodes value which must be consumed by the decoder entirely.
+fn decode_value<T: Decodable>(pair: &raw::Pair) -> Result<T, encode::Error> {
+    if !pair.key.key.is_empty() {
+        return Err(crate::Error::InvalidKey(pair.key.clone()).into());
+    }
+    encode::deserialize(&pair.value)
+}
+
+/// Detects version of the serialized PSBT from `PSBT_GLOBAL_VERSION` field,
+/// which defaults to zero if absent.
+///
+/// # Errors
+///
+/// Errors if the version is not supported, or if the global map is not
+/// consistent with the declared version: PSBTv0 must and PSBTv2 must not have
+/// a global unsigned transaction.
+pub(crate) fn decode_version(bytes: &[u8]) -> Result<PsbtVersion, DecodeError> {
+    let globals = decode_globals(&mut Cursor::new(bytes))?;
+    let version = globals
+        .iter()
+        .find(|pair| pair.key.type_value == PSBT_GLOBAL_VERSION)
+        .map(decode_value::<u32>)
+        .transpose()?
+        .unwrap_or_default();
+    let has_unsigned_tx = globals
+        .iter()
+        .any(|pair| pair.key.type_value == PSBT_GLOBAL_UNSIGNED_TX);
+    match (version, has_unsigned_tx) {
+        (0, true) => Ok(PsbtVersion::V0),
+        (0, false) => Err(DecodeError::Invalid(
+            crate::Error::MustHaveUnsignedTx.into(),
+        )),
+        (2, false) => Ok(PsbtVersion::V2),
+        (2, true) => Err(DecodeError::Invalid(encode::Error::ParseFailed(
+            "PSBT version 2 must not contain global unsigned transaction",
+        ))),
+        (version, _) => Err(DecodeError::UnsupportedVersion(version)),
+    }
+}
+
+/// Decodes PSBT serialized according to BIP-370 (PSBTv2), returning it
+/// together with the number of consumed bytes.
+pub(crate) fn deserialize_partial(bytes: &[u8]) -> Result<(Psbt, usize), encode::Error> {
+    let mut cursor = Cursor::new(bytes);
+    let globals = decode_globals(&mut cursor)?;
+    let (mut psbt, input_count, output_count) = decode_global_pairs(globals)?;
+
+    // Each of the input and output maps takes at least a single byte of its
+    // separator, so larger counts can't be satisfied by the remaining data
+    let remaining = bytes.len() as u64 - cursor.position();
+    let map_count = (input_count as u64).checked_add(output_count as u64);
+    if map_count.map(|count| count > remaining).unwrap_or(true) {
+        return Err(DecodeError::TruncatedInput.into());
+    }
+
+    for index in 0..input_count {
+        psbt.inputs.push(decode_input(index, &mut cursor)?);
+    }
+    for index in 0..output_count {
+        psbt.outputs.push(decode_output(index, &mut cursor)?);
+    }
+
+    Ok((psbt, cursor.position() as usize))
+}
+
+/// Constructs PSBTv2 global data, having no inputs and outputs, from the
+/// decoded global map key-value pairs. Returns it together with the number of
+/// inputs and outputs declared by `PSBT_GLOBAL_INPUT_COUNT` and
+/// `PSBT_GLOBAL_OUTPUT_COUNT`.
+pub(crate) fn decode_global_pairs(
+    globals: Vec<raw::Pair>,
+) -> Result<(Psbt, usize, usize), encode::Error> {
+    let mut psbt = Psbt {
+        psbt_version: PsbtVersion::V2,
+        ..Psbt::default()
+    };
+    let mut tx_version = None;
+    let mut input_count = None;
+    let mut output_count = None;
+    for pair in globals {
+        match pair.key.type_value {
+            PSBT_GLOBAL_INPUT_COUNT => {
+                input_count = Some(decode_value::<VarInt>(&pair)?.0 as usize);
+            }
+            PSBT_GLOBAL_OUTPUT_COUNT => {
+                output_count = Some(decode_value::<VarInt>(&pair)?.0 as usize);
+            }
+            // There is no typed support for transaction modification flags
+            // yet, so we keep them as an unknown key
+            PSBT_GLOBAL_TX_MODIFIABLE => {
+                psbt.unknown.insert(pair.key, pair.value);
+            }
+            // Transaction version is kept as-is, even if it does not fit into
+            // `i32`, for the serialization roundtrip to be lossless
+            PSBT_GLOBAL_TX_VERSION => {
+                let version = decode_value::<u32>(&pair)?;
+                psbt.tx_version = version;
+                tx_version = Some(version);
+            }
+            _ => {
+                psbt.set_global(pair.key, pair.value).map_err(|_| {
+                    encode::Error::ParseFailed("invalid PSBT global key-value pair")
+                })?;
+            }
+        }
+    }
+    match (tx_version, input_count, output_count) {
+        (Some(_), Some(input_count), Some(output_count)) => Ok((psbt, input_count, output_count)),
+        _ => Err(encode::Error::ParseFailed(
+            "PSBT version 2 requires transaction version, input and output counts",
+        )),
+    }
+}
+
+/// Decodes PSBTv2 input map, which must be placed at `index` position.
+pub(crate) fn decode_input(index: usize, d: &mut impl io::Read) -> Result<Input, encode::Error> {
+    let mut v0 = InputV0::consensus_decode(d)?;
+    let mut take = |type_value: u8| -> Result<Option<u32>, encode::Error> {
+        v0.unknown
+            .remove(&key(type_value))
+            .map(|value| encode::deserialize::<u32>(&value))
+            .transpose()
+    };
+    let vout = take(PSBT_IN_OUTPUT_INDEX)?;
+    let sequence = take(PSBT_IN_SEQUENCE)?;
+    let time_locktime = take(PSBT_IN_REQUIRED_TIME_LOCKTIME)?
+        .map(LockTimestamp::try_from)
+        .transpose()
+        .map_err(|_| encode::Error::ParseFailed("invalid PSBT input required time lock"))?;
+    let height_locktime = take(PSBT_IN_REQUIRED_HEIGHT_LOCKTIME)?
+        .map(LockHeight::try_from)
+        .transpose()
+        .map_err(|_| encode::Error::ParseFailed("invalid PSBT input required height lock"))?;
+    let txid = v0
+        .unknown
+        .remove(&key(PSBT_IN_PREVIOUS_TXID))
+        .map(|value| encode::deserialize::<Txid>(&value))
+        .transpose()?;
+    let (txid, vout) = match (txid, vout) {
+        (Some(txid), Some(vout)) => (txid, vout),
+        _ => {
+            return Err(encode::Error::ParseFailed(
+                "PSBT version 2 input requires previous txid and output index",
+            ))
+        }
+    };
+    let txin = TxIn {
+        previous_output: OutPoint::new(txid, vout),
+        sequence: sequence.unwrap_or(u32::MAX),
+        ..TxIn::default()
+    };
+    let mut input = Input::with(index, v0, txin);
+    // Unlike PSBTv0, PSBTv2 distinguishes an explicit final sequence number
+    // from an absent one, which must be preserved for the serialization
+    // roundtrip to be lossless
+    input.sequence_number = sequence.map(SeqNo::from_consensus);
+    input.required_time_locktime = time_locktime;
+    input.required_height_locktime = height_locktime;
+    Ok(input)
+}
+
+/// Decodes PSBTv2 output map, which must be placed at `index` position.
+pub(crate) fn decode_output(index: usize, d: &mut impl io::Read) -> Result<Output, encode::Error> {
+    let mut v0 = OutputV0::consensus_decode(d)?;
+    let amount = v0
+        .unknown
+        .remove(&key(PSBT_OUT_AMOUNT))
+        .map(|value| encode::deserialize::<u64>(&value))
+        .transpose()?;
+    let script = v0.unknown.remove(&key(PSBT_OUT_SCRIPT)).map(Script::from);
+    let txout = match (amount, script) {
+        (Some(value), Some(script_pubkey)) => TxOut {
+            value,
+            script_pubkey,
+        },
+        _ => {
+            return Err(encode::Error::ParseFailed(
+                "PSBT version 2 output requires amount and script",
+            ))
+        }
+    };
+    Ok(Output::with(index, v0, txout)?)
+}
+
+/// Serializes PSBT according to BIP-370 (PSBTv2).
+pub(crate) fn serialize(psbt: &Psbt) -> Vec<u8> {
+    let mut globals = vec![raw::Pair {
+        key: key(PSBT_GLOBAL_TX_VERSION),
+        value: psbt.tx_version.to_le_bytes().to_vec(),
+    }];
+    if let Some(lock_time) = psbt.fallback_locktime {
+        globals.push(raw::Pair {
+            key: key(PSBT_GLOBAL_FALLBACK_LOCKTIME),
+            value: lock_time.into_consensus().to_le_bytes().to_vec(),
+        });
+    }
+    globals.push(raw::Pair {
+        key: key(PSBT_GLOBAL_INPUT_COUNT),
+        value: encode::serialize(&VarInt(psbt.inputs.len() as u64)),
+    });
+    globals.push(raw::Pair {
+        key: key(PSBT_GLOBAL_OUTPUT_COUNT),
+        value: encode::serialize(&VarInt(psbt.outputs.len() as u64)),
+    });
+    for (xpub, (fingerprint, derivation)) in &psbt.xpub {
+        let mut value = fingerprint.as_bytes().to_vec();
+        for index in derivation {
+            value.extend(u32::from(*index).to_le_bytes());
+        }
+        globals.push(raw::Pair {
+            key: raw::Key {
+                type_value: PSBT_GLOBAL_XPUB,
+                key: xpub.encode().to_vec(),
+            },
+            value,
+        });
+    }
+    globals.push(raw::Pair {
+        key: key(PSBT_GLOBAL_VERSION),
+        value: PsbtVersion::V2.as_u32().to_le_bytes().to_vec(),
+    });
+    for (key, value) in &psbt.proprietary {
+        globals.push(raw::Pair {
+            key: key.to_key(),
+            value: value.clone(),
+        });
+    }
+    for (key, value) in &psbt.unknown {
+        globals.push(raw::Pair {
+            key: key.clone(),
+            value: value.clone(),
+        });
+    }
+
+    let mut data = b"psbt\xff".to_vec();
+    for pair in globals {
+        pair.consensus_encode(&mut data)
+            .expect("in-memory encoders don't error");
+    }
+    data.push(0x00);
+
+    for input in &psbt.inputs {
+        let (mut v0, txin) = input.clone().split();
+        let outpoint = txin.previous_output;
+        v0.unknown.insert(
+            key(PSBT_IN_PREVIOUS_TXID),
+            encode::serialize(&outpoint.txid),
+        );
+        v0.unknown.insert(
+            key(PSBT_IN_OUTPUT_INDEX),
+            outpoint.vout.to_le_bytes().to_vec(),
+        );
+        if let Some(seq_no) = input.sequence_number {
+            v0.unknown.insert(
+                key(PSBT_IN_SEQUENCE),
+                seq_no.into_consensus().to_le_bytes().to_vec(),
+            );
+        }
+        if let Some(lock_time) = input.required_time_locktime {
+            v0.unknown.insert(
+                key(PSBT_IN_REQUIRED_TIME_LOCKTIME),
+                lock_time.into_consensus().to_le_bytes().to_vec(),
+            );
+        }
+        if let Some(lock_time) = input.required_height_locktime {
+            v0.unknown.insert(
+                key(PSBT_IN_REQUIRED_HEIGHT_LOCKTIME),
+                lock_time.into_consensus().to_le_bytes().to_vec(),
+            );
+        }
+        v0.consensus_encode(&mut data)
+            .expect("in-memory encoders don't error");
+    }
+
+    for output in &psbt.outputs {
+        let (mut v0, txout) = output.clone().split();
+        v0.unknown
+            .insert(key(PSBT_OUT_AMOUNT), txout.value.to_le_bytes().to_vec());
+        v0.unknown
+            .insert(key(PSBT_OUT_SCRIPT), txout.script_pubkey.to_bytes());
+        v0.consensus_encode(&mut data)
+            .expect("in-memory encoders don't error");
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use amplify::hex::{FromHex, ToHex};
+    use bitcoin::util::bip32::{DerivationPath, ExtendedPubKey, Fingerprint};
+    use bitcoin::Transaction;
+    use descriptors::locks::LockTime;
+
+    use super::*;
+    use crate::serialize::{Deserialize, Serialize};
+    use crate::v0::PsbtV0;
+
+    fn psbt() -> Psbt {
+        let prev_tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn::default()],
+            output: vec![TxOut {
+                value: 10_000,
+                script_pubkey: Script::from_str("0014d0c59903c5bac2868760e90fd521a4665aa76520")
+                    .unwrap(),
+            }],
+        };
+        let xpub = ExtendedPubKey::from_str(
+            "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V",
+        )
+        .unwrap();
+        Psbt {
+            psbt_version: PsbtVersion::V2,
+            tx_version: 2,
+            fallback_locktime: Some(LockTime::from_consensus(700_000)),
+            inputs: vec![Input {
+                previous_outpoint: OutPoint::new(prev_tx.txid(), 0),
+                sequence_number: Some(SeqNo::from_consensus(0xFFFF_FFFD)),
+                required_height_locktime: Some(LockHeight::try_from(710_000).unwrap()),
+                witness_utxo: Some(prev_tx.output[0].clone()),
+                ..Input::default()
+            }],
+            outputs: vec![Output::new(0, TxOut {
+                value: 9_000,
+                script_pubkey: Script::from_str("6a").unwrap(),
+            })
+            .unwrap()],
+            xpub: bmap! {
+                xpub => (
+                    Fingerprint::from(&[0x73, 0xc5, 0xda, 0x0a][..]),
+                    DerivationPath::from_str("m/84'/0'/0'").unwrap()
+                )
+            },
+            proprietary: bmap! {
+                raw::ProprietaryKey {
+                    prefix: b"TST".to_vec(),
+                    subtype: 1,
+                    key: vec![]
+                } => vec![0xFF]
+            },
+            unknown: none!(),
+        }
+    }
+
+    #[test]
+    fn v2_roundtrip() {
+        let psbt = psbt();
+        let data = psbt.serialize();
+        // PSBT_GLOBAL_VERSION key with value 2
+        assert!(data.to_hex().contains("01fb0402000000"));
+        assert_eq!(decode_version(&data).unwrap(), PsbtVersion::V2);
+        assert_eq!(Psbt::deserialize(&data).unwrap(), psbt);
+    }
+
+    #[test]
+    fn explicit_final_sequence() {
+        let mut psbt = psbt();
+        psbt.inputs[0].sequence_number = Some(SeqNo::from_consensus(u32::MAX));
+        let data = psbt.serialize();
+        // PSBT_IN_SEQUENCE key with value 0xFFFFFFFF
+        assert!(data.to_hex().contains("011004ffffffff"));
+        let deserialized = Psbt::deserialize(&data).unwrap();
+        assert_eq!(deserialized, psbt);
+        assert_eq!(deserialized.serialize(), data);
+
+        psbt.inputs[0].sequence_number = None;
+        let data = psbt.serialize();
+        assert!(!data.to_hex().contains("011004"));
+        assert_eq!(Psbt::deserialize(&data).unwrap(), psbt);
+    }
+
+    #[test]
+    fn optional_fallback_locktime() {
+        let mut psbt = psbt();
+        psbt.inputs[0].required_height_locktime = None;
+        psbt.fallback_locktime = None;
+        let data = psbt.serialize();
+        assert!(decode_globals(&mut Cursor::new(&data))
+            .unwrap()
+            .iter()
+            .all(|pair| pair.key.type_value != PSBT_GLOBAL_FALLBACK_LOCKTIME));
+        let deserialized = Psbt::deserialize(&data).unwrap();
+        assert_eq!(deserialized.fallback_locktime, None);
+        assert_eq!(deserialized.lock_time(), LockTime::default());
+
+        // PSBTv0 always has a concrete lock time, where zero maps to no
+        // fallback lock time
+        let v0 = PsbtV0::from(psbt.clone());
+        assert_eq!(v0.unsigned_tx.lock_time, 0);
+        assert_eq!(Psbt::try_from(v0).unwrap().fallback_locktime, None);
+
+        psbt.fallback_locktime = Some(LockTime::from_consensus(700_000));
+        let v0 = PsbtV0::from(psbt);
+        assert_eq!(v0.unsigned_tx.lock_time, 700_000);
+        assert_eq!(
+            Psbt::try_from(v0).unwrap().fallback_locktime,
+            Some(LockTime::from_consensus(700_000))
+        );
+    }
+
+    #[test]
+    fn v0_version_omitted() {
+        let mut psbt = psbt();
+        psbt.psbt_version = PsbtVersion::V0;
+        let data = psbt.serialize();
+        assert!(!data.to_hex().contains("01fb04"));
+        assert_eq!(decode_version(&data).unwrap(), PsbtVersion::V0);
+        assert_eq!(
+            Psbt::deserialize(&data).unwrap().psbt_version,
+            PsbtVersion::V0
+        );
+    }
+
+    #[test]
+    fn inconsistent_version() {
+        let mut psbt = psbt();
+        psbt.psbt_version = PsbtVersion::V0;
+        let v0 = psbt.serialize();
+
+        // PSBT declaring version 2 while having global unsigned transaction
+        let mut data = b"psbt\xff".to_vec();
+        data.extend(Vec::<u8>::from_hex("01fb0402000000").unwrap());
+        data.extend(&v0[5..]);
+        assert!(decode_version(&data).is_err());
+        assert!(Psbt::deserialize(&data).is_err());
+
+        // PSBT version 2 without unsigned transaction and input/output counts
+        let hex = "70736274ff01020402000000 01fb0402000000 00".replace(' ', "");
+        let data = Vec::<u8>::from_hex(&hex).unwrap();
+        assert_eq!(decode_version(&data).unwrap(), PsbtVersion::V2);
+        assert!(Psbt::deserialize(&data).is_err());
+
+        // PSBT version 0 without unsigned transaction and unsupported version
+        for hex in ["70736274ff01020402000000 00", "70736274ff01fb0401000000 00"] {
+            let data = Vec::<u8>::from_hex(&hex.replace(' ', "")).unwrap();
+            assert!(Psbt::deserialize(&data).is_err());
+        }
+    }
+}
@@ -20,9 +20,10 @@ use std::str::FromStr;
 
 use amplify::Wrapper;
 use bitcoin::hashes::{hex, Hash};
-use bitcoin::schnorr::TweakedPublicKey;
-use bitcoin::secp256k1::XOnlyPublicKey;
+use bitcoin::schnorr::{TapTweak, TweakedPublicKey};
+use bitcoin::secp256k1::{Secp256k1, Verification, XOnlyPublicKey};
 use bitcoin::util::address::{self, Payload, WitnessVersion};
+use bitcoin::util::taproot::TapBranchHash;
 use bitcoin::{
     secp256k1, Address, Network, PubkeyHash, Script, ScriptHash, WPubkeyHash, WScriptHash,
 };
@@ -173,6 +174,18 @@ impl AddressPayload {
         }
     }
 
+    /// Constructs P2TR payload from a given x-only internal key and an
+    /// optional taproot script tree merkle root. For BIP-86 single-key
+    /// wallets the merkle root must be `None`.
+    pub fn p2tr<C: Verification>(
+        secp: &Secp256k1<C>,
+        internal_key: XOnlyPublicKey,
+        merkle_root: Option<TapBranchHash>,
+    ) -> Self {
+        let (output_key, _) = internal_key.tap_tweak(secp, merkle_root);
+        AddressPayload::Taproot { output_key }
+    }
+
     /// Constructs payload from a given address. Fails on future (post-taproot)
     /// witness types with `None`.
     pub fn from_address(address: Address) -> Option<Self> { Self::from_payload(address.payload) }
@@ -504,3 +517,50 @@ impl From<bitcoin::Network> for AddressNetwork {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use bitcoin::hashes::hex::FromHex;
+    use bitcoin::util::bip32::{ChildNumber, ExtendedPubKey};
+
+    use super::*;
+
+    #[test]
+    fn bip86_p2tr_address() {
+        let secp = Secp256k1::verification_only();
+        let account_xpub = ExtendedPubKey::from_str(
+            "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ",
+        )
+        .unwrap();
+        let xpub = account_xpub
+            .derive_pub(&secp, &[
+                ChildNumber::Normal { index: 0 },
+                ChildNumber::Normal { index: 0 },
+            ])
+            .unwrap();
+        let internal_key = XOnlyPublicKey::from(xpub.public_key);
+        assert_eq!(
+            internal_key.to_string(),
+            "cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115"
+        );
+
+        let payload = AddressPayload::p2tr(&secp, internal_key, None);
+        let address = payload.into_address(Network::Bitcoin);
+        assert_eq!(
+            address.to_string(),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+        assert_eq!(address.address_type(), Some(address::AddressType::P2tr));
+        assert_eq!(
+            AddressFormat::from(address).witness_version(),
+            Some(WitnessVersion::V1)
+        );
+        assert_eq!(Payload::from(payload), Payload::WitnessProgram {
+            version: WitnessVersion::V1,
+            program: Vec::from_hex(
+                "a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"
+            )
+            .unwrap()
+        });
+    }
+}
@@ -46,7 +46,7 @@ use electrum_client::ElectrumApi;
 use miniscript::psbt::PsbtExt;
 use miniscript::{Descriptor, MiniscriptKey, TranslatePk};
 use psbt::serialize::Deserialize;
-use psbt::{construct, ProprietaryKeyDescriptor, ProprietaryKeyError, ProprietaryKeyLocation};
+use psbt::{construct, ProprietaryKeyDescriptor, ProprietaryKeyError};
 use slip132::{
     DefaultResolver, FromSlip132, KeyApplication, KeyVersion, ToSlip132, VersionResolver,
 };
@@ -645,30 +645,7 @@ impl Args {
         psbt.fallback_locktime = Some(lock_time);
 
         for key in proprietary_keys {
-            match key.location {
-                ProprietaryKeyLocation::Input(pos) if pos as usize >= psbt.inputs.len() => {
-                    return Err(ProprietaryKeyError::InputOutOfRange(pos, psbt.inputs.len()).into())
-                }
-                ProprietaryKeyLocation::Output(pos) if pos as usize >= psbt.outputs.len() => {
-                    return Err(
-                        ProprietaryKeyError::OutputOutOfRange(pos, psbt.inputs.len()).into(),
-                    )
-                }
-                ProprietaryKeyLocation::Global => {
-                    psbt.proprietary
-                        .insert(key.into(), key.value.as_ref().cloned().unwrap_or_default());
-                }
-                ProprietaryKeyLocation::Input(pos) => {
-                    psbt.inputs[pos as usize]
-                        .proprietary
-                        .insert(key.into(), key.value.as_ref().cloned().unwrap_or_default());
-                }
-                ProprietaryKeyLocation::Output(pos) => {
-                    psbt.outputs[pos as usize]
-                        .proprietary
-                        .insert(key.into(), key.value.as_ref().cloned().unwrap_or_default());
-                }
-            }
+            psbt.push_proprietary(key.clone())?;
         }
 
         fs::write(psbt_path, &psbt.serialize())?;